@@ -1,15 +1,17 @@
+use crate::component::{Component, ComponentError, Outgoing, send_request};
 use lib::{
     field::{
-        CpuStatus,
+        cores::{CoreState, TargetCoreMask},
         freq::{Freq, TargetFreq},
         power::Power,
         temp::Temp,
+        throttle::Throttle,
         usage::Usage,
     },
     proto::*,
 };
-use std::sync::{Arc, Mutex, mpsc::Sender};
-use crate::component::{Component, ComponentError};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc::UnboundedSender;
 
 #[derive(Debug)]
 pub struct Cpu {
@@ -19,11 +21,13 @@ pub struct Cpu {
     usage: Usage,
     temp: Temp,
     power: Power,
-    sender: Arc<Mutex<Sender<MsgBody>>>,
+    throttle: Throttle,
+    cores: CoreState,
+    sender: Arc<Mutex<UnboundedSender<Outgoing>>>,
 }
 
 impl Cpu {
-    pub fn new(id_num: u8, sender: Arc<Mutex<Sender<MsgBody>>>) -> Self {
+    pub fn new(id_num: u8, sender: Arc<Mutex<UnboundedSender<Outgoing>>>) -> Self {
         Self {
             id_num,
             desc: String::default(),
@@ -31,22 +35,24 @@ impl Cpu {
             usage: Usage::default(),
             temp: Temp::default(),
             power: Power::default(),
+            throttle: Throttle::default(),
+            cores: CoreState::default(),
             sender,
         }
     }
 
     pub fn set_freq(&self, target_freq: TargetFreq) {
-        let msg_packet =
-            MsgPacket::new(MsgMode::Request, None, 0, self.id_num, MsgCommand::SetFreq);
-        let payload = target_freq
-            .serialize()
+        send_request(&self.sender, self.id_num, &Request::SetFreq(target_freq))
             .expect("Failed to serialize payload");
+    }
 
-        let msg_body = MsgBody::new(msg_packet, vec![payload]);
-        let sender = self.sender.lock().unwrap();
-        sender
-            .send(msg_body)
-            .expect("Failed to send message to the channel");
+    pub fn set_core_mask(&self, target_mask: TargetCoreMask) {
+        send_request(
+            &self.sender,
+            self.id_num,
+            &Request::SetCoreMask(target_mask),
+        )
+        .expect("Failed to serialize payload");
     }
 
     pub fn get_freq(&self) -> &Freq {
@@ -61,6 +67,12 @@ impl Cpu {
     pub fn get_power(&self) -> &Power {
         &self.power
     }
+    pub fn get_throttle(&self) -> &Throttle {
+        &self.throttle
+    }
+    pub fn get_cores(&self) -> &CoreState {
+        &self.cores
+    }
     pub fn get_desc(&self) -> &String {
         &self.desc
     }
@@ -70,43 +82,21 @@ type Result<T> = std::result::Result<T, ComponentError>;
 
 impl Component for Cpu {
     fn refresh_status(&mut self) -> Result<()> {
-        let msg_packet = MsgPacket::new(
-            MsgMode::Request,
-            None,
-            0,
-            self.id_num,
-            MsgCommand::GetStatus,
-        );
-        let msg_body = MsgBody::new(msg_packet, vec![]);
-        let sender = self.sender.lock().unwrap();
-        sender
-            .send(msg_body)
-            .expect("Failed to send message to the channel");
-        Ok(())
+        send_request(&self.sender, self.id_num, &Request::GetStatus)
     }
-    fn update_from_reply(
-        &mut self,
-        command: &MsgCommand,
-        payload: &[Vec<u8>],
-    ) -> super::Result<()> {
-        match command {
-            MsgCommand::GetStatus => {
-                assert_eq!(payload.len(), 1);
-                let cpu_status: CpuStatus = CpuStatus::deserialize(&payload[0])?;
+    fn update_from_response(&mut self, response: Response) -> super::Result<()> {
+        match response {
+            Response::Status(Status::Cpu(cpu_status)) => {
                 self.freq = cpu_status.freq;
                 self.usage = cpu_status.usage;
                 self.temp = cpu_status.temp;
                 self.power = cpu_status.power;
-                // let msg_packet =
-                //     MsgPacket::new(MsgMode::Request, None, 0, self.id_num, MsgCommand::SetFreq)
-                //         .serialize()
-                //         .expect("Failed to serialize MsgPacket");
-                // let sender = self.sender.lock().unwrap();
-                // sender
-                //     .send(MsgBody::new(msg_packet, Some(vec![1, 2, 2])))
-                //     .unwrap();
+                self.throttle = cpu_status.throttle;
+                self.cores = cpu_status.cores;
+            }
+            Response::CoreState(cores) => {
+                self.cores = cores;
             }
-            MsgCommand::SetFreq => {}
             _ => {}
         }
         Ok(())
@@ -1,19 +1,19 @@
-use crate::component::Component;
-use lib::field::fan_speed::FanIndex;
+use crate::component::{Component, Outgoing, send_request};
+use lib::field::fan_speed::{FanIndex, FanValues};
 use lib::field::{fan_speed::FanSpeed, fan_speed::TargetFanSpeed};
 use lib::proto::*;
-use std::sync::mpsc::Sender;
 use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc::UnboundedSender;
 
 pub struct Fan {
     id_num: u8,
     cpu_fan_speed: FanSpeed,
     gpu_fan_speed: FanSpeed,
-    sender: Arc<Mutex<Sender<MsgBody>>>,
+    sender: Arc<Mutex<UnboundedSender<Outgoing>>>,
 }
 
 impl Fan {
-    pub fn new(id_num: u8, sender: Arc<Mutex<Sender<MsgBody>>>) -> Self {
+    pub fn new(id_num: u8, sender: Arc<Mutex<UnboundedSender<Outgoing>>>) -> Self {
         Self {
             id_num,
             cpu_fan_speed: FanSpeed::default(),
@@ -30,63 +30,29 @@ impl Fan {
         &self.gpu_fan_speed
     }
 
+    // The same duty for both fans with FanIndex::All
     pub fn set_fan_speed(&self, index: FanIndex, target_fan_speed: TargetFanSpeed) {
-        let msg_packet = MsgPacket::new(
-            MsgMode::Request,
-            None,
-            0,
-            self.id_num,
-            MsgCommand::SetFanSpeed,
-        );
-        let mut payload = vec![index.serialize().unwrap()];
-        payload.push(
-            target_fan_speed
-                .serialize()
-                .expect("Failed to serialize payload"),
-        );
-        let msg_body = MsgBody::new(msg_packet, payload);
-        let sender = self.sender.lock().unwrap();
-        sender
-            .send(msg_body)
-            .expect("Failed to send message to the channel");
+        let duty = FanValues::with_index(index, target_fan_speed);
+        send_request(&self.sender, self.id_num, &Request::SetFanSpeed(duty))
+            .expect("Failed to serialize payload");
     }
 }
 
 impl Component for Fan {
     fn refresh_status(&mut self) -> super::Result<()> {
-        let msg_packet = MsgPacket::new(
-            MsgMode::Request,
-            None,
-            0,
+        send_request(
+            &self.sender,
             self.id_num,
-            MsgCommand::GetFanSpeed,
-        );
-        let payload = FanIndex::All.serialize()?;
-        let msg_body = MsgBody::new(msg_packet, vec![payload]);
-        let sender = self.sender.lock().unwrap();
-        sender
-            .send(msg_body)
-            .expect("Failed to send message to the channel");
-        Ok(())
+            &Request::GetFanSpeed(FanIndex::All),
+        )
     }
-    fn update_from_reply(
-        &mut self,
-        command: &MsgCommand,
-        payload: &[Vec<u8>],
-    ) -> super::Result<()> {
-        if *command == MsgCommand::GetFanSpeed {
-            let fan_index: FanIndex = FanIndex::deserialize(&payload[0])?;
-            match fan_index {
-                FanIndex::Cpu => {
-                    self.cpu_fan_speed = FanSpeed::deserialize(&payload[1])?;
-                }
-                FanIndex::Gpu => {
-                    self.gpu_fan_speed = FanSpeed::deserialize(&payload[1])?;
-                }
-                FanIndex::All => {
-                    self.cpu_fan_speed = FanSpeed::deserialize(&payload[1])?;
-                    self.gpu_fan_speed = FanSpeed::deserialize(&payload[2])?;
-                }
+    fn update_from_response(&mut self, response: Response) -> super::Result<()> {
+        if let Response::FanSpeed(fan_speed) = response {
+            if let Some(cpu_fan_speed) = fan_speed.get_cpu() {
+                self.cpu_fan_speed = cpu_fan_speed.clone();
+            }
+            if let Some(gpu_fan_speed) = fan_speed.get_gpu() {
+                self.gpu_fan_speed = gpu_fan_speed.clone();
             }
         }
         Ok(())
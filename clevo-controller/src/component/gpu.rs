@@ -1,16 +1,148 @@
+use crate::component::{Component, ComponentError, Outgoing, send_request};
 use lib::{
-    field::{freq::Freq, power::Power, temp::Temp, usage::Usage},
+    field::{
+        desc::{Desc, DeviceInfo},
+        fan_speed::FanSpeed,
+        freq::{Freq, TargetFreq},
+        limit::GpuLimits,
+        power::{Power, PowerState, TargetPower},
+        temp::Temp,
+        usage::Usage,
+    },
     proto::*,
 };
-use std::sync::{Arc, Mutex, mpsc::Sender};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc::UnboundedSender;
 
-#[allow(dead_code)]
 pub struct Gpu {
     id_num: u8,
     desc: String,
+    device_info: Option<DeviceInfo>,
     freq: Freq,
     usage: Usage,
     temp: Temp,
     power: Power,
-    sender: Arc<Mutex<Sender<MsgBody>>>,
+    fan_speed: FanSpeed,
+    freq_limit: TargetFreq,
+    rc6_residency: Option<f32>,
+    power_state: PowerState,
+    limits: Option<GpuLimits>, // None until the daemon answered GetGpuLimits
+    sender: Arc<Mutex<UnboundedSender<Outgoing>>>,
+}
+
+impl Gpu {
+    pub fn new(id_num: u8, desc: &Desc, sender: Arc<Mutex<UnboundedSender<Outgoing>>>) -> Self {
+        Self {
+            id_num,
+            desc: desc.get_desc().to_string(),
+            device_info: desc.get_device_info().cloned(),
+            freq: Freq::default(),
+            usage: Usage::default(),
+            temp: Temp::default(),
+            power: Power::default(),
+            fan_speed: FanSpeed::default(),
+            freq_limit: TargetFreq::default(),
+            rc6_residency: None,
+            power_state: PowerState::default(),
+            limits: None,
+            sender,
+        }
+    }
+
+    fn send(&self, request: Request) {
+        send_request(&self.sender, self.id_num, &request).expect("Failed to serialize payload");
+    }
+
+    pub fn set_freq(&self, target_freq: TargetFreq) {
+        self.send(Request::SetFreq(target_freq));
+    }
+
+    // Lock the memory clock, in MHz
+    pub fn set_mem_freq(&self, target_freq: TargetFreq) {
+        self.send(Request::SetMemFreq(target_freq));
+    }
+
+    // Only the max of the target is used
+    pub fn set_power_limit(&self, target_power: TargetPower) {
+        self.send(Request::SetPowerLimit(target_power));
+    }
+
+    pub fn reset_limits(&self) {
+        self.send(Request::ResetGpuLimits);
+    }
+
+    pub fn request_limits(&self) {
+        self.send(Request::GetGpuLimits);
+    }
+
+    pub fn get_freq(&self) -> &Freq {
+        &self.freq
+    }
+    pub fn get_usage(&self) -> &Usage {
+        &self.usage
+    }
+    pub fn get_temp(&self) -> &Temp {
+        &self.temp
+    }
+    pub fn get_power(&self) -> &Power {
+        &self.power
+    }
+    pub fn get_fan_speed(&self) -> &FanSpeed {
+        &self.fan_speed
+    }
+    pub fn get_freq_limit(&self) -> &TargetFreq {
+        &self.freq_limit
+    }
+    pub fn get_rc6_residency(&self) -> Option<f32> {
+        self.rc6_residency
+    }
+    pub fn get_power_state(&self) -> PowerState {
+        self.power_state
+    }
+    pub fn get_desc(&self) -> &String {
+        &self.desc
+    }
+    pub fn get_device_info(&self) -> Option<&DeviceInfo> {
+        self.device_info.as_ref()
+    }
+    pub fn get_limits(&self) -> Option<&GpuLimits> {
+        self.limits.as_ref()
+    }
+}
+
+type Result<T> = std::result::Result<T, ComponentError>;
+
+impl Component for Gpu {
+    fn refresh_status(&mut self) -> Result<()> {
+        send_request(&self.sender, self.id_num, &Request::GetStatus)
+    }
+    fn update_from_response(&mut self, response: Response) -> Result<()> {
+        match response {
+            Response::Status(Status::Gpu(gpu_status)) => {
+                self.freq = gpu_status.freq;
+                self.usage = gpu_status.usage;
+                self.temp = gpu_status.temp;
+                self.power = gpu_status.power;
+                self.fan_speed = gpu_status.fan_speed;
+                self.freq_limit = gpu_status.freq_limit;
+                self.rc6_residency = gpu_status.rc6_residency;
+                self.power_state = gpu_status.power_state;
+            }
+            Response::Freq(freq_limit) => {
+                self.freq_limit = freq_limit;
+            }
+            Response::GpuLimits(limits) => {
+                if let Some(gpu_clock) = limits.get_gpu_clock() {
+                    self.freq_limit = gpu_clock.clone();
+                }
+                self.limits = Some(limits);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn accept(&mut self, visitor: &mut dyn super::Visitor) {
+        visitor.visit_gpu(self);
+    }
 }
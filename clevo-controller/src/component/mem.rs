@@ -0,0 +1,63 @@
+use crate::component::{Component, ComponentError, Outgoing, send_request};
+use lib::{
+    field::mem::{MemUsage, Pressure},
+    proto::*,
+};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc::UnboundedSender;
+
+pub struct Mem {
+    id_num: u8,
+    usage: MemUsage,
+    memory_pressure: Option<Pressure>,
+    cpu_pressure: Option<Pressure>,
+    io_pressure: Option<Pressure>,
+    sender: Arc<Mutex<UnboundedSender<Outgoing>>>,
+}
+
+impl Mem {
+    pub fn new(id_num: u8, sender: Arc<Mutex<UnboundedSender<Outgoing>>>) -> Self {
+        Self {
+            id_num,
+            usage: MemUsage::default(),
+            memory_pressure: None,
+            cpu_pressure: None,
+            io_pressure: None,
+            sender,
+        }
+    }
+
+    pub fn get_usage(&self) -> &MemUsage {
+        &self.usage
+    }
+    pub fn get_memory_pressure(&self) -> Option<&Pressure> {
+        self.memory_pressure.as_ref()
+    }
+    pub fn get_cpu_pressure(&self) -> Option<&Pressure> {
+        self.cpu_pressure.as_ref()
+    }
+    pub fn get_io_pressure(&self) -> Option<&Pressure> {
+        self.io_pressure.as_ref()
+    }
+}
+
+type Result<T> = std::result::Result<T, ComponentError>;
+
+impl Component for Mem {
+    fn refresh_status(&mut self) -> Result<()> {
+        send_request(&self.sender, self.id_num, &Request::GetStatus)
+    }
+    fn update_from_response(&mut self, response: Response) -> Result<()> {
+        if let Response::Status(Status::Memory(mem_status)) = response {
+            self.usage = mem_status.usage;
+            self.memory_pressure = mem_status.memory_pressure;
+            self.cpu_pressure = mem_status.cpu_pressure;
+            self.io_pressure = mem_status.io_pressure;
+        }
+        Ok(())
+    }
+
+    fn accept(&mut self, visitor: &mut dyn super::Visitor) {
+        visitor.visit_mem(self);
+    }
+}
@@ -1,13 +1,24 @@
 pub mod cpu;
 pub mod fan;
 pub mod gpu;
+pub mod mem;
+pub mod net;
+pub mod power_supply;
+pub mod storage;
 
 use cpu::Cpu;
 use fan::Fan;
 use gpu::Gpu;
 use lib::field::FieldError;
-use lib::proto::MsgCommand;
+use lib::proto::{MsgBody, MsgError, ProtoError, Request, Response};
 use lib::stream::StreamError;
+use mem::Mem;
+use net::Net;
+use power_supply::PowerSupply;
+use std::sync::Mutex;
+use std::time::Duration;
+use storage::Storage;
+use tokio::sync::{mpsc::UnboundedSender, oneshot};
 
 #[derive(Debug, thiserror::Error)]
 pub enum ComponentError {
@@ -19,6 +30,8 @@ pub enum ComponentError {
     OperationNotSupport, // Operation not supported by the hardware
     #[error("Bad reply from daemon")]
     BadReply,
+    #[error("Protocol Error: {0}")]
+    ProtoError(#[from] ProtoError), // Request that can't be encoded
 }
 
 type Result<T> = std::result::Result<T, ComponentError>;
@@ -27,13 +40,53 @@ type Result<T> = std::result::Result<T, ComponentError>;
 pub trait Component {
     // Refresh self status from msg reply from daemon
     fn refresh_status(&mut self) -> Result<()>;
-    fn update_from_reply(&mut self, command: &MsgCommand, payload: &[Vec<u8>]) -> Result<()>;
+    fn update_from_response(&mut self, response: Response) -> Result<()>;
     fn accept(&mut self, visitor: &mut dyn Visitor);
 }
 
+/// The reply to a request, or the error the daemon replied with, or `MsgError::Timeout`
+/// when no reply came in time
+pub type ReplyResult = std::result::Result<MsgBody, MsgError>;
+
+/// A request queued for the communicator task
+pub struct Outgoing {
+    pub body: MsgBody,
+    pub timeout: Option<Duration>, // None for the request timeout of the service
+    pub reply: Option<oneshot::Sender<ReplyResult>>, // None to only update the component
+}
+
+impl From<MsgBody> for Outgoing {
+    fn from(body: MsgBody) -> Self {
+        Self {
+            body,
+            timeout: None,
+            reply: None,
+        }
+    }
+}
+
+// Queue a request for the communicator thread, which sends it to the daemon
+pub fn send_request(
+    sender: &Mutex<UnboundedSender<Outgoing>>,
+    id_num: u8,
+    request: &Request,
+) -> Result<()> {
+    let msg_body = MsgBody::request(id_num, request)?;
+    sender
+        .lock()
+        .unwrap()
+        .send(msg_body.into())
+        .expect("Failed to send message to the channel");
+    Ok(())
+}
+
 // 访问者模式, see https://en.wikipedia.org/wiki/Visitor_pattern,https://colobu.com/rust-patterns/patterns/behavioural/visitor.html
 pub trait Visitor {
     fn visit_cpu(&mut self, cpu: &Cpu);
     fn visit_fan(&mut self, fan: &Fan);
     fn visit_gpu(&mut self, gpu: &Gpu);
+    fn visit_net(&mut self, net: &Net);
+    fn visit_mem(&mut self, mem: &Mem);
+    fn visit_storage(&mut self, storage: &Storage);
+    fn visit_power_supply(&mut self, power_supply: &PowerSupply);
 }
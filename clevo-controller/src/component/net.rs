@@ -0,0 +1,42 @@
+use crate::component::{Component, ComponentError, Outgoing, send_request};
+use lib::{field::net_speed::NetSpeed, proto::*};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc::UnboundedSender;
+
+pub struct Net {
+    id_num: u8,
+    net_speed: NetSpeed,
+    sender: Arc<Mutex<UnboundedSender<Outgoing>>>,
+}
+
+impl Net {
+    pub fn new(id_num: u8, sender: Arc<Mutex<UnboundedSender<Outgoing>>>) -> Self {
+        Self {
+            id_num,
+            net_speed: NetSpeed::default(),
+            sender,
+        }
+    }
+
+    pub fn get_net_speed(&self) -> &NetSpeed {
+        &self.net_speed
+    }
+}
+
+type Result<T> = std::result::Result<T, ComponentError>;
+
+impl Component for Net {
+    fn refresh_status(&mut self) -> Result<()> {
+        send_request(&self.sender, self.id_num, &Request::GetStatus)
+    }
+    fn update_from_response(&mut self, response: Response) -> Result<()> {
+        if let Response::Status(Status::Network(net_speed)) = response {
+            self.net_speed = net_speed;
+        }
+        Ok(())
+    }
+
+    fn accept(&mut self, visitor: &mut dyn super::Visitor) {
+        visitor.visit_net(self);
+    }
+}
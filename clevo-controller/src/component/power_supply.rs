@@ -0,0 +1,57 @@
+use crate::component::{Component, ComponentError, Outgoing, send_request};
+use lib::{
+    field::{power::Power, power_supply::PowerSource},
+    proto::*,
+};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc::UnboundedSender;
+
+pub struct PowerSupply {
+    id_num: u8,
+    source: PowerSource,
+    adapter_power: Option<Power>,
+    supplies: Vec<String>,
+    sender: Arc<Mutex<UnboundedSender<Outgoing>>>,
+}
+
+impl PowerSupply {
+    pub fn new(id_num: u8, sender: Arc<Mutex<UnboundedSender<Outgoing>>>) -> Self {
+        Self {
+            id_num,
+            source: PowerSource::default(),
+            adapter_power: None,
+            supplies: vec![],
+            sender,
+        }
+    }
+
+    pub fn get_source(&self) -> PowerSource {
+        self.source
+    }
+    pub fn get_adapter_power(&self) -> Option<&Power> {
+        self.adapter_power.as_ref()
+    }
+    pub fn get_supplies(&self) -> &Vec<String> {
+        &self.supplies
+    }
+}
+
+type Result<T> = std::result::Result<T, ComponentError>;
+
+impl Component for PowerSupply {
+    fn refresh_status(&mut self) -> Result<()> {
+        send_request(&self.sender, self.id_num, &Request::GetStatus)
+    }
+    fn update_from_response(&mut self, response: Response) -> Result<()> {
+        if let Response::Status(Status::PowerSupply(status)) = response {
+            self.source = status.source;
+            self.adapter_power = status.adapter_power;
+            self.supplies = status.supplies;
+        }
+        Ok(())
+    }
+
+    fn accept(&mut self, visitor: &mut dyn super::Visitor) {
+        visitor.visit_power_supply(self);
+    }
+}
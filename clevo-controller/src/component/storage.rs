@@ -0,0 +1,49 @@
+use crate::component::{Component, ComponentError, Outgoing, send_request};
+use lib::{field::storage::DriveTemp, proto::*};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc::UnboundedSender;
+
+pub struct Storage {
+    id_num: u8,
+    drives: Vec<DriveTemp>,
+    sender: Arc<Mutex<UnboundedSender<Outgoing>>>,
+}
+
+impl Storage {
+    pub fn new(id_num: u8, sender: Arc<Mutex<UnboundedSender<Outgoing>>>) -> Self {
+        Self {
+            id_num,
+            drives: vec![],
+            sender,
+        }
+    }
+
+    pub fn get_drives(&self) -> &Vec<DriveTemp> {
+        &self.drives
+    }
+
+    // Composite temperature of the hottest drive, None before the first reply
+    pub fn get_hottest(&self) -> Option<&DriveTemp> {
+        self.drives
+            .iter()
+            .max_by_key(|drive| drive.get_composite().get_value())
+    }
+}
+
+type Result<T> = std::result::Result<T, ComponentError>;
+
+impl Component for Storage {
+    fn refresh_status(&mut self) -> Result<()> {
+        send_request(&self.sender, self.id_num, &Request::GetStatus)
+    }
+    fn update_from_response(&mut self, response: Response) -> Result<()> {
+        if let Response::Status(Status::Storage(storage_status)) = response {
+            self.drives = storage_status.drives;
+        }
+        Ok(())
+    }
+
+    fn accept(&mut self, visitor: &mut dyn super::Visitor) {
+        visitor.visit_storage(self);
+    }
+}
@@ -1,3 +1,4 @@
 pub mod temp_controler;
 pub mod component;
 pub mod service;
+pub mod status;
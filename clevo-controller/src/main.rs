@@ -1,5 +1,8 @@
 use clevo_controller::service::core::Service;
+use clevo_controller::status::StatusPrinter;
 use clevo_controller::temp_controler::Controler;
+use lib::client::DEFAULT_SOCKET_NAME;
+use lib::field::{category::Category, desc::Desc};
 use std::env;
 use std::sync::{Arc, Mutex};
 use std::thread;
@@ -7,8 +10,29 @@ use std::thread;
 #[tokio::main(flavor = "current_thread")]
 async fn main() {
     let socket_name =
-        dotenv::var("SOCKET_NAME").unwrap_or_else(|_| "clevo-controler.sock".to_string());
-    let (service, handle) = Service::init(socket_name.as_str()).expect("Failed to create service");
+        dotenv::var("SOCKET_NAME").unwrap_or_else(|_| DEFAULT_SOCKET_NAME.to_string());
+    let (service, handle) = Service::init(socket_name.as_str())
+        .await
+        .unwrap_or_else(|e| {
+            eprintln!("Failed to connect to the daemon: {}", e);
+            std::process::exit(1);
+        });
+    // `clevo-controller status` prints one round of status and exits
+    if env::args().nth(1).as_deref() == Some("status") {
+        // give the refresher a round to fetch every component
+        tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+        let mut service = service;
+        let mut printer = StatusPrinter::new();
+        let mut components: Vec<u8> = service.get_components().into_keys().collect();
+        components.sort();
+        for id in components {
+            service.accept(id, &mut printer);
+            if let Some(capabilities) = service.get_capabilities(id) {
+                printer.print_capabilities(&capabilities);
+            }
+        }
+        return;
+    }
     let service = Arc::new(Mutex::new(service));
     let service_clone = service.clone();
     thread::spawn(move || {
@@ -26,11 +50,16 @@ async fn main() {
         let mut contorler = Controler::new(&config_path);
         loop {
             let mut service = service_clone.lock().unwrap();
-            service.accept(0, &mut contorler);
-            service.accept(1, &mut contorler);
+            // visit fans last, so they follow the temperatures just collected
+            let mut components: Vec<(u8, Desc)> = service.get_components().into_iter().collect();
+            components
+                .sort_by_key(|(id, desc)| (matches!(desc.get_category(), Category::Fan), *id));
+            for (id, _) in components {
+                service.accept(id, &mut contorler);
+            }
             drop(service);
             std::thread::sleep(std::time::Duration::from_secs(2));
         }
     });
-    handle.join();
+    handle.join().await;
 }
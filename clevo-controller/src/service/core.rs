@@ -1,32 +1,65 @@
-use crate::component::{Component, Visitor, cpu::Cpu, fan::Fan};
+use crate::component::{
+    Component, ComponentError, Outgoing, ReplyResult, Visitor, cpu::Cpu, fan::Fan, gpu::Gpu,
+    mem::Mem, net::Net, power_supply::PowerSupply, storage::Storage,
+};
 use lib::{
-    field::{ComponentList, category::Category, desc::Desc},
-    proto::{MsgBody, MsgCommand, MsgMode, MsgPacket, ProtoError, recv_msg, send_msg},
-    stream::SocketStream,
+    field::{
+        ComponentList,
+        capability::Capabilities,
+        category::Category,
+        desc::Desc,
+        event::Event,
+        fan_speed::FanIndex,
+        subscription::{Subscription, Topic},
+    },
+    handshake::{Features, SUPPORTED_FEATURES, Session, client_handshake_async},
+    proto::{
+        MsgBody, MsgCommand, MsgError, MsgMode, ProtoError, Request, Response,
+        codec::{self, MsgReader},
+    },
+    stream::asynchronous::AsyncSocketStream,
 };
 use std::{
     collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::{
     sync::{
-        Arc, Mutex,
-        mpsc::{Receiver, Sender},
+        mpsc::{UnboundedReceiver, UnboundedSender},
+        oneshot,
     },
-    thread::JoinHandle,
+    task::JoinHandle,
 };
 
 type Result<T> = std::result::Result<T, ProtoError>;
 
+// How often overdue requests are looked for, a timeout is late by at most this much
+const EXPIRY_INTERVAL: Duration = Duration::from_millis(100);
+
 pub struct ComponentInfo {
     desc: Desc,
     active: bool,
+    capabilities: Option<Capabilities>, // None until the daemon answered GetCapabilities
 }
 
 impl ComponentInfo {
     pub fn new(desc: Desc) -> Self {
-        Self { desc, active: true }
+        Self {
+            desc,
+            active: true,
+            capabilities: None,
+        }
     }
     pub fn get_desc(&self) -> &Desc {
         &self.desc
     }
+    pub fn get_capabilities(&self) -> Option<&Capabilities> {
+        self.capabilities.as_ref()
+    }
+    pub fn set_capabilities(&mut self, capabilities: Capabilities) {
+        self.capabilities = Some(capabilities);
+    }
     pub fn is_active(&self) -> bool {
         self.active
     }
@@ -38,56 +71,133 @@ impl ComponentInfo {
 pub struct ServiceConfig {
     interval: u64, // Time interval for refreshing hardware data by seconds
     socket_name: String,
+    request_timeout: Duration, // How long a request may wait for its reply
+}
+
+// A request sent to the daemon that has no reply yet
+struct Pending {
+    id_num: u8,
+    command: MsgCommand,
+    deadline: Instant,
+    reply: Option<oneshot::Sender<ReplyResult>>, // Who waits for the outcome, if anyone
+}
+
+impl Pending {
+    // Hand the outcome to whoever waits for it, or log a failure nobody waits for
+    fn complete(self, result: ReplyResult) {
+        match self.reply {
+            // the caller may have stopped waiting
+            Some(reply) => {
+                let _ = reply.send(result);
+            }
+            None => {
+                if let Err(error) = result {
+                    eprintln!(
+                        "{} on component {} failed [{}]: {}",
+                        self.command,
+                        self.id_num,
+                        error.get_code(),
+                        error
+                    );
+                }
+            }
+        }
+    }
 }
 
+type PendingTable = Arc<Mutex<HashMap<u64, Pending>>>;
+type ComponentTable = Arc<Mutex<HashMap<u8, Box<dyn Component + Send + Sync>>>>;
+type ComponentInfoTable = Arc<Mutex<HashMap<u8, ComponentInfo>>>;
+
 pub struct Service {
     config: ServiceConfig,
-    components_info: Arc<Mutex<HashMap<u8, ComponentInfo>>>,
-    components: Arc<Mutex<HashMap<u8, Box<dyn Component + Send + Sync>>>>,
-    sender: Arc<Mutex<Sender<MsgBody>>>,
+    components_info: ComponentInfoTable,
+    components: ComponentTable,
+    sender: Arc<Mutex<UnboundedSender<Outgoing>>>,
+    session: Session, // Version and features agreed with the daemon
 }
 
 pub struct ServiceHandle {
     communicator_handle: JoinHandle<()>,
+    receiver_handle: JoinHandle<()>,
+    expiry_handle: JoinHandle<()>,
     refresher_handle: JoinHandle<()>,
 }
 
 impl ServiceHandle {
-    pub fn new(communicator_handle: JoinHandle<()>, refresher_handle: JoinHandle<()>) -> Self {
+    pub fn new(
+        communicator_handle: JoinHandle<()>,
+        receiver_handle: JoinHandle<()>,
+        expiry_handle: JoinHandle<()>,
+        refresher_handle: JoinHandle<()>,
+    ) -> Self {
         Self {
             communicator_handle,
+            receiver_handle,
+            expiry_handle,
             refresher_handle,
         }
     }
-    pub fn join(self) {
-        self.communicator_handle.join().unwrap();
-        self.refresher_handle.join().unwrap();
+    pub async fn join(self) {
+        self.communicator_handle.await.unwrap();
+        self.receiver_handle.await.unwrap();
+        self.expiry_handle.await.unwrap();
+        self.refresher_handle.await.unwrap();
     }
 }
 
 impl Service {
-    pub fn init(socket_name: &str) -> Result<(Service, ServiceHandle)> {
-        let (sender, receiver) = std::sync::mpsc::channel();
+    /// Connect to the daemon, the service runs on tasks of the current tokio runtime
+    pub async fn init(socket_name: &str) -> Result<(Service, ServiceHandle)> {
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
         let mut service = Self {
             sender: Arc::new(Mutex::new(sender)),
             config: ServiceConfig {
                 interval: 1,
                 socket_name: socket_name.to_string(),
+                request_timeout: Duration::from_secs(3),
             },
             components_info: Arc::new(Mutex::new(HashMap::new())),
             components: Arc::new(Mutex::new(HashMap::new())),
+            session: Session::default(),
         };
-        let communicator_handle = service.spawn_communicator(receiver)?;
+        let (communicator_handle, receiver_handle, expiry_handle) =
+            service.spawn_communicator(receiver).await?;
         let refresher_handle = service.spawn_refresher()?;
         Ok((
             service,
-            ServiceHandle {
+            ServiceHandle::new(
                 communicator_handle,
+                receiver_handle,
+                expiry_handle,
                 refresher_handle,
-            },
+            ),
         ))
     }
 
+    /// Send a request to the component `id_num`, the receiver gets its reply, the error the
+    /// daemon replied with, or `MsgError::Timeout` once `timeout` (the request timeout of the
+    /// service if None) passed without a reply
+    pub fn send(
+        &self,
+        id_num: u8,
+        request: &Request,
+        timeout: Option<Duration>,
+    ) -> Result<oneshot::Receiver<ReplyResult>> {
+        let (reply, receiver) = oneshot::channel();
+        let outgoing = Outgoing {
+            body: MsgBody::request(id_num, request)?,
+            timeout,
+            reply: Some(reply),
+        };
+        self.sender
+            .lock()
+            .unwrap()
+            .send(outgoing)
+            .map_err(|_| ProtoError::Other("the communicator has stopped".to_string()))?;
+        Ok(receiver)
+    }
+
     pub fn accept(&mut self, id: u8, visitor: &mut dyn Visitor) {
         let mut components = self.components.lock().unwrap();
         components
@@ -96,6 +206,10 @@ impl Service {
             .accept(visitor);
     }
 
+    pub fn get_session(&self) -> &Session {
+        &self.session
+    }
+
     pub fn get_components(&self) -> HashMap<u8, Desc> {
         let components_info = self.components_info.lock().unwrap();
         components_info
@@ -106,6 +220,14 @@ impl Service {
             })
             .collect()
     }
+    /// What the component can do, None if the daemon doesn't tell or hasn't answered yet
+    pub fn get_capabilities(&self, id: u8) -> Option<Capabilities> {
+        let components_info = self.components_info.lock().unwrap();
+        components_info
+            .get(&id)
+            .and_then(|info| info.get_capabilities().cloned())
+    }
+
     pub fn active_component(&mut self, id: u8) {
         let mut components_info = self.components_info.lock().unwrap();
         if let Some(component) = components_info.get_mut(&id) {
@@ -134,73 +256,322 @@ impl Service {
                     components.insert(*id_num, Box::new(cpu));
                 }
                 Category::Gpu => {
-                    unimplemented!()
+                    let gpu = Gpu::new(*id_num, desc, Arc::clone(&self.sender));
+                    components.insert(*id_num, Box::new(gpu));
                 }
                 Category::Fan => {
                     let fan = Fan::new(*id_num, Arc::clone(&self.sender));
                     components.insert(*id_num, Box::new(fan));
                 }
+                Category::Network => {
+                    let net = Net::new(*id_num, Arc::clone(&self.sender));
+                    components.insert(*id_num, Box::new(net));
+                }
+                Category::Memory => {
+                    let mem = Mem::new(*id_num, Arc::clone(&self.sender));
+                    components.insert(*id_num, Box::new(mem));
+                }
+                Category::Storage => {
+                    let storage = Storage::new(*id_num, Arc::clone(&self.sender));
+                    components.insert(*id_num, Box::new(storage));
+                }
+                Category::PowerSupply => {
+                    let power_supply = PowerSupply::new(*id_num, Arc::clone(&self.sender));
+                    components.insert(*id_num, Box::new(power_supply));
+                }
             }
         });
     }
 
-    // Get harware list --> create hardware object --> add to components --> msg loop
-    fn spawn_communicator(&mut self, receiver: Receiver<MsgBody>) -> Result<JoinHandle<()>> {
+    // Ask what every component supports, the replies are kept in the component info
+    fn query_capabilities(&self) -> Result<()> {
+        let sender = self.sender.lock().unwrap();
+        for id_num in self.components_info.lock().unwrap().keys() {
+            let body = MsgBody::request(*id_num, &Request::GetCapabilities)?;
+            sender
+                .send(body.into())
+                .expect("Failed to send message to the channel");
+        }
+        Ok(())
+    }
+
+    // Ask the daemon to push the status of every component instead of polling it,
+    // along with the component events
+    fn subscribe(&self) -> Result<()> {
+        let topics = self
+            .components_info
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(id_num, info)| status_topic(*id_num, info.get_desc()))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let subscription = Subscription::new(topics, self.config.interval * 1000, true);
+        let body = MsgBody::request(0, &Request::Subscribe(subscription))?;
+        self.sender
+            .lock()
+            .unwrap()
+            .send(body.into())
+            .expect("Failed to send message to the channel");
+        Ok(())
+    }
+
+    // Get harware list --> create hardware object --> add to components --> msg loop.
+    // Requests go out from the communicator task, replies come back on the receiver task
+    // and are matched to their request by sequence number, so several can be in flight.
+    // A third task fails the requests whose reply is overdue.
+    async fn spawn_communicator(
+        &mut self,
+        mut receiver: UnboundedReceiver<Outgoing>,
+    ) -> Result<(JoinHandle<()>, JoinHandle<()>, JoinHandle<()>)> {
+        let (stream_reader, mut stream_writer) =
+            AsyncSocketStream::new(self.config.socket_name.as_str())
+                .await?
+                .split();
+        let mut stream_reader = MsgReader::new(stream_reader);
+        self.session =
+            client_handshake_async(&mut stream_reader, &mut stream_writer, SUPPORTED_FEATURES)
+                .await?;
         // get component list first
-        let packet = MsgPacket::new(MsgMode::Reply, None, 0, 0, MsgCommand::GetComponentList);
-        let body = MsgBody::new(packet, vec![]);
-        let mut socket_stream = SocketStream::new(self.config.socket_name.as_str())
-            .expect("Failed to create socket stream");
-        send_msg(&mut socket_stream, &body).expect("Failed to send message");
-        let reply_msg = recv_msg(&mut socket_stream).unwrap();
+        let version = self.session.get_version();
+        let body = MsgBody::request(0, &Request::GetComponentList)?;
+        codec::send_msg(&mut stream_writer, version, &body).await?;
+        let reply_msg = stream_reader.recv().await?;
         let component_list =
             ComponentList::deserialize(reply_msg.get_payload()[0].as_slice()).unwrap();
-        dbg!(&component_list);
         self.add_component(&component_list);
+        if self.session.supports(Features::CAPABILITIES) {
+            self.query_capabilities()?;
+        }
+        if self.session.supports(Features::SUBSCRIBE) {
+            self.subscribe()?;
+        }
 
-        // messsage reveiwer thread start
-        let components_clone = Arc::clone(&self.components);
+        let pending: PendingTable = Arc::new(Mutex::new(HashMap::new()));
 
-        let handle = std::thread::spawn(move || {
-            loop {
-                match receiver.recv() {
-                    Ok(body) => {
-                        send_msg(&mut socket_stream, &body).unwrap();
-                    }
+        // messsage sender task start, sequence 0 was the component list
+        let pending_clone = Arc::clone(&pending);
+        let request_timeout = self.config.request_timeout;
+        let communicator_handle = tokio::spawn(async move {
+            let mut sequence = 1;
+            // ends once every component is gone, nothing will be sent anymore
+            while let Some(outgoing) = receiver.recv().await {
+                // components build requests in the units of this build
+                let mut body = match outgoing.body.request_for_version(version) {
+                    Ok(body) => body,
                     Err(e) => {
-                        panic!("Failed to receive message: {}", e);
+                        eprintln!("Failed to convert request: {}", e);
+                        continue;
                     }
+                };
+                body.set_sequence(sequence);
+                let packet = body.get_packet();
+                pending_clone.lock().unwrap().insert(
+                    sequence,
+                    Pending {
+                        id_num: packet.get_id_num(),
+                        command: packet.get_command().clone(),
+                        deadline: Instant::now() + outgoing.timeout.unwrap_or(request_timeout),
+                        reply: outgoing.reply,
+                    },
+                );
+                sequence += 1;
+                if let Err(e) = codec::send_msg(&mut stream_writer, version, &body).await {
+                    eprintln!("Failed to send message: {}", e);
+                    break;
                 }
-                let body = recv_msg(&mut socket_stream).expect("Failed to receive message");
+            }
+        });
+
+        let pending_clone = Arc::clone(&pending);
+        let expiry_handle = tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(EXPIRY_INTERVAL);
+            loop {
+                ticker.tick().await;
+                expire_pending(&pending_clone);
+            }
+        });
+
+        // messsage reveiwer task start
+        let components_clone = Arc::clone(&self.components);
+        let components_info_clone = Arc::clone(&self.components_info);
+        let receiver_handle = tokio::spawn(async move {
+            loop {
+                let body = match stream_reader.recv().await {
+                    Ok(body) => body,
+                    Err(e) => {
+                        eprintln!("Failed to receive message: {}", e);
+                        break;
+                    }
+                };
                 let packet = body.get_packet();
-                let mut components = components_clone.lock().unwrap();
-                if let Some(component) = components.get_mut(&packet.get_id_num()) {
-                    component
-                        .update_from_reply(packet.get_command(), body.get_payload())
-                        .unwrap();
-                } else {
-                    eprintln!("Component not found for index: {}", packet.get_id_num());
+                if *packet.get_mode() == MsgMode::Notify {
+                    handle_notify(&components_clone, &components_info_clone, &body);
+                    continue;
+                }
+                // a reply without a pending request already timed out, or isn't ours
+                let Some(request) = pending.lock().unwrap().remove(&packet.get_sequence()) else {
+                    eprintln!(
+                        "Dropping reply {} with unknown sequence {}",
+                        packet.get_command(),
+                        packet.get_sequence()
+                    );
+                    continue;
+                };
+                if request.id_num != packet.get_id_num() || request.command != *packet.get_command()
+                {
+                    eprintln!(
+                        "Reply {} for component {} doesn't match request {} for component {}",
+                        packet.get_command(),
+                        packet.get_id_num(),
+                        request.command,
+                        request.id_num
+                    );
+                    continue;
                 }
+                // an error reply carries no payload, keep the last known state
+                if let Some(error) = packet.get_error() {
+                    request.complete(Err(error.clone()));
+                    continue;
+                }
+                update_component(
+                    &components_clone,
+                    &components_info_clone,
+                    request.id_num,
+                    &request.command,
+                    body.get_payload(),
+                );
+                request.complete(Ok(body));
             }
         });
-        Ok(handle)
+        Ok((communicator_handle, receiver_handle, expiry_handle))
     }
 
     fn spawn_refresher(&mut self) -> Result<JoinHandle<()>> {
         // refresh component status
         let components_clone = Arc::clone(&self.components);
         let interval = self.config.interval;
+        let subscribed = self.session.supports(Features::SUBSCRIBE);
 
-        let handle = std::thread::spawn(move || {
+        let handle = tokio::spawn(async move {
+            // the daemon pushes the status on its own
+            if subscribed {
+                return;
+            }
+            let mut ticker = tokio::time::interval(Duration::from_secs(interval));
             loop {
+                ticker.tick().await;
                 let mut components = components_clone.lock().unwrap();
                 components.iter_mut().for_each(|(_, c)| {
                     c.refresh_status().unwrap();
                 });
-                drop(components);
-                std::thread::sleep(std::time::Duration::from_secs(interval));
             }
         });
         Ok(handle)
     }
 }
+
+// Fail the requests whose reply is overdue with a timeout, a late reply is then ignored
+fn expire_pending(pending: &PendingTable) {
+    let now = Instant::now();
+    let expired: Vec<(u64, Pending)> = {
+        let mut pending = pending.lock().unwrap();
+        let sequences: Vec<u64> = pending
+            .iter()
+            .filter(|(_, request)| request.deadline <= now)
+            .map(|(sequence, _)| *sequence)
+            .collect();
+        sequences
+            .into_iter()
+            .filter_map(|sequence| Some((sequence, pending.remove(&sequence)?)))
+            .collect()
+    };
+    for (sequence, request) in expired {
+        request.complete(Err(MsgError::Timeout(format!(
+            "no reply to request {}",
+            sequence
+        ))));
+    }
+}
+
+// The request a component polls its status with, the fan needs to be told which fans
+fn status_topic(id_num: u8, desc: &Desc) -> Result<Topic> {
+    let request = match desc.get_category() {
+        Category::Fan => Request::GetFanSpeed(FanIndex::All),
+        _ => Request::GetStatus,
+    };
+    Topic::new(id_num, &request)
+}
+
+// Decode a reply or a push by the category of its component, then hand it to the component
+fn update_component(
+    components: &ComponentTable,
+    components_info: &ComponentInfoTable,
+    id_num: u8,
+    command: &MsgCommand,
+    payload: &[Vec<u8>],
+) {
+    let Some(category) = components_info
+        .lock()
+        .unwrap()
+        .get(&id_num)
+        .map(|info| info.get_desc().get_category().clone())
+    else {
+        eprintln!("Component not found for index: {}", id_num);
+        return;
+    };
+    let result = Response::decode(command, &category, payload)
+        .map_err(ComponentError::from)
+        .and_then(|response| match response {
+            // about the component rather than its state
+            Response::Capabilities(capabilities) => {
+                if let Some(info) = components_info.lock().unwrap().get_mut(&id_num) {
+                    info.set_capabilities(capabilities);
+                }
+                Ok(())
+            }
+            response => match components.lock().unwrap().get_mut(&id_num) {
+                Some(component) => component.update_from_response(response),
+                None => Err(ComponentError::OperationNotSupport),
+            },
+        });
+    if let Err(e) = result {
+        eprintln!("Bad {} reply for component {}: {}", command, id_num, e);
+    }
+}
+
+// A push from the daemon, either an event or the same payload as a reply would carry
+fn handle_notify(
+    components: &ComponentTable,
+    components_info: &ComponentInfoTable,
+    body: &MsgBody,
+) {
+    let packet = body.get_packet();
+    if let Some(error) = packet.get_error() {
+        eprintln!(
+            "{} push for component {} failed [{}]: {}",
+            packet.get_command(),
+            packet.get_id_num(),
+            error.get_code(),
+            error
+        );
+        return;
+    }
+    if *packet.get_command() == MsgCommand::Event {
+        match body
+            .get_payload()
+            .first()
+            .map(|payload| Event::deserialize(payload))
+        {
+            Some(Ok(event)) => eprintln!("component {}: {}", packet.get_id_num(), event),
+            _ => eprintln!("Bad event from component {}", packet.get_id_num()),
+        }
+        return;
+    }
+    update_component(
+        components,
+        components_info,
+        packet.get_id_num(),
+        packet.get_command(),
+        body.get_payload(),
+    );
+}
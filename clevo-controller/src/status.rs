@@ -0,0 +1,214 @@
+use crate::component::{
+    Visitor, cpu::Cpu, fan::Fan, gpu::Gpu, mem::Mem, net::Net, power_supply::PowerSupply,
+    storage::Storage,
+};
+use lib::field::{
+    capability::{Capabilities, Range},
+    mem::Pressure,
+    power::PowerState,
+};
+use std::fmt::Display;
+
+// Bytes per second in the largest unit that keeps the value above 1
+fn format_rate(bytes: u64) -> String {
+    let units = ["B/s", "KiB/s", "MiB/s", "GiB/s"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < units.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, units[unit])
+}
+
+fn format_size(bytes: u64) -> String {
+    format!("{:.1} GiB", bytes as f64 / (1024.0 * 1024.0 * 1024.0))
+}
+
+// The unit once after both ends, not after each like the unit types print it
+fn format_range<T: Copy + PartialOrd, V: Display>(
+    range: &Range<T>,
+    value: impl Fn(T) -> V,
+    unit: &str,
+) -> String {
+    format!(
+        "{}-{} {}",
+        value(range.get_min()),
+        value(range.get_max()),
+        unit
+    )
+}
+
+fn format_pressure(pressure: Option<&Pressure>) -> String {
+    match pressure {
+        Some(pressure) => {
+            let [avg10, avg60, avg300] = pressure.get_some();
+            format!("{:.2} {:.2} {:.2}", avg10, avg60, avg300)
+        }
+        None => "n/a".to_string(),
+    }
+}
+
+/// Prints the status of every visited component to stdout
+#[derive(Debug, Default)]
+pub struct StatusPrinter;
+
+impl StatusPrinter {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// What can be changed on the component printed last, nothing if it is read only
+    pub fn print_capabilities(&self, capabilities: &Capabilities) {
+        let controls: Vec<String> = capabilities
+            .get_commands()
+            .iter()
+            .filter(|command| command.is_control())
+            .map(|command| format!("{:?}", command))
+            .collect();
+        if !controls.is_empty() {
+            println!("  controls: {}", controls.join(", "));
+        }
+        let ranges = [
+            (
+                "duty",
+                capabilities
+                    .get_duty()
+                    .map(|range| format_range(range, |duty| duty, "%")),
+            ),
+            (
+                "freq",
+                capabilities
+                    .get_freq()
+                    .map(|range| format_range(range, |freq| freq.get_value(), "MHz")),
+            ),
+            (
+                "mem freq",
+                capabilities
+                    .get_mem_freq()
+                    .map(|range| format_range(range, |freq| freq.get_value(), "MHz")),
+            ),
+            (
+                "power limit",
+                capabilities
+                    .get_power_limit()
+                    .map(|range| format_range(range, |power| power.as_watts(), "W")),
+            ),
+        ];
+        for (name, range) in ranges {
+            if let Some(range) = range {
+                println!("  {}: {}", name, range);
+            }
+        }
+        if !capabilities.get_governors().is_empty() {
+            println!("  governors: {}", capabilities.get_governors().join(" "));
+        }
+        if !capabilities.get_power_modes().is_empty() {
+            println!(
+                "  power modes: {}",
+                capabilities.get_power_modes().join(" ")
+            );
+        }
+    }
+}
+
+impl Visitor for StatusPrinter {
+    fn visit_cpu(&mut self, cpu: &Cpu) {
+        let online = cpu.get_freq().get_value().iter().flatten().count();
+        println!("CPU");
+        println!("  temp:    {}", cpu.get_temp().get_value());
+        println!("  power:   {}", cpu.get_power().get_value());
+        println!(
+            "  cores:   {}/{} online",
+            online,
+            cpu.get_freq().get_value().len()
+        );
+        println!(
+            "  throttled: {}",
+            if cpu.get_throttle().is_throttled() {
+                "yes"
+            } else {
+                "no"
+            }
+        );
+    }
+    fn visit_fan(&mut self, fan: &Fan) {
+        println!("Fan");
+        println!("  cpu:     {} rpm", fan.get_cpu_fan_speed().get_rpm());
+        println!("  gpu:     {} rpm", fan.get_gpu_fan_speed().get_rpm());
+    }
+    fn visit_gpu(&mut self, gpu: &Gpu) {
+        println!("{}", gpu.get_desc());
+        if gpu.get_power_state() == PowerState::Suspended {
+            println!("  suspended");
+            return;
+        }
+        println!("  temp:    {}", gpu.get_temp().get_value());
+        println!("  power:   {}", gpu.get_power().get_value());
+        if let Some(Some(freq)) = gpu.get_freq().get_value().first() {
+            println!("  freq:    {}", freq);
+        }
+    }
+    fn visit_net(&mut self, net: &Net) {
+        println!("Network");
+        for interface in net.get_net_speed().get_interfaces() {
+            println!(
+                "  {:<12} rx {:>12} ({} pkt/s)  tx {:>12} ({} pkt/s)",
+                interface.get_name(),
+                format_rate(interface.get_rx_bytes()),
+                interface.get_rx_packets(),
+                format_rate(interface.get_tx_bytes()),
+                interface.get_tx_packets()
+            );
+        }
+    }
+    fn visit_mem(&mut self, mem: &Mem) {
+        let usage = mem.get_usage();
+        println!("Memory");
+        println!(
+            "  used:    {} / {} ({} cached, {} available)",
+            format_size(usage.get_used()),
+            format_size(usage.get_total()),
+            format_size(usage.get_cached()),
+            format_size(usage.get_available())
+        );
+        println!(
+            "  swap:    {} / {}",
+            format_size(usage.get_swap_used()),
+            format_size(usage.get_swap_total())
+        );
+        // "some" averages over 10s, 60s and 300s
+        println!(
+            "  pressure memory {}, cpu {}, io {}",
+            format_pressure(mem.get_memory_pressure()),
+            format_pressure(mem.get_cpu_pressure()),
+            format_pressure(mem.get_io_pressure())
+        );
+    }
+    fn visit_storage(&mut self, storage: &Storage) {
+        println!("Storage");
+        for drive in storage.get_drives() {
+            println!(
+                "  {:<8} {} (warning {}){}  {}",
+                drive.get_name(),
+                drive.get_composite().get_value(),
+                drive
+                    .get_warning()
+                    .map_or("n/a".to_string(), |warning| warning.get_value().to_string()),
+                if drive.is_over_warning() { " HOT" } else { "" },
+                drive.get_model()
+            );
+        }
+    }
+    fn visit_power_supply(&mut self, power_supply: &PowerSupply) {
+        println!("Power supply");
+        match power_supply.get_adapter_power() {
+            Some(power) => println!(
+                "  source:  {} ({} W)",
+                power_supply.get_source(),
+                power.get_value().as_watts()
+            ),
+            None => println!("  source:  {}", power_supply.get_source()),
+        }
+    }
+}
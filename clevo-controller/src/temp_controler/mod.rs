@@ -2,6 +2,7 @@ use std::path::Path;
 
 use lib::field::{
     fan_speed::{FanIndex, TargetFanSpeed},
+    power::PowerState,
     temp::Temp,
 };
 use pid::PidControler;
@@ -31,6 +32,13 @@ pub enum ControlerError {
     NotSupport,
 }
 
+// Fan an extra temperature input is fed to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum FanInput {
+    Cpu,
+    Gpu,
+}
+
 pub trait ControlerAlgo {
     // return fan speed in percentage(1~100)
     fn update(&mut self, current_temp: &Temp) -> u32;
@@ -44,7 +52,11 @@ pub struct Controler {
     cpu_current_temp: Temp,
 
     gpu_algo: Box<dyn ControlerAlgo>,
-    gpu_current_temp: Temp,
+    gpu_current_temp: Option<Temp>, // Hottest discrete gpu of this round, None if there is none
+
+    storage_algo: Box<dyn ControlerAlgo>,
+    storage_current_temp: Option<Temp>, // Hottest nvme drive of this round
+    storage_over_warning: bool,         // A drive is at its warning temperature and may throttle
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -54,6 +66,23 @@ pub struct ControlerCfg {
 
     pub cpu_pid_cfg: pid::PidCfg,
     pub gpu_pid_cfg: pid::PidCfg,
+
+    // Fan that also cools the nvme drives, None to leave them out of the fan policy.
+    // The fan runs at the higher of its own speed and the speed asked by the drives
+    #[serde(default)]
+    pub storage_fan: Option<FanInput>,
+    #[serde(default = "default_storage_pid_cfg")]
+    pub storage_pid_cfg: pid::PidCfg,
+}
+
+fn default_storage_pid_cfg() -> pid::PidCfg {
+    pid::PidCfg {
+        target_temp: 55.0,
+        kp: 1.0,
+        ki: 0.5,
+        kd: 0.5,
+        smoothing_factor: 0.3,
+    }
 }
 
 impl Default for ControlerCfg {
@@ -61,7 +90,7 @@ impl Default for ControlerCfg {
         ControlerCfg {
             cpu_method: Method::Pid,
             cpu_pid_cfg: pid::PidCfg {
-                target_temp: 60000.0,
+                target_temp: 60.0,
                 kp: 1.0,
                 ki: 0.5,
                 kd: 0.5,
@@ -70,12 +99,15 @@ impl Default for ControlerCfg {
 
             gpu_method: Method::Pid,
             gpu_pid_cfg: pid::PidCfg {
-                target_temp: 60000.0,
+                target_temp: 60.0,
                 kp: 1.0,
                 ki: 0.5,
                 kd: 0.5,
                 smoothing_factor: 0.3,
             },
+
+            storage_fan: None,
+            storage_pid_cfg: default_storage_pid_cfg(),
         }
     }
 }
@@ -90,7 +122,11 @@ impl Controler {
             cpu_current_temp: Temp::default(),
 
             gpu_algo: Box::new(PidControler::new(ControlerCfg::default().gpu_pid_cfg)),
-            gpu_current_temp: Temp::default(),
+            gpu_current_temp: None,
+
+            storage_algo: Box::new(PidControler::new(default_storage_pid_cfg())),
+            storage_current_temp: None,
+            storage_over_warning: false,
         };
         if Path::new(cfg_path).exists() {
             controler.load_from_json();
@@ -102,7 +138,11 @@ impl Controler {
         // read json config file
         // parse json to Controler config
         let cfg = std::fs::read_to_string(&self.cfg_path).unwrap();
-        let cfg: ControlerCfg = serde_json::from_str(&cfg).unwrap();
+        let mut cfg: ControlerCfg = serde_json::from_str(&cfg).unwrap();
+        // saved back in °C on drop
+        cfg.cpu_pid_cfg.migrate();
+        cfg.gpu_pid_cfg.migrate();
+        cfg.storage_pid_cfg.migrate();
         match cfg.cpu_method {
             Method::TableLookUp => {
                 unimplemented!()
@@ -111,7 +151,7 @@ impl Controler {
                 unimplemented!()
             }
             Method::Pid => {
-                let pid = pid::PidControler::new(cfg.cpu_pid_cfg);
+                let pid = pid::PidControler::new(cfg.cpu_pid_cfg.clone());
                 self.cpu_algo = Box::new(pid);
             }
         }
@@ -123,10 +163,13 @@ impl Controler {
                 unimplemented!()
             }
             Method::Pid => {
-                let pid = pid::PidControler::new(cfg.gpu_pid_cfg);
+                let pid = pid::PidControler::new(cfg.gpu_pid_cfg.clone());
                 self.gpu_algo = Box::new(pid);
             }
         }
+        self.storage_algo = Box::new(PidControler::new(cfg.storage_pid_cfg.clone()));
+        // keep the loaded config, it is written back on drop
+        self.cfg = cfg;
     }
 
     pub fn save_to_json(&self) {
@@ -149,11 +192,62 @@ impl Visitor for Controler {
         self.cpu_current_temp = cpu.get_temp().clone();
     }
     fn visit_fan(&mut self, fan: &crate::component::fan::Fan) {
+        // a drive past its warning temperature throttles, cool it at full speed
+        let storage_target_fan_speed = match self.storage_current_temp.take() {
+            Some(_) if self.cfg.storage_fan.is_some() && self.storage_over_warning => Some(100),
+            Some(temp) if self.cfg.storage_fan.is_some() => Some(self.storage_algo.update(&temp)),
+            _ => None,
+        };
+        let storage_fan = self.cfg.storage_fan;
+        let storage_target_for = |fan_input: FanInput| {
+            storage_target_fan_speed.filter(|_| storage_fan == Some(fan_input))
+        };
+
         let cpu_target_fan_speed = self.cpu_algo.update(&self.cpu_current_temp);
+        let cpu_target_fan_speed = storage_target_for(FanInput::Cpu)
+            .map_or(cpu_target_fan_speed, |storage| {
+                storage.max(cpu_target_fan_speed)
+            });
         fan.set_fan_speed(FanIndex::Cpu, TargetFanSpeed::new(cpu_target_fan_speed));
+        // leave the gpu fan to the EC when there is nothing to follow, None sorts below any speed
+        let gpu_target_fan_speed = self
+            .gpu_current_temp
+            .take()
+            .map(|gpu_current_temp| self.gpu_algo.update(&gpu_current_temp))
+            .max(storage_target_for(FanInput::Gpu));
+        if let Some(gpu_target_fan_speed) = gpu_target_fan_speed {
+            fan.set_fan_speed(FanIndex::Gpu, TargetFanSpeed::new(gpu_target_fan_speed));
+        }
+    }
+    fn visit_gpu(&mut self, gpu: &crate::component::gpu::Gpu) {
+        // the integrated gpu is on the cpu die, the gpu fan is for the discrete one
+        if gpu
+            .get_device_info()
+            .is_some_and(|device_info| device_info.is_integrated())
+        {
+            return;
+        }
+        // a runtime suspended gpu produces no heat and reports no temperature
+        let temp = if gpu.get_power_state() == PowerState::Suspended {
+            Temp::default()
+        } else {
+            gpu.get_temp().clone()
+        };
+        self.gpu_current_temp = match self.gpu_current_temp.take() {
+            Some(current) if current.get_value() > temp.get_value() => Some(current),
+            _ => Some(temp),
+        };
     }
-    fn visit_gpu(&mut self, _gpu: &crate::component::gpu::Gpu) {
-        // TODO: Update GPU temperature
-        self.gpu_current_temp = Temp::default();
+    fn visit_net(&mut self, _net: &crate::component::net::Net) {}
+    fn visit_mem(&mut self, _mem: &crate::component::mem::Mem) {}
+    fn visit_power_supply(&mut self, _power_supply: &crate::component::power_supply::PowerSupply) {}
+    fn visit_storage(&mut self, storage: &crate::component::storage::Storage) {
+        self.storage_current_temp = storage
+            .get_hottest()
+            .map(|drive| drive.get_composite().clone());
+        self.storage_over_warning = storage
+            .get_drives()
+            .iter()
+            .any(|drive| drive.is_over_warning());
     }
 }
@@ -2,9 +2,9 @@
 use lib::field::temp::Temp;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct PidCfg {
-    pub target_temp: f32,
+    pub target_temp: f32, // In °C
     pub kp: f32,
     pub ki: f32,
     pub kd: f32,
@@ -12,6 +12,19 @@ pub struct PidCfg {
     pub smoothing_factor: f32,
 }
 
+// Configs written before the unit types held the target in millidegrees, no fan target
+// is anywhere near 1000 °C
+const LEGACY_TARGET_TEMP: f32 = 1000.0;
+
+impl PidCfg {
+    /// Convert a target temperature saved in millidegrees to °C
+    pub fn migrate(&mut self) {
+        if self.target_temp > LEGACY_TARGET_TEMP {
+            self.target_temp /= 1000.0;
+        }
+    }
+}
+
 pub struct PidControler {
     cfg: PidCfg,
     prev_error: f32,
@@ -36,10 +49,12 @@ impl PidControler {
 use super::ControlerAlgo;
 impl ControlerAlgo for PidControler {
     fn update(&mut self, current_temp: &Temp) -> u32 {
-        let current_temp = current_temp.get_value() as f32;
+        // the gains and clamps are tuned for an error in millidegrees
+        let current_temp = current_temp.get_value().get_value() as f32;
+        let target_temp = self.cfg.target_temp * 1000.0;
         let delta_time = self.last_update_time.elapsed().unwrap().as_secs_f32();
         self.last_update_time = std::time::SystemTime::now();
-        let error = (current_temp - self.cfg.target_temp).clamp(0.0, 8000.0);
+        let error = (current_temp - target_temp).clamp(0.0, 8000.0);
         self.integral += error * delta_time;
         self.integral = self.integral.clamp(0.0, 2000.0);
         let derivative = (error - self.prev_error) / delta_time;
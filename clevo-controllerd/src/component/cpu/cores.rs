@@ -0,0 +1,209 @@
+use super::{CpuError, Result};
+use crate::lowlevel::{accessor::fd, procfs};
+use lib::field::{
+    cores::{CoreState, TargetCoreMask},
+    unit::{Kilohertz, Megahertz},
+};
+
+pub const CPU_SYSFS_PATH: &str = "/sys/devices/system/cpu";
+
+// (frequency, usage in percentage) of every core
+type CoreSample = (Vec<Option<Megahertz>>, Vec<Option<f32>>);
+
+// Cpu index list from /sys/devices/system/cpu/cpu*, sorted
+pub fn list_cpus() -> Vec<usize> {
+    let mut cpus: Vec<usize> = match std::fs::read_dir(CPU_SYSFS_PATH) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                entry
+                    .file_name()
+                    .to_str()
+                    .and_then(|name| name.strip_prefix("cpu"))
+                    .and_then(|index| index.parse().ok())
+            })
+            .collect(),
+        Err(_) => vec![],
+    };
+    cpus.sort();
+    cpus
+}
+
+// (busy, total) jiffies of every online cpu listed in /proc/stat, indexed by cpu number
+fn read_cpu_times(cpu_count: usize) -> Result<Vec<Option<(u64, u64)>>> {
+    let stat =
+        std::fs::read_to_string(procfs::proc_path("stat")).map_err(|_| CpuError::FdReadError)?;
+    let mut times = vec![None; cpu_count];
+    for line in stat.lines() {
+        let mut parts = line.split_whitespace();
+        let Some(index) = parts
+            .next()
+            .and_then(|name| name.strip_prefix("cpu"))
+            .and_then(|index| index.parse::<usize>().ok())
+        else {
+            continue;
+        };
+        // user nice system idle iowait irq softirq steal, guest time is already in user
+        let values: Vec<u64> = parts
+            .take(8)
+            .map(|v| v.parse())
+            .collect::<std::result::Result<_, _>>()?;
+        let total: u64 = values.iter().sum();
+        let idle = values.get(3).unwrap_or(&0) + values.get(4).unwrap_or(&0);
+        if index < cpu_count {
+            times[index] = Some((total - idle, total));
+        }
+    }
+    Ok(times)
+}
+
+/// Per core usage, frequency and online state, indexed by cpu number so offline cores
+/// keep their place in every vector
+#[derive(Debug)]
+pub struct CoreMonitor {
+    cpu_count: usize,
+    hotpluggable: Vec<bool>, // cpuN/online only exists for cores that can be hotplugged
+    online: Vec<bool>,
+    last_times: Vec<Option<(u64, u64)>>,
+}
+
+impl CoreMonitor {
+    pub fn init() -> Result<Self> {
+        let cpus = list_cpus();
+        let cpu_count = cpus.last().map_or(0, |cpu| cpu + 1);
+        let hotpluggable = (0..cpu_count)
+            .map(|cpu| {
+                std::path::Path::new(&format!("{}/cpu{}/online", CPU_SYSFS_PATH, cpu)).exists()
+            })
+            .collect();
+        let mut monitor = CoreMonitor {
+            cpu_count,
+            hotpluggable,
+            online: vec![false; cpu_count],
+            last_times: read_cpu_times(cpu_count)?,
+        };
+        monitor.refresh_online()?;
+        Ok(monitor)
+    }
+
+    fn refresh_online(&mut self) -> Result<()> {
+        for cpu in 0..self.cpu_count {
+            self.online[cpu] = if self.hotpluggable[cpu] {
+                let fd = fd::Fd::new(
+                    &format!("{}/cpu{}/online", CPU_SYSFS_PATH, cpu),
+                    libc::O_RDONLY,
+                )?;
+                fd.read(8)? == "1"
+            } else {
+                true
+            };
+        }
+        Ok(())
+    }
+
+    pub fn get_state(&self) -> CoreState {
+        CoreState::new(self.online.clone(), self.hotpluggable.clone())
+    }
+
+    /// Whether any core can be taken offline, cpu0 never can
+    pub fn can_hotplug(&self) -> bool {
+        self.hotpluggable
+            .iter()
+            .skip(1)
+            .any(|hotpluggable| *hotpluggable)
+    }
+
+    /// Refresh online state and return frequency and usage of every core
+    pub fn refresh(&mut self) -> Result<CoreSample> {
+        self.refresh_online()?;
+        let times = read_cpu_times(self.cpu_count)?;
+        let usage = times
+            .iter()
+            .zip(self.last_times.iter())
+            .enumerate()
+            .map(|(cpu, (current, last))| match (current, last) {
+                (Some((busy, total)), Some((last_busy, last_total))) if self.online[cpu] => {
+                    let total = total.saturating_sub(*last_total);
+                    if total == 0 {
+                        Some(0.0)
+                    } else {
+                        Some(busy.saturating_sub(*last_busy) as f32 * 100.0 / total as f32)
+                    }
+                }
+                (Some(_), None) if self.online[cpu] => Some(0.0),
+                _ => None,
+            })
+            .collect();
+        self.last_times = times;
+
+        let freq = (0..self.cpu_count)
+            .map(|cpu| {
+                if !self.online[cpu] {
+                    return None;
+                }
+                // scaling_cur_freq is in kHz, report 0 if cpufreq is not available
+                let freq = fd::Fd::new(
+                    &format!("{}/cpu{}/cpufreq/scaling_cur_freq", CPU_SYSFS_PATH, cpu),
+                    libc::O_RDONLY,
+                )
+                .and_then(|fd| fd.read(32))
+                .ok()
+                .and_then(|freq| freq.parse().ok())
+                .map_or(Megahertz::default(), |freq| Kilohertz::new(freq).as_mhz());
+                Some(freq)
+            })
+            .collect();
+        Ok((freq, usage))
+    }
+
+    /// Bring cores online or offline, cpu0 and the last online core are never taken offline
+    pub fn set_mask(&mut self, target: &TargetCoreMask) -> Result<()> {
+        let mask = target.get_mask();
+        if mask.len() > self.cpu_count {
+            return Err(CpuError::InvalidCoreMask(format!(
+                "mask has {} cores, only {} present",
+                mask.len(),
+                self.cpu_count
+            )));
+        }
+        self.refresh_online()?;
+        let mut online = self.online.clone();
+        for (cpu, target_online) in mask.iter().enumerate() {
+            if online[cpu] == *target_online {
+                continue;
+            }
+            if cpu == 0 && !target_online {
+                return Err(CpuError::InvalidCoreMask(
+                    "cpu0 can't be taken offline".to_string(),
+                ));
+            }
+            if !self.hotpluggable[cpu] {
+                return Err(CpuError::InvalidCoreMask(format!(
+                    "cpu{} is not hotpluggable",
+                    cpu
+                )));
+            }
+            online[cpu] = *target_online;
+        }
+        if !online.iter().any(|online| *online) {
+            return Err(CpuError::InvalidCoreMask(
+                "can't take the last online core offline".to_string(),
+            ));
+        }
+
+        // bring cores online first, so there is always an online core left
+        for target_online in [true, false] {
+            for (cpu, online) in online.iter().enumerate() {
+                if *online == target_online && self.online[cpu] != target_online {
+                    let fd = fd::Fd::new(
+                        &format!("{}/cpu{}/online", CPU_SYSFS_PATH, cpu),
+                        libc::O_WRONLY,
+                    )?;
+                    fd.write(if target_online { b"1" } else { b"0" })?;
+                    self.online[cpu] = target_online;
+                }
+            }
+        }
+        Ok(())
+    }
+}
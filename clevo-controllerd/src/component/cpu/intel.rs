@@ -1,21 +1,55 @@
-use crate::component::cpu::CpuError;
-use crate::{component::Component, lowlevel::accessor::fd};
+use crate::component::cpu::{
+    CpuError,
+    cores::{CPU_SYSFS_PATH, CoreMonitor},
+    throttle::ThrottleMonitor,
+};
+use crate::{
+    component::{Component, unsupported},
+    lowlevel::accessor::fd,
+};
+use lib::field::{
+    event::Event,
+    throttle::{Throttle, ThrottleReason},
+    unit::{Megahertz, MilliCelsius, Milliwatts},
+};
 use std::collections::HashMap;
 
 // TODO: Self impl instead of use sysinfo crates
 #[derive(Debug)]
 pub struct IntelCpu {
-    sysinfo: sysinfo::System,
+    core_monitor: CoreMonitor,
     fd_list: HashMap<String, fd::Fd>,
     last_refresh_time_stamp: std::time::Instant,
     energy_comsumption: u64,
+    throttle_monitor: ThrottleMonitor,
 
     index: u8, // preserve, not use
     name: String,
-    freq: Vec<u64>,
-    usage: Vec<f32>,
-    period_power: u64,
-    temp: u64,
+    freq: Vec<Option<Megahertz>>,
+    usage: Vec<Option<f32>>,
+    period_power: Milliwatts,
+    temp: MilliCelsius,
+    throttle: Throttle,
+    events: Vec<Event>, // Not yet polled
+}
+
+// Throttling because something is too hot, as opposed to power or turbo limits
+fn is_thermal_throttle(throttle: &Throttle) -> bool {
+    throttle.get_reasons().iter().any(|reason| {
+        matches!(
+            reason,
+            ThrottleReason::Thermal | ThrottleReason::Prochot | ThrottleReason::CriticalTemp
+        )
+    })
+}
+
+const PLATFORM_PROFILE_CHOICES: &str = "/sys/firmware/acpi/platform_profile_choices";
+
+// Space separated choices of a sysfs file, empty if the file doesn't exist
+fn read_choices(path: &str) -> Vec<String> {
+    std::fs::read_to_string(path)
+        .map(|choices| choices.split_whitespace().map(str::to_string).collect())
+        .unwrap_or_default()
 }
 
 /// for example:
@@ -34,18 +68,18 @@ fn add_fd(
         match fd::Fd::new(&fd_path, libc::O_RDONLY) {
             Ok(fd) => {
                 let read_value = fd.read(32);
-                if let Ok(read_value) = read_value {
-                    if read_value == value {
-                        let fd_path = format!("{}{}/{}", base_path, index, key_to_add);
-                        let fd = fd::Fd::new(&fd_path, libc::O_RDONLY);
-                        if let Ok(fd) = fd {
-                            fd_list.insert(key_to_add.to_string(), fd);
-                            break;
-                        }
+                if let Ok(read_value) = read_value
+                    && read_value == value
+                {
+                    let fd_path = format!("{}{}/{}", base_path, index, key_to_add);
+                    let fd = fd::Fd::new(&fd_path, libc::O_RDONLY);
+                    if let Ok(fd) = fd {
+                        fd_list.insert(key_to_add.to_string(), fd);
+                        break;
                     }
                 }
             }
-            Err(fd::FdError::OpenError) => {
+            Err(fd::FdError::OpenError(_)) => {
                 panic!(
                     "Failed to open fd, Try to run as root or check the path: {}",
                     fd_path
@@ -67,14 +101,17 @@ impl IntelCpu {
                 sysinfo.cpus()[0].vendor_id(),
                 sysinfo.cpus()[0].brand()
             ),
-            freq: sysinfo.cpus().iter().map(|cpu| cpu.frequency()).collect(),
-            usage: sysinfo.cpus().iter().map(|cpu| cpu.cpu_usage()).collect(),
-            sysinfo,
+            freq: vec![],
+            usage: vec![],
+            core_monitor: CoreMonitor::init()?,
             fd_list: HashMap::new(),
             energy_comsumption: 0,
+            throttle_monitor: ThrottleMonitor::init()?,
             last_refresh_time_stamp: std::time::Instant::now(),
-            period_power: 0,
-            temp: 0,
+            period_power: Milliwatts::default(),
+            temp: MilliCelsius::default(),
+            throttle: Throttle::default(),
+            events: vec![],
         };
         // TODO: Bad to hardcode these, a better way should be used
         add_fd(
@@ -119,43 +156,43 @@ impl IntelCpu {
             }
         };
         // To prevent sub overflow when resume from suspend or power metics reset
+        // uJ per ms is mW
         if current_energy_comsumption > self.energy_comsumption {
-            self.period_power = (current_energy_comsumption - self.energy_comsumption)
-                / self.last_refresh_time_stamp.elapsed().as_millis() as u64;
+            self.period_power = Milliwatts::new(
+                (current_energy_comsumption - self.energy_comsumption)
+                    / self.last_refresh_time_stamp.elapsed().as_millis() as u64,
+            );
         }
         self.energy_comsumption = current_energy_comsumption;
         self.last_refresh_time_stamp = std::time::Instant::now();
 
         // refresh cpu temperature
         self.temp = match self.fd_list.get("temp") {
-            Some(fd) => fd.read(32)?.parse()?,
+            Some(fd) => MilliCelsius::new(fd.read(32)?.parse()?),
             None => {
                 return Err(CpuError::FdNotFound);
             }
         };
 
-        self.sysinfo.refresh_cpu_all();
-        // refresh cpu usage
-        self.usage = self
-            .sysinfo
-            .cpus()
-            .iter()
-            .map(|cpu| cpu.cpu_usage())
-            .collect();
-        // refresh cpu frequency
-        self.freq = self
-            .sysinfo
-            .cpus()
-            .iter()
-            .map(|cpu| cpu.frequency())
-            .collect();
+        // refresh throttle events since last refresh, alert when heat starts throttling
+        let throttle = self.throttle_monitor.refresh()?;
+        if is_thermal_throttle(&throttle) && !is_thermal_throttle(&self.throttle) {
+            self.events.push(Event::ThermalAlert {
+                source: self.name.clone(),
+                temp: Temp::new(self.temp),
+            });
+        }
+        self.throttle = throttle;
+
+        // refresh cpu frequency and usage, None for offline cores
+        (self.freq, self.usage) = self.core_monitor.refresh()?;
         Ok(())
     }
 }
 
-use lib::field::category::Category;
 use lib::field::{CpuStatus, desc::Desc, freq::Freq, power::Power, temp::Temp, usage::Usage};
-use lib::proto::{MsgCommand, MsgError};
+use lib::field::{capability::Capabilities, category::Category};
+use lib::proto::{MsgCommand, MsgError, Request, Response, Status};
 impl Component for IntelCpu {
     fn get_desc(&self) -> Desc {
         Desc::new(Category::Cpu, self.index, &self.name)
@@ -164,33 +201,38 @@ impl Component for IntelCpu {
     fn refresh_status(&mut self) -> std::result::Result<(), crate::component::ComponentError> {
         self.refresh().map_err(|e| e.into())
     }
-    fn handle_command(
-        &mut self,
-        command: &MsgCommand,
-        _payload: &[Vec<u8>],
-    ) -> Result<Vec<Vec<u8>>, MsgError> {
-        let mut reply_payload = vec![];
-        match command {
-            MsgCommand::GetStatus => {
-                let cpu_status = CpuStatus {
-                    freq: Freq::new(self.freq.clone()),
-                    power: Power::new(self.period_power),
-                    temp: Temp::new(self.temp),
-                    usage: Usage::new(self.usage.clone()),
-                };
-                reply_payload.push(cpu_status.serialize().unwrap());
-            }
-            MsgCommand::SetFreq => {
-                println!("SetFreq");
-                unimplemented!()
-            }
-            _ => {
-                MsgError::UnsupportedOperation(format!(
-                    "Operation not supported by the hardware:{}",
-                    command
-                ));
+    fn poll_events(&mut self) -> Vec<Event> {
+        std::mem::take(&mut self.events)
+    }
+    fn get_capabilities(&self) -> Capabilities {
+        let mut commands = vec![MsgCommand::GetStatus];
+        if self.core_monitor.can_hotplug() {
+            commands.push(MsgCommand::SetCoreMask);
+        }
+        let mut capabilities = Capabilities::new(commands);
+        capabilities.set_governors(read_choices(&format!(
+            "{}/cpu0/cpufreq/scaling_available_governors",
+            CPU_SYSFS_PATH
+        )));
+        capabilities.set_power_modes(read_choices(PLATFORM_PROFILE_CHOICES));
+        capabilities
+    }
+    fn handle_request(&mut self, request: &Request) -> Result<Response, MsgError> {
+        match request {
+            Request::GetStatus => Ok(Response::Status(Status::Cpu(CpuStatus {
+                freq: Freq::new(self.freq.clone()),
+                power: Power::new(self.period_power),
+                temp: Temp::new(self.temp),
+                usage: Usage::new(self.usage.clone()),
+                throttle: self.throttle.clone(),
+                cores: self.core_monitor.get_state(),
+            }))),
+            Request::SetCoreMask(target) => {
+                self.core_monitor.set_mask(target)?;
+                Ok(Response::CoreState(self.core_monitor.get_state()))
             }
+            // SetFreq isn't implemented for intel cpus yet
+            _ => Err(unsupported(request)),
         }
-        Ok(reply_payload)
     }
 }
@@ -1,5 +1,8 @@
+pub mod cores;
 pub mod intel;
+pub mod throttle;
 use crate::lowlevel::accessor::fd;
+use lib::proto::MsgError;
 
 #[derive(Debug, thiserror::Error)]
 pub enum CpuError {
@@ -17,6 +20,18 @@ pub enum CpuError {
     TooFrequent,
     #[error("timer interval too large, may overflow")]
     Overflow,
+    #[error("invalid core mask: {0}")]
+    InvalidCoreMask(String),
 }
 
 type Result<T> = std::result::Result<T, CpuError>;
+
+impl From<CpuError> for MsgError {
+    fn from(err: CpuError) -> Self {
+        match err {
+            CpuError::FdError(err) => err.into(),
+            CpuError::InvalidCoreMask(_) => MsgError::InvalidCommand(err.to_string()),
+            _ => MsgError::DeviceError(err.to_string()),
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use super::Result;
+use super::cores::{CPU_SYSFS_PATH, list_cpus};
+use crate::lowlevel::accessor::{fd, msr};
+use lib::field::throttle::{Throttle, ThrottleReason};
+
+const MSR_CORE_PERF_LIMIT_REASONS: u32 = 0x64F;
+
+// (bit, reason) pairs of the status bits, the sticky log bits are ignored
+const PERF_LIMIT_REASONS_BITS: [(u8, ThrottleReason); 11] = [
+    (0, ThrottleReason::Prochot),
+    (1, ThrottleReason::Thermal),
+    (4, ThrottleReason::Other),   // Residency state regulation
+    (5, ThrottleReason::Thermal), // Running average thermal limit
+    (6, ThrottleReason::VrThermal),
+    (7, ThrottleReason::VrCurrent),
+    (8, ThrottleReason::Other), // Electrical design point
+    (10, ThrottleReason::Pl1),
+    (11, ThrottleReason::Pl2),
+    (12, ThrottleReason::MaxTurbo),
+    (13, ThrottleReason::Other), // Turbo transition attenuation
+];
+
+fn decode_bits(value: u64, bits: &[(u8, ThrottleReason)], reasons: &mut Vec<ThrottleReason>) {
+    for (bit, reason) in bits {
+        if (value >> bit) & 1 == 1 && !reasons.contains(reason) {
+            reasons.push(reason.clone());
+        }
+    }
+}
+
+fn read_count(fd: &fd::Fd) -> Result<u64> {
+    Ok(fd.read(32)?.parse()?)
+}
+
+fn open_core_count(cpu: usize) -> Option<fd::Fd> {
+    fd::Fd::new(
+        &format!(
+            "{}/cpu{}/thermal_throttle/core_throttle_count",
+            CPU_SYSFS_PATH, cpu
+        ),
+        libc::O_RDONLY,
+    )
+    .ok()
+}
+
+fn open_package_count(cpu: usize) -> Option<fd::Fd> {
+    fd::Fd::new(
+        &format!(
+            "{}/cpu{}/thermal_throttle/package_throttle_count",
+            CPU_SYSFS_PATH, cpu
+        ),
+        libc::O_RDONLY,
+    )
+    .ok()
+}
+
+// The package counter read through one of the cpus of the package, any other one takes over
+// when it goes offline
+#[derive(Debug)]
+struct PackageCounter {
+    cpus: Vec<usize>,
+    fd: Option<fd::Fd>,
+    count: u64,
+}
+
+/// Track the thermal_throttle counters of every cpu and turn them into events per refresh
+/// interval, decode the throttle reasons from the msr when the msr driver is available
+#[derive(Debug)]
+pub struct ThrottleMonitor {
+    core_fds: Vec<Option<fd::Fd>>, // indexed by cpu number, None if not exposed
+    core_counts: Vec<u64>,
+    packages: Vec<PackageCounter>, // one per physical package
+    msr: Option<msr::MsrAccessor>,
+}
+
+impl ThrottleMonitor {
+    pub fn init() -> Result<Self> {
+        let cpus = list_cpus();
+        let cpu_count = cpus.last().map_or(0, |cpu| cpu + 1);
+        let mut core_fds: Vec<Option<fd::Fd>> = (0..cpu_count).map(|_| None).collect();
+        let mut package_ids = vec![];
+        let mut packages: Vec<PackageCounter> = vec![];
+        for cpu in cpus {
+            core_fds[cpu] = open_core_count(cpu);
+            let package = fd::Fd::new(
+                &format!("{}/cpu{}/topology/physical_package_id", CPU_SYSFS_PATH, cpu),
+                libc::O_RDONLY,
+            )
+            .and_then(|fd| fd.read(32));
+            let Ok(package) = package else {
+                continue;
+            };
+            match package_ids.iter().position(|id| *id == package) {
+                Some(index) => packages[index].cpus.push(cpu),
+                None => {
+                    package_ids.push(package);
+                    packages.push(PackageCounter {
+                        cpus: vec![cpu],
+                        fd: None, // opened by the first refresh
+                        count: 0,
+                    });
+                }
+            }
+        }
+        let mut monitor = ThrottleMonitor {
+            core_counts: vec![0; core_fds.len()],
+            core_fds,
+            packages,
+            msr: Some(msr::MsrAccessor::new()).filter(|msr| msr.is_available(0)),
+        };
+        // take the first snapshot, so the first refresh only reports new events
+        monitor.refresh()?;
+        Ok(monitor)
+    }
+
+    pub fn refresh(&mut self) -> Result<Throttle> {
+        let mut core_events = vec![0; self.core_fds.len()];
+        for (index, fd) in self.core_fds.iter_mut().enumerate() {
+            // the thermal_throttle directory goes away with an offline core and
+            // comes back with a reset counter, reopen it once the core is back
+            if fd.is_none() {
+                *fd = open_core_count(index);
+                self.core_counts[index] = 0;
+            }
+            match fd.as_ref().map(read_count) {
+                Some(Ok(count)) => {
+                    core_events[index] = count.saturating_sub(self.core_counts[index]);
+                    self.core_counts[index] = count;
+                }
+                Some(Err(_)) => *fd = None,
+                None => {}
+            }
+        }
+        let mut package_events = 0;
+        for package in &mut self.packages {
+            // every cpu keeps its own copy of the package counter, the first read through
+            // another cpu only sets where to count from
+            let reopened = package.fd.is_none();
+            if reopened {
+                package.fd = package.cpus.iter().find_map(|cpu| open_package_count(*cpu));
+            }
+            match package.fd.as_ref().map(read_count) {
+                Some(Ok(count)) => {
+                    if !reopened {
+                        package_events += count.saturating_sub(package.count);
+                    }
+                    package.count = count;
+                }
+                Some(Err(_)) => package.fd = None,
+                None => {}
+            }
+        }
+
+        let mut reasons = vec![];
+        if let Some(msr) = &self.msr {
+            if let Ok(status) = msr.package_therm_status() {
+                let status_reasons = [
+                    (status.thermal(), ThrottleReason::Thermal),
+                    (status.prochot(), ThrottleReason::Prochot),
+                    (status.critical(), ThrottleReason::CriticalTemp),
+                    (status.power_limit(), ThrottleReason::PowerLimit),
+                ];
+                for (active, reason) in status_reasons {
+                    if active {
+                        reasons.push(reason);
+                    }
+                }
+            }
+            // not every model implements the perf limit reasons msr
+            if let Ok(value) = msr.read(0, MSR_CORE_PERF_LIMIT_REASONS) {
+                decode_bits(value, &PERF_LIMIT_REASONS_BITS, &mut reasons);
+            }
+        }
+        Ok(Throttle::new(core_events, package_events, reasons))
+    }
+}
@@ -1,10 +1,15 @@
-use crate::{component::Component, lowlevel::accessor::ec};
+use crate::{
+    component::{Component, unsupported},
+    lowlevel::accessor::ec,
+};
 use lib::{
     field::{
+        capability::{Capabilities, Range},
         category::Category,
-        fan_speed::{FanIndex, FanSpeed, TargetFanSpeed},
+        event::Event,
+        fan_speed::{FanIndex, FanMode, FanSpeed, FanValues},
     },
-    proto::MsgCommand,
+    proto::{MsgCommand, MsgError, Request, Response},
 };
 
 const EC_CPU_FAN_RPM_HI_ADDR: u8 = 0xD0;
@@ -18,6 +23,9 @@ pub struct Fan {
     ec: ec::EcAccessor,
     cpu_fan_speed: FanSpeed,
     gpu_fan_speed: FanSpeed,
+    cpu_fan_mode: FanMode, // The EC is in charge until a duty is set
+    gpu_fan_mode: FanMode,
+    events: Vec<Event>, // Not yet polled
 }
 
 impl Default for Fan {
@@ -32,30 +40,50 @@ impl Fan {
             ec: ec::EcAccessor::new(),
             cpu_fan_speed: FanSpeed::default(),
             gpu_fan_speed: FanSpeed::default(),
+            cpu_fan_mode: FanMode::Auto,
+            gpu_fan_mode: FanMode::Auto,
+            events: vec![],
+        }
+    }
+
+    // Remember the mode of a fan, with an event when it changes
+    fn set_fan_mode(&mut self, fan: FanIndex, mode: FanMode) {
+        let current = match fan {
+            FanIndex::Cpu => &mut self.cpu_fan_mode,
+            FanIndex::Gpu => &mut self.gpu_fan_mode,
+            FanIndex::All => {
+                self.set_fan_mode(FanIndex::Cpu, mode);
+                self.set_fan_mode(FanIndex::Gpu, mode);
+                return;
+            }
+        };
+        if *current != mode {
+            *current = mode;
+            self.events.push(Event::FanModeChanged { fan, mode });
         }
     }
 
-    pub fn get_fan_rpm(&self, category: Category) -> u32 {
+    pub fn get_fan_rpm(&self, category: Category) -> Result<u32, ec::EcError> {
         let hi;
         let lo;
         match category {
             Category::Cpu => {
-                hi = self.ec.read_byte(EC_CPU_FAN_RPM_HI_ADDR);
-                lo = self.ec.read_byte(EC_CPU_FAN_RPM_LO_ADDR);
+                hi = self.ec.read_byte(EC_CPU_FAN_RPM_HI_ADDR)?;
+                lo = self.ec.read_byte(EC_CPU_FAN_RPM_LO_ADDR)?;
             }
             Category::Gpu => {
-                hi = self.ec.read_byte(EC_GPU_FAN_RPM_HI_ADDR);
-                lo = self.ec.read_byte(EC_GPU_FAN_RPM_LO_ADDR);
+                hi = self.ec.read_byte(EC_GPU_FAN_RPM_HI_ADDR)?;
+                lo = self.ec.read_byte(EC_GPU_FAN_RPM_LO_ADDR)?;
             }
             _ => {
                 panic!("Invalid fan category");
             }
         }
         let rpm = ((hi as u16) << 8) | (lo as u16);
-        if rpm == 0 { 0 } else { 2156220u32 / rpm as u32 }
+        Ok(if rpm == 0 { 0 } else { 2156220u32 / rpm as u32 })
     }
 
-    pub fn set_fan_speed(&self, category: Category, duty: u64) {
+    pub fn set_fan_speed(&self, category: Category, duty: u64) -> Result<(), ec::EcError> {
         assert!(
             (0..=100).contains(&duty),
             "Duty cycle must be between 0 and 100"
@@ -64,12 +92,12 @@ impl Fan {
             EC_SET_FAN_SPEED_CMD,
             category as u8,
             ((duty as f32 * 255.0) / 100.0) as u8,
-        );
+        )
     }
 
-    pub fn set_fan_auto(&self, category: Category) {
+    pub fn set_fan_auto(&self, category: Category) -> Result<(), ec::EcError> {
         self.ec
-            .cmd_write(EC_SET_FAN_SPEED_CMD, EC_SET_FAN_AUTO_ADDR, category as u8);
+            .cmd_write(EC_SET_FAN_SPEED_CMD, EC_SET_FAN_AUTO_ADDR, category as u8)
     }
 }
 
@@ -78,67 +106,60 @@ impl Component for Fan {
         lib::field::desc::Desc::new(Category::Fan, 0, "Fan")
     }
     fn refresh_status(&mut self) -> Result<(), crate::component::ComponentError> {
-        self.cpu_fan_speed.set_rpm(self.get_fan_rpm(Category::Cpu));
-        self.gpu_fan_speed.set_rpm(self.get_fan_rpm(Category::Gpu));
+        self.cpu_fan_speed.set_rpm(self.get_fan_rpm(Category::Cpu)?);
+        self.gpu_fan_speed.set_rpm(self.get_fan_rpm(Category::Gpu)?);
         Ok(())
     }
-    fn handle_command(
-        &mut self,
-        command: &lib::proto::MsgCommand,
-        payload: &[Vec<u8>],
-    ) -> Result<Vec<Vec<u8>>, lib::proto::MsgError> {
-        let mut reply_payload = vec![];
-        reply_payload.extend_from_slice(payload);
-        if !payload.is_empty() {
-            let fan_index = FanIndex::deserialize(&payload[0]).unwrap();
-            match command {
-                MsgCommand::GetFanSpeed => match fan_index {
-                    FanIndex::All => {
-                        reply_payload.push(self.cpu_fan_speed.serialize().unwrap());
-                        reply_payload.push(self.gpu_fan_speed.serialize().unwrap());
-                    }
-                    FanIndex::Cpu => {
-                        reply_payload.push(self.cpu_fan_speed.serialize().unwrap());
-                    }
-                    FanIndex::Gpu => {
-                        reply_payload.push(self.gpu_fan_speed.serialize().unwrap());
-                    }
-                },
-                MsgCommand::SetFanSpeed => match fan_index {
-                    FanIndex::Cpu => {
-                        let target_fan_speed = TargetFanSpeed::deserialize(&payload[1]).unwrap();
-                        self.set_fan_speed(Category::Cpu, target_fan_speed.get_duty() as u64);
-                    }
-                    FanIndex::Gpu => {
-                        let target_fan_speed = TargetFanSpeed::deserialize(&payload[1]).unwrap();
-                        self.set_fan_speed(Category::Gpu, target_fan_speed.get_duty() as u64);
-                    }
-                    FanIndex::All => {
-                        let cpu_target_fan_speed =
-                            TargetFanSpeed::deserialize(&payload[1]).unwrap();
-                        let gpu_target_fan_speed =
-                            TargetFanSpeed::deserialize(&payload[2]).unwrap();
-                        self.set_fan_speed(Category::Cpu, cpu_target_fan_speed.get_duty() as u64);
-                        self.set_fan_speed(Category::Gpu, gpu_target_fan_speed.get_duty() as u64);
-                    }
-                },
-                MsgCommand::SetFanAuto => match fan_index {
-                    FanIndex::Cpu => {
-                        self.set_fan_auto(Category::Cpu);
-                    }
-                    FanIndex::Gpu => {
-                        self.set_fan_auto(Category::Gpu);
-                    }
-                    FanIndex::All => {
-                        self.set_fan_auto(Category::Cpu);
-                        self.set_fan_auto(Category::Gpu);
-                    }
+    fn poll_events(&mut self) -> Vec<Event> {
+        std::mem::take(&mut self.events)
+    }
+    fn get_capabilities(&self) -> Capabilities {
+        let mut capabilities = Capabilities::new(vec![
+            MsgCommand::GetFanSpeed,
+            MsgCommand::SetFanSpeed,
+            MsgCommand::SetFanAuto,
+        ]);
+        capabilities.set_fans(vec![FanIndex::Cpu, FanIndex::Gpu]);
+        capabilities.set_duty(Range::new(0, 100));
+        capabilities
+    }
+    fn handle_request(&mut self, request: &Request) -> Result<Response, MsgError> {
+        match request {
+            Request::GetFanSpeed(fan) => Ok(Response::FanSpeed(match fan {
+                FanIndex::Cpu => FanValues::Cpu(self.cpu_fan_speed.clone()),
+                FanIndex::Gpu => FanValues::Gpu(self.gpu_fan_speed.clone()),
+                FanIndex::All => FanValues::All {
+                    cpu: self.cpu_fan_speed.clone(),
+                    gpu: self.gpu_fan_speed.clone(),
                 },
-                _ => {}
+            })),
+            Request::SetFanSpeed(duty) => {
+                if let Some(target) = duty.values().iter().find(|target| target.get_duty() > 100) {
+                    return Err(MsgError::InvalidCommand(format!(
+                        "fan duty {}% above 100%",
+                        target.get_duty()
+                    )));
+                }
+                if let Some(target) = duty.get_cpu() {
+                    self.set_fan_speed(Category::Cpu, target.get_duty() as u64)?;
+                }
+                if let Some(target) = duty.get_gpu() {
+                    self.set_fan_speed(Category::Gpu, target.get_duty() as u64)?;
+                }
+                self.set_fan_mode(duty.get_index(), FanMode::Manual);
+                Ok(Response::Done)
+            }
+            Request::SetFanAuto(fan) => {
+                if *fan != FanIndex::Gpu {
+                    self.set_fan_auto(Category::Cpu)?;
+                }
+                if *fan != FanIndex::Cpu {
+                    self.set_fan_auto(Category::Gpu)?;
+                }
+                self.set_fan_mode(*fan, FanMode::Auto);
+                Ok(Response::Done)
             }
-        } else {
-            panic!("Attempt to communicate with 'fan' withot index payload ");
+            _ => Err(unsupported(request)),
         }
-        Ok(reply_payload)
     }
 }
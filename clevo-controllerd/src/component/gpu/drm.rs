@@ -0,0 +1,295 @@
+use super::{GpuBackend, GpuError, Result, read_power_state};
+use crate::lowlevel::accessor::fd;
+use lib::field::{
+    GpuStatus,
+    capability::{Capabilities, Range},
+    desc::DeviceInfo,
+    freq::{Freq, TargetFreq},
+    power::{Power, PowerState},
+    temp::Temp,
+    unit::{Megahertz, MilliCelsius, Milliwatts},
+    usage::Usage,
+};
+use lib::proto::MsgCommand;
+use std::time::Instant;
+
+pub const DRM_SYSFS_PATH: &str = "/sys/class/drm";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Driver {
+    I915,
+    Amdgpu,
+}
+
+fn read_value(path: &str) -> Option<String> {
+    fd::Fd::new(path, libc::O_RDONLY)
+        .and_then(|fd| fd.read(32))
+        .ok()
+}
+
+fn read_u64(path: &str) -> Option<u64> {
+    read_value(path).and_then(|value| value.parse().ok())
+}
+
+// The frequency files of i915 are in MHz
+fn read_mhz(path: &str) -> Option<Megahertz> {
+    read_value(path)
+        .and_then(|value| value.parse().ok())
+        .map(Megahertz::new)
+}
+
+fn write_value(path: &str, value: &str) -> Result<()> {
+    let fd = fd::Fd::new(path, libc::O_WRONLY)?;
+    fd.write(value.as_bytes())?;
+    Ok(())
+}
+
+// Card directories under /sys/class/drm, connectors like card0-eDP-1 are skipped
+pub fn list_cards() -> Vec<String> {
+    let mut cards: Vec<String> = match std::fs::read_dir(DRM_SYSFS_PATH) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .filter(|name| {
+                name.strip_prefix("card")
+                    .is_some_and(|index| index.parse::<u32>().is_ok())
+            })
+            .map(|name| format!("{}/{}", DRM_SYSFS_PATH, name))
+            .collect(),
+        Err(_) => vec![],
+    };
+    cards.sort();
+    cards
+}
+
+// amdgpu lists every dpm level as "<level>: <freq>Mhz", the current one is marked with '*'
+fn parse_dpm_levels(levels: &str) -> (Option<Megahertz>, Option<Megahertz>, Option<Megahertz>) {
+    let mut current = None;
+    let mut min = None;
+    let mut max = None;
+    for line in levels.lines() {
+        let Some(freq) = line
+            .split_whitespace()
+            .nth(1)
+            .and_then(|freq| freq.to_lowercase().strip_suffix("mhz")?.parse().ok())
+            .map(Megahertz::new)
+        else {
+            continue;
+        };
+        min = Some(min.map_or(freq, |min: Megahertz| min.min(freq)));
+        max = Some(max.map_or(freq, |max: Megahertz| max.max(freq)));
+        if line.trim_end().ends_with('*') {
+            current = Some(freq);
+        }
+    }
+    (current, min, max)
+}
+
+/// Gpu exposed through the drm sysfs interface, i915 and amdgpu are supported
+#[derive(Debug)]
+pub struct DrmGpu {
+    card_path: String,
+    driver: Driver,
+    device_info: DeviceInfo,
+    hwmon_path: Option<String>,
+    last_rc6: Option<(u64, Instant)>,    // rc6 residency in ms
+    last_energy: Option<(u64, Instant)>, // energy in uJ
+}
+
+impl DrmGpu {
+    pub fn init(card_path: &str) -> Result<Self> {
+        let driver = std::fs::read_link(format!("{}/device/driver", card_path))
+            .ok()
+            .and_then(|driver| Some(driver.file_name()?.to_str()?.to_string()));
+        let driver = match driver.as_deref() {
+            Some("i915") => Driver::I915,
+            Some("amdgpu") => Driver::Amdgpu,
+            driver => {
+                return Err(GpuError::Other(format!(
+                    "unsupported drm driver {:?} for {}",
+                    driver, card_path
+                )));
+            }
+        };
+        let pci_id = std::fs::read_link(format!("{}/device", card_path))
+            .ok()
+            .and_then(|device| Some(device.file_name()?.to_str()?.to_string()))
+            .unwrap_or_default();
+        let vendor = match read_value(&format!("{}/device/vendor", card_path)).as_deref() {
+            Some("0x8086") => "Intel".to_string(),
+            Some("0x1002") => "AMD".to_string(),
+            Some("0x10de") => "NVIDIA".to_string(),
+            vendor => vendor.unwrap_or("Unknown").to_string(),
+        };
+        let hwmon_path = std::fs::read_dir(format!("{}/device/hwmon", card_path))
+            .ok()
+            .and_then(|mut entries| entries.find_map(|entry| entry.ok()))
+            .and_then(|entry| entry.path().to_str().map(|path| path.to_string()));
+        // only amdgpu tells the size of its dedicated memory, an APU reports the system memory
+        // carved out for it, amdgpu only exposes the northbridge voltage (in1) on APUs
+        let is_apu = hwmon_path
+            .as_ref()
+            .is_some_and(|hwmon_path| read_value(&format!("{}/in1_label", hwmon_path)).is_some());
+        let vram =
+            read_u64(&format!("{}/device/mem_info_vram_total", card_path)).filter(|_| !is_apu);
+        let mut gpu = DrmGpu {
+            card_path: card_path.to_string(),
+            driver,
+            device_info: DeviceInfo::new(&vendor, &pci_id, vram),
+            hwmon_path,
+            last_rc6: None,
+            last_energy: None,
+        };
+        // first sample for the counters, so the first refresh has something to diff against
+        gpu.refresh()?;
+        Ok(gpu)
+    }
+
+    fn card_file(&self, name: &str) -> String {
+        format!("{}/{}", self.card_path, name)
+    }
+
+    // (current, min, max) frequency
+    fn read_freq(&self) -> (Option<Megahertz>, Option<Megahertz>, Option<Megahertz>) {
+        match self.driver {
+            Driver::I915 => (
+                read_mhz(&self.card_file("gt_cur_freq_mhz")),
+                read_mhz(&self.card_file("gt_min_freq_mhz")),
+                read_mhz(&self.card_file("gt_max_freq_mhz")),
+            ),
+            Driver::Amdgpu => std::fs::read_to_string(self.card_file("device/pp_dpm_sclk"))
+                .map(|levels| parse_dpm_levels(&levels))
+                .unwrap_or_default(),
+        }
+    }
+
+    // (min, max) frequency the i915 hardware accepts
+    fn hw_freq_range(&self) -> (Megahertz, Megahertz) {
+        (
+            read_mhz(&self.card_file("gt_RPn_freq_mhz")).unwrap_or_default(),
+            read_mhz(&self.card_file("gt_RP0_freq_mhz")).unwrap_or(Megahertz::new(u32::MAX)),
+        )
+    }
+
+    // Percentage of the interval the gpu spent in rc6 since the last call
+    fn read_rc6_residency(&mut self) -> Option<f32> {
+        let rc6 = read_u64(&self.card_file("power/rc6_residency_ms"))?;
+        let now = Instant::now();
+        let residency = self.last_rc6.and_then(|(last_rc6, last_time)| {
+            let elapsed = now.duration_since(last_time).as_millis() as f32;
+            (elapsed > 0.0)
+                .then(|| (rc6.saturating_sub(last_rc6) as f32 * 100.0 / elapsed).clamp(0.0, 100.0))
+        });
+        self.last_rc6 = Some((rc6, now));
+        residency
+    }
+
+    // Power from power1_average or from the energy counter when only that exists
+    fn read_power(&mut self) -> Option<Milliwatts> {
+        let hwmon_path = self.hwmon_path.as_ref()?;
+        if let Some(power) = read_u64(&format!("{}/power1_average", hwmon_path))
+            .or_else(|| read_u64(&format!("{}/power1_input", hwmon_path)))
+        {
+            return Some(Milliwatts::from_microwatts(power));
+        }
+        let energy = read_u64(&format!("{}/energy1_input", hwmon_path))?;
+        let now = Instant::now();
+        // uJ per ms is mW
+        let power = self.last_energy.and_then(|(last_energy, last_time)| {
+            let elapsed = now.duration_since(last_time).as_millis() as u64;
+            (elapsed > 0).then(|| Milliwatts::new(energy.saturating_sub(last_energy) / elapsed))
+        });
+        self.last_energy = Some((energy, now));
+        power
+    }
+}
+
+impl GpuBackend for DrmGpu {
+    fn name(&self) -> String {
+        let card = self.card_path.rsplit('/').next().unwrap_or_default();
+        match self.driver {
+            Driver::I915 => format!("Intel GPU ({})", card),
+            Driver::Amdgpu => format!("AMD GPU ({})", card),
+        }
+    }
+
+    fn device_info(&self) -> Option<DeviceInfo> {
+        Some(self.device_info.clone())
+    }
+
+    fn refresh(&mut self) -> Result<GpuStatus> {
+        // a discrete card is woken up by reading its sysfs attributes, skip it while it sleeps
+        let power_state = read_power_state(&self.card_file("device"));
+        if power_state == PowerState::Suspended {
+            return Ok(GpuStatus {
+                power_state,
+                ..Default::default()
+            });
+        }
+        let (current, min, max) = self.read_freq();
+        let rc6_residency = self.read_rc6_residency();
+        let usage = match self.driver {
+            Driver::Amdgpu => {
+                read_u64(&self.card_file("device/gpu_busy_percent")).map(|v| v as f32)
+            }
+            // i915 has no busy counter, the time out of rc6 is the closest thing
+            Driver::I915 => rc6_residency.map(|rc6| 100.0 - rc6),
+        };
+        let temp = self
+            .hwmon_path
+            .as_ref()
+            .and_then(|hwmon_path| read_u64(&format!("{}/temp1_input", hwmon_path)))
+            .map(MilliCelsius::new);
+        Ok(GpuStatus {
+            freq: Freq::new(vec![current]),
+            usage: Usage::new(vec![usage]),
+            temp: Temp::new(temp.unwrap_or_default()),
+            power: Power::new(self.read_power().unwrap_or_default()),
+            freq_limit: TargetFreq::new(min.unwrap_or_default(), max.unwrap_or_default()),
+            rc6_residency,
+            power_state,
+            ..Default::default()
+        })
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        if self.driver != Driver::I915 {
+            return Capabilities::new(vec![MsgCommand::GetStatus]);
+        }
+        let mut capabilities = Capabilities::new(vec![MsgCommand::GetStatus, MsgCommand::SetFreq]);
+        let (hw_min, hw_max) = self.hw_freq_range();
+        capabilities.set_freq(Range::new(hw_min, hw_max));
+        capabilities
+    }
+
+    fn set_freq(&mut self, target: &TargetFreq) -> Result<TargetFreq> {
+        if self.driver != Driver::I915 {
+            return Err(GpuError::Unsupported(
+                "setting frequency is only supported on i915".to_string(),
+            ));
+        }
+        let (min, max) = (target.get_min(), target.get_max());
+        let (hw_min, hw_max) = self.hw_freq_range();
+        if min > max || min < hw_min || max > hw_max {
+            return Err(GpuError::InvalidValue(format!(
+                "frequency range {}-{} not within {}-{}",
+                min, max, hw_min, hw_max
+            )));
+        }
+        // the kernel rejects min > max, so move the bound that keeps the range valid first
+        let current_max = read_mhz(&self.card_file("gt_max_freq_mhz")).unwrap_or_default();
+        let (min_value, max_value) = (min.get_value().to_string(), max.get_value().to_string());
+        if min > current_max {
+            write_value(&self.card_file("gt_max_freq_mhz"), &max_value)?;
+            write_value(&self.card_file("gt_min_freq_mhz"), &min_value)?;
+        } else {
+            write_value(&self.card_file("gt_min_freq_mhz"), &min_value)?;
+            write_value(&self.card_file("gt_max_freq_mhz"), &max_value)?;
+        }
+        let (_, min, max) = self.read_freq();
+        Ok(TargetFreq::new(
+            min.unwrap_or_default(),
+            max.unwrap_or_default(),
+        ))
+    }
+}
@@ -0,0 +1,270 @@
+use super::{GpuBackend, GpuError, Result};
+use lib::field::{
+    GpuStatus,
+    capability::{Capabilities, Range},
+    desc::DeviceInfo,
+    freq::{Freq, TargetFreq},
+    limit::GpuLimits,
+    power::{Power, PowerState},
+    temp::Temp,
+    unit::{Megahertz, MilliCelsius, Milliwatts},
+    usage::Usage,
+};
+use lib::proto::MsgCommand;
+
+const MIN_POWER_LIMIT: Milliwatts = Milliwatts::from_watts(35);
+const MAX_POWER_LIMIT: Milliwatts = Milliwatts::from_watts(115);
+const DEFAULT_POWER_LIMIT: Milliwatts = Milliwatts::from_watts(80);
+// Like nvml, a locked clock below the lowest supported one is raised to it
+const MIN_GPU_CLOCK: Megahertz = Megahertz::new(210);
+const MAX_GPU_CLOCK: Megahertz = Megahertz::new(2100);
+const MAX_MEM_CLOCK: Megahertz = Megahertz::new(8000);
+
+/// Fake gpu for machines without a supported gpu, walks through a load cycle so the
+/// client side can be exercised
+#[derive(Debug)]
+pub struct MockGpu {
+    tick: u64,
+    power_limit: Milliwatts,
+    gpu_clock: Option<TargetFreq>,
+    mem_clock: Option<TargetFreq>,
+}
+
+impl Default for MockGpu {
+    fn default() -> Self {
+        Self {
+            tick: 0,
+            power_limit: DEFAULT_POWER_LIMIT,
+            gpu_clock: None,
+            mem_clock: None,
+        }
+    }
+}
+
+impl MockGpu {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+fn check_max_clock(target: &TargetFreq, max_clock: Megahertz) -> Result<()> {
+    if target.get_max() > max_clock {
+        return Err(GpuError::InvalidValue(format!(
+            "clock {} above the maximum {}",
+            target.get_max(),
+            max_clock
+        )));
+    }
+    Ok(())
+}
+
+impl GpuBackend for MockGpu {
+    fn name(&self) -> String {
+        "Mock GPU".to_string()
+    }
+
+    fn device_info(&self) -> Option<DeviceInfo> {
+        None
+    }
+
+    fn refresh(&mut self) -> Result<GpuStatus> {
+        self.tick += 1;
+        // triangle wave between 0 and 100 percent load
+        let load = (self.tick % 20).abs_diff(10) * 10;
+        let freq = Megahertz::new(300 + load as u32 * 15).min(
+            self.gpu_clock
+                .as_ref()
+                .map_or(MAX_GPU_CLOCK, |clock| clock.get_max()),
+        );
+        Ok(GpuStatus {
+            freq: Freq::new(vec![Some(freq)]),
+            temp: Temp::new(MilliCelsius::new(40000 + load * 400)),
+            power: Power::new(Milliwatts::new(5000 + load * 800).min(self.power_limit)),
+            usage: Usage::new(vec![Some(load as f32)]),
+            freq_limit: self.gpu_clock.clone().unwrap_or_default(),
+            power_state: PowerState::Active,
+            ..Default::default()
+        })
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        let mut capabilities = Capabilities::new(vec![
+            MsgCommand::GetStatus,
+            MsgCommand::GetGpuLimits,
+            MsgCommand::SetFreq,
+            MsgCommand::SetMemFreq,
+            MsgCommand::SetPowerLimit,
+            MsgCommand::ResetGpuLimits,
+        ]);
+        capabilities.set_freq(Range::new(Megahertz::default(), MAX_GPU_CLOCK));
+        capabilities.set_mem_freq(Range::new(Megahertz::default(), MAX_MEM_CLOCK));
+        capabilities
+    }
+
+    fn set_freq(&mut self, target: &TargetFreq) -> Result<TargetFreq> {
+        check_max_clock(target, MAX_GPU_CLOCK)?;
+        let applied = TargetFreq::new(target.get_min().max(MIN_GPU_CLOCK), target.get_max());
+        self.gpu_clock = Some(applied.clone());
+        Ok(applied)
+    }
+
+    fn limits(&self) -> Result<GpuLimits> {
+        Ok(GpuLimits::new(
+            Power::new(self.power_limit),
+            Power::new(MIN_POWER_LIMIT),
+            Power::new(MAX_POWER_LIMIT),
+            Power::new(DEFAULT_POWER_LIMIT),
+            self.gpu_clock.clone(),
+            self.mem_clock.clone(),
+        ))
+    }
+
+    fn set_power_limit(&mut self, power_limit: Milliwatts) -> Result<()> {
+        self.power_limit = power_limit;
+        Ok(())
+    }
+
+    fn set_mem_freq(&mut self, target: &TargetFreq) -> Result<()> {
+        check_max_clock(target, MAX_MEM_CLOCK)?;
+        self.mem_clock = Some(target.clone());
+        Ok(())
+    }
+
+    fn reset_limits(&mut self) -> Result<()> {
+        self.power_limit = DEFAULT_POWER_LIMIT;
+        self.gpu_clock = None;
+        self.mem_clock = None;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component::{Component, gpu::Gpu};
+    use lib::field::power::TargetPower;
+    use lib::proto::{MsgError, Request, Response, Status};
+
+    fn limits(gpu: &mut Gpu, request: Request) -> std::result::Result<GpuLimits, MsgError> {
+        match gpu.handle_request(&request)? {
+            Response::GpuLimits(limits) => Ok(limits),
+            response => panic!("unexpected response {:?}", response),
+        }
+    }
+
+    fn mock_gpu() -> Gpu {
+        Gpu::new(0, Box::new(MockGpu::new()))
+    }
+
+    #[test]
+    fn set_power_limit_echoes_limits() {
+        let mut gpu = mock_gpu();
+        let target = TargetPower::new(Milliwatts::from_watts(60));
+        let limits = limits(&mut gpu, Request::SetPowerLimit(target)).unwrap();
+        assert_eq!(
+            limits.get_power_limit().get_value(),
+            Milliwatts::from_watts(60)
+        );
+        assert_eq!(limits.get_min_power_limit().get_value(), MIN_POWER_LIMIT);
+        assert_eq!(limits.get_max_power_limit().get_value(), MAX_POWER_LIMIT);
+        assert_eq!(
+            limits.get_default_power_limit().get_value(),
+            DEFAULT_POWER_LIMIT
+        );
+    }
+
+    #[test]
+    fn set_power_limit_out_of_range() {
+        let mut gpu = mock_gpu();
+        for watts in [0, 34, 116] {
+            let target = TargetPower::new(Milliwatts::from_watts(watts));
+            assert!(matches!(
+                limits(&mut gpu, Request::SetPowerLimit(target)),
+                Err(MsgError::InvalidCommand(_))
+            ));
+        }
+        // the rejected values didn't reach the backend
+        let limits = limits(&mut gpu, Request::GetGpuLimits).unwrap();
+        assert_eq!(limits.get_power_limit().get_value(), DEFAULT_POWER_LIMIT);
+    }
+
+    #[test]
+    fn set_mem_freq_echoes_limits() {
+        let mut gpu = mock_gpu();
+        let target = TargetFreq::new(Megahertz::new(5000), Megahertz::new(7000));
+        let limits = limits(&mut gpu, Request::SetMemFreq(target)).unwrap();
+        let mem_clock = limits.get_mem_clock().unwrap();
+        assert_eq!(mem_clock.get_min(), Megahertz::new(5000));
+        assert_eq!(mem_clock.get_max(), Megahertz::new(7000));
+        assert!(limits.get_gpu_clock().is_none());
+    }
+
+    #[test]
+    fn set_mem_freq_out_of_range() {
+        let mut gpu = mock_gpu();
+        // min above max
+        let target = TargetFreq::new(Megahertz::new(7000), Megahertz::new(5000));
+        assert!(matches!(
+            limits(&mut gpu, Request::SetMemFreq(target)),
+            Err(MsgError::InvalidCommand(_))
+        ));
+        // above what the memory clock goes to
+        let target = TargetFreq::new(Megahertz::new(5000), Megahertz::new(9000));
+        assert!(matches!(
+            limits(&mut gpu, Request::SetMemFreq(target)),
+            Err(MsgError::InvalidCommand(_))
+        ));
+        let limits = limits(&mut gpu, Request::GetGpuLimits).unwrap();
+        assert!(limits.get_mem_clock().is_none());
+    }
+
+    #[test]
+    fn set_freq_echoes_applied_clocks() {
+        let mut gpu = mock_gpu();
+        let target = TargetFreq::new(Megahertz::new(0), Megahertz::new(1500));
+        let Response::Freq(applied) = gpu.handle_request(&Request::SetFreq(target)).unwrap() else {
+            panic!("not Freq");
+        };
+        assert_eq!(applied.get_min(), MIN_GPU_CLOCK);
+        assert_eq!(applied.get_max(), Megahertz::new(1500));
+        let gpu_clock = limits(&mut gpu, Request::GetGpuLimits)
+            .unwrap()
+            .get_gpu_clock()
+            .unwrap()
+            .clone();
+        assert_eq!(gpu_clock.get_min(), MIN_GPU_CLOCK);
+    }
+
+    #[test]
+    fn set_freq_survives_refresh() {
+        let mut gpu = mock_gpu();
+        let target = TargetFreq::new(Megahertz::new(300), Megahertz::new(1500));
+        gpu.handle_request(&Request::SetFreq(target)).unwrap();
+        // the monitor refreshes every component in between
+        gpu.refresh_status().unwrap();
+        gpu.refresh_status().unwrap();
+        let Response::Status(Status::Gpu(status)) =
+            gpu.handle_request(&Request::GetStatus).unwrap()
+        else {
+            panic!("not a gpu status");
+        };
+        assert_eq!(status.freq_limit.get_min(), Megahertz::new(300));
+        assert_eq!(status.freq_limit.get_max(), Megahertz::new(1500));
+    }
+
+    #[test]
+    fn reset_gpu_limits() {
+        let mut gpu = mock_gpu();
+        let target = TargetPower::new(Milliwatts::from_watts(40));
+        limits(&mut gpu, Request::SetPowerLimit(target)).unwrap();
+        let target = TargetFreq::new(Megahertz::new(300), Megahertz::new(1500));
+        gpu.handle_request(&Request::SetFreq(target)).unwrap();
+        let target = TargetFreq::new(Megahertz::new(5000), Megahertz::new(7000));
+        limits(&mut gpu, Request::SetMemFreq(target)).unwrap();
+
+        let limits = limits(&mut gpu, Request::ResetGpuLimits).unwrap();
+        assert_eq!(limits.get_power_limit().get_value(), DEFAULT_POWER_LIMIT);
+        assert!(limits.get_gpu_clock().is_none());
+        assert!(limits.get_mem_clock().is_none());
+    }
+}
@@ -1 +1,249 @@
+pub mod drm;
+pub mod mock;
 pub mod nvidia;
+
+use crate::component::{Component, ComponentError, unsupported};
+use crate::lowlevel::accessor::fd;
+use lib::field::{
+    GpuStatus,
+    capability::{Capabilities, Range},
+    category::Category,
+    desc::{Desc, DeviceInfo},
+    freq::TargetFreq,
+    limit::GpuLimits,
+    power::PowerState,
+    unit::Milliwatts,
+};
+use lib::proto::{MsgCommand, MsgError, Request, Response, Status};
+use nvml_wrapper::error::NvmlError;
+
+#[derive(Debug, thiserror::Error)]
+pub enum GpuError {
+    #[error("nvml error: {0}")]
+    NvmlError(#[from] NvmlError),
+    #[error("sysfs error: {0}")]
+    FdError(#[from] fd::FdError),
+    #[error("unsupported: {0}")]
+    Unsupported(String),
+    #[error("invalid value: {0}")]
+    InvalidValue(String),
+    #[error("{0}")]
+    Other(String),
+}
+
+type Result<T> = std::result::Result<T, GpuError>;
+
+impl From<GpuError> for MsgError {
+    fn from(err: GpuError) -> Self {
+        match err {
+            GpuError::FdError(err) => err.into(),
+            GpuError::NvmlError(NvmlError::NoPermission) => MsgError::AccessDenied(err.to_string()),
+            GpuError::NvmlError(NvmlError::InvalidArg) => MsgError::InvalidCommand(err.to_string()),
+            GpuError::NvmlError(NvmlError::NotSupported) => {
+                MsgError::UnsupportedOperation(err.to_string())
+            }
+            GpuError::NvmlError(NvmlError::Timeout) => MsgError::Timeout(err.to_string()),
+            GpuError::Unsupported(_) => MsgError::UnsupportedOperation(err.to_string()),
+            GpuError::InvalidValue(_) => MsgError::InvalidCommand(err.to_string()),
+            _ => MsgError::DeviceError(err.to_string()),
+        }
+    }
+}
+
+pub const PCI_SYSFS_PATH: &str = "/sys/bus/pci/devices";
+
+/// Runtime pm state of a pci device, reading it doesn't wake the device up
+pub fn read_power_state(device_path: &str) -> PowerState {
+    fd::Fd::new(
+        &format!("{}/power/runtime_status", device_path),
+        libc::O_RDONLY,
+    )
+    .and_then(|fd| fd.read(16))
+    .map_or(PowerState::Unknown, |status| match status.as_str() {
+        "suspended" | "suspending" => PowerState::Suspended,
+        "active" | "resuming" => PowerState::Active,
+        _ => PowerState::Unknown,
+    })
+}
+
+/// A source of gpu status, one per vendor interface (nvml, drm sysfs, ...)
+pub trait GpuBackend {
+    fn name(&self) -> String;
+
+    // Vendor, pci address and memory, None for a device that isn't a real pci device
+    fn device_info(&self) -> Option<DeviceInfo>;
+
+    // Query the hardware and return the current status
+    fn refresh(&mut self) -> Result<GpuStatus>;
+
+    // Commands this backend implements with the frequency ranges it accepts, the power
+    // limit range is read from limits()
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::new(vec![MsgCommand::GetStatus])
+    }
+
+    // Set the min/max frequency in MHz, return the limits actually applied
+    fn set_freq(&mut self, _target: &TargetFreq) -> Result<TargetFreq> {
+        Err(GpuError::Unsupported(format!(
+            "setting frequency on {}",
+            self.name()
+        )))
+    }
+
+    // Power limit and locked clocks, with the range the driver accepts
+    fn limits(&self) -> Result<GpuLimits> {
+        Err(GpuError::Unsupported(format!("limits on {}", self.name())))
+    }
+
+    // Power limit already checked against the range reported by limits()
+    fn set_power_limit(&mut self, _power_limit: Milliwatts) -> Result<()> {
+        Err(GpuError::Unsupported(format!(
+            "setting power limit on {}",
+            self.name()
+        )))
+    }
+
+    // Lock the memory clock to the range in MHz
+    fn set_mem_freq(&mut self, _target: &TargetFreq) -> Result<()> {
+        Err(GpuError::Unsupported(format!(
+            "setting memory frequency on {}",
+            self.name()
+        )))
+    }
+
+    // Back to the default power limit and unlocked clocks
+    fn reset_limits(&mut self) -> Result<()> {
+        Err(GpuError::Unsupported(format!(
+            "resetting limits on {}",
+            self.name()
+        )))
+    }
+}
+
+fn check_freq_range(target: &TargetFreq) -> std::result::Result<(), MsgError> {
+    if target.get_min() > target.get_max() {
+        return Err(MsgError::InvalidCommand(format!(
+            "min frequency {} above max frequency {}",
+            target.get_min(),
+            target.get_max()
+        )));
+    }
+    Ok(())
+}
+
+/// Every supported gpu, ordered by pci address so the order stays the same across boots,
+/// a gpu visible through both nvml and drm is only listed once
+pub fn enumerate() -> Vec<Box<dyn GpuBackend + Send + Sync>> {
+    let mut gpus: Vec<Box<dyn GpuBackend + Send + Sync>> = vec![];
+    match nvidia::NvidiaGpu::enumerate() {
+        Ok(nvidia_gpus) => nvidia_gpus
+            .into_iter()
+            .for_each(|gpu| gpus.push(Box::new(gpu))),
+        Err(e) => println!("Nvidia gpu not available: {}", e),
+    }
+    for card in drm::list_cards() {
+        match drm::DrmGpu::init(&card) {
+            Ok(gpu) => {
+                let pci_id = gpu.device_info().map(|info| info.get_pci_id().to_string());
+                let known = gpus.iter().any(|known| {
+                    known
+                        .device_info()
+                        .map(|info| info.get_pci_id().to_string())
+                        == pci_id
+                });
+                if !known {
+                    gpus.push(Box::new(gpu));
+                }
+            }
+            Err(e) => println!("Skip drm card {}: {}", card, e),
+        }
+    }
+    gpus.sort_by_key(|gpu| gpu.device_info().map(|info| info.get_pci_id().to_string()));
+    gpus
+}
+
+pub struct Gpu {
+    index: u8,
+    backend: Box<dyn GpuBackend + Send + Sync>,
+    status: GpuStatus,
+}
+
+impl Gpu {
+    pub fn new(index: u8, backend: Box<dyn GpuBackend + Send + Sync>) -> Self {
+        Self {
+            index,
+            backend,
+            status: GpuStatus::default(),
+        }
+    }
+}
+
+impl Component for Gpu {
+    fn get_desc(&self) -> Desc {
+        let mut desc = Desc::new(Category::Gpu, self.index, &self.backend.name());
+        if let Some(device_info) = self.backend.device_info() {
+            desc.set_device_info(device_info);
+        }
+        desc
+    }
+
+    fn refresh_status(&mut self) -> std::result::Result<(), ComponentError> {
+        self.status = self.backend.refresh()?;
+        Ok(())
+    }
+
+    fn get_capabilities(&self) -> Capabilities {
+        let mut capabilities = self.backend.capabilities();
+        if capabilities.supports(&MsgCommand::SetPowerLimit)
+            && let Ok(limits) = self.backend.limits()
+        {
+            capabilities.set_power_limit(Range::new(
+                limits.get_min_power_limit().get_value(),
+                limits.get_max_power_limit().get_value(),
+            ));
+        }
+        capabilities
+    }
+
+    fn handle_request(&mut self, request: &Request) -> std::result::Result<Response, MsgError> {
+        let response = match request {
+            Request::GetStatus => Response::Status(Status::Gpu(self.status.clone())),
+            Request::GetGpuLimits => Response::GpuLimits(self.backend.limits()?),
+            Request::SetFreq(target) => {
+                check_freq_range(target)?;
+                let applied = self.backend.set_freq(target)?;
+                self.status.freq_limit = applied.clone();
+                Response::Freq(applied)
+            }
+            Request::SetMemFreq(target) => {
+                check_freq_range(target)?;
+                self.backend.set_mem_freq(target)?;
+                Response::GpuLimits(self.backend.limits()?)
+            }
+            Request::SetPowerLimit(target) => {
+                let limits = self.backend.limits()?;
+                let power_limit = target.get_max();
+                let range = Range::new(
+                    limits.get_min_power_limit().get_value(),
+                    limits.get_max_power_limit().get_value(),
+                );
+                if !range.contains(power_limit) {
+                    return Err(MsgError::InvalidCommand(format!(
+                        "power limit {} not within {}-{}",
+                        power_limit,
+                        range.get_min(),
+                        range.get_max()
+                    )));
+                }
+                self.backend.set_power_limit(power_limit)?;
+                Response::GpuLimits(self.backend.limits()?)
+            }
+            Request::ResetGpuLimits => {
+                self.backend.reset_limits()?;
+                Response::GpuLimits(self.backend.limits()?)
+            }
+            _ => return Err(unsupported(request)),
+        };
+        Ok(response)
+    }
+}
@@ -1,55 +1,217 @@
-#[derive(Debug)]
-pub enum GpuError {
-    NvmlError,
-    Other(String),
-}
-impl From<nvml_wrapper::error::NvmlError> for GpuError {
-    fn from(_err: nvml_wrapper::error::NvmlError) -> Self {
-        GpuError::NvmlError
-    }
-}
-
-type Result<T> = std::result::Result<T, GpuError>;
+use super::{GpuBackend, GpuError, PCI_SYSFS_PATH, Result, read_power_state};
+use lib::field::{
+    GpuStatus,
+    capability::{Capabilities, Range},
+    desc::DeviceInfo,
+    freq::{Freq, TargetFreq},
+    limit::GpuLimits,
+    power::{Power, PowerState},
+    temp::Temp,
+    unit::{Megahertz, MilliCelsius, Milliwatts},
+    usage::Usage,
+};
+use lib::proto::MsgCommand;
+use nvml_wrapper::enum_wrappers::device::{Clock, ClockId, TemperatureSensor};
+use nvml_wrapper::enums::device::GpuLockedClocksSetting;
+use std::sync::Arc;
 
 pub struct NvidiaGpu {
-    nvml: nvml_wrapper::Nvml,
-    pub desc: String,
-    pub freq: u64,
-    pub temp: u64,
-    pub power: u64,
+    nvml: Arc<nvml_wrapper::Nvml>, // shared by every nvidia gpu
+    bus_id: String,                // nvml pci bus id, stable unlike the device index
+    desc: String,
+    device_info: DeviceInfo,
+    device_path: String, // pci device in sysfs, for the runtime pm state
+    // nvml can't read locked clocks back, remember what was applied
+    gpu_clock: Option<TargetFreq>,
+    mem_clock: Option<TargetFreq>,
+}
+
+// nvml uses a 8 digits pci domain ("00000000:01:00.0"), sysfs uses 4 digits
+fn sysfs_bus_id(bus_id: &str) -> String {
+    let bus_id = bus_id.to_lowercase();
+    match bus_id.split_once(':') {
+        Some((domain, rest)) if domain.len() > 4 => {
+            format!("{}:{}", &domain[domain.len() - 4..], rest)
+        }
+        _ => bus_id,
+    }
 }
 
 impl NvidiaGpu {
-    pub fn init() -> Result<Self> {
-        let nvml = nvml_wrapper::Nvml::init()?;
-        let device = nvml.device_by_index(0)?;
-        let gpu = NvidiaGpu {
+    /// Every gpu nvml knows about, a device nvml fails to describe is skipped
+    pub fn enumerate() -> Result<Vec<Self>> {
+        let nvml = Arc::new(nvml_wrapper::Nvml::init()?);
+        let mut gpus = vec![];
+        for index in 0..nvml.device_count()? {
+            match NvidiaGpu::init(&nvml, index) {
+                Ok(gpu) => gpus.push(gpu),
+                Err(e) => println!("Skip nvidia gpu {}: {}", index, e),
+            }
+        }
+        Ok(gpus)
+    }
+
+    fn init(nvml: &Arc<nvml_wrapper::Nvml>, index: u32) -> Result<Self> {
+        let device = nvml.device_by_index(index)?;
+        let bus_id = device.pci_info()?.bus_id;
+        let pci_id = sysfs_bus_id(&bus_id);
+        Ok(NvidiaGpu {
+            nvml: Arc::clone(nvml),
             desc: device.name()?,
-            freq: device.clock(
-                nvml_wrapper::enum_wrappers::device::Clock::Graphics,
-                nvml_wrapper::enum_wrappers::device::ClockId::Current,
-            )? as u64,
-            temp: device.temperature(nvml_wrapper::enum_wrappers::device::TemperatureSensor::Gpu)?
-                as u64,
-            power: device.power_usage()? as u64,
-            nvml,
+            device_info: DeviceInfo::new(
+                "NVIDIA",
+                &pci_id,
+                device.memory_info().ok().map(|memory| memory.total),
+            ),
+            device_path: format!("{}/{}", PCI_SYSFS_PATH, pci_id),
+            bus_id,
+            gpu_clock: None,
+            mem_clock: None,
+        })
+    }
+
+    fn device(&self) -> Result<nvml_wrapper::Device<'_>> {
+        Ok(self.nvml.device_by_pci_bus_id(self.bus_id.as_str())?)
+    }
+
+    fn check_max_clock(&self, clock: Clock, target: &TargetFreq) -> Result<()> {
+        let max_clock = Megahertz::new(self.device()?.max_clock_info(clock)?);
+        if target.get_max() > max_clock {
+            return Err(GpuError::InvalidValue(format!(
+                "clock {} above the maximum {}",
+                target.get_max(),
+                max_clock
+            )));
+        }
+        Ok(())
+    }
+
+    // nvml clamps locked clocks to the graphics clocks supported at the current memory clock,
+    // the target as it was asked when those can't be read
+    fn applied_gpu_clock(&self, target: &TargetFreq) -> TargetFreq {
+        let supported = self.device().ok().and_then(|device| {
+            let mem_clock = device.clock(Clock::Memory, ClockId::Current).ok()?;
+            device.supported_graphics_clocks(mem_clock).ok()
+        });
+        let Some((lowest, highest)) =
+            supported.and_then(|clocks| Some((*clocks.iter().min()?, *clocks.iter().max()?)))
+        else {
+            return target.clone();
         };
+        let clamp = |freq: Megahertz| Megahertz::new(freq.get_value().clamp(lowest, highest));
+        TargetFreq::new(clamp(target.get_min()), clamp(target.get_max()))
+    }
+}
+
+impl GpuBackend for NvidiaGpu {
+    fn name(&self) -> String {
+        self.desc.clone()
+    }
+
+    fn device_info(&self) -> Option<DeviceInfo> {
+        Some(self.device_info.clone())
+    }
+
+    fn refresh(&mut self) -> Result<GpuStatus> {
+        // any nvml query resumes a runtime suspended gpu, so don't touch it while it sleeps
+        let power_state = read_power_state(&self.device_path);
+        if power_state == PowerState::Suspended {
+            return Ok(GpuStatus {
+                power_state,
+                ..Default::default()
+            });
+        }
+        let device = self.device()?;
+        Ok(GpuStatus {
+            freq: Freq::new(vec![Some(Megahertz::new(
+                device.clock(Clock::Graphics, ClockId::Current)?,
+            ))]),
+            // nvml reports whole degrees
+            temp: Temp::new(MilliCelsius::from_celsius(
+                device.temperature(TemperatureSensor::Gpu)? as u64,
+            )),
+            power: Power::new(Milliwatts::new(device.power_usage()? as u64)),
+            usage: Usage::new(vec![Some(device.utilization_rates()?.gpu as f32)]),
+            freq_limit: self.gpu_clock.clone().unwrap_or_default(),
+            power_state,
+            ..Default::default()
+        })
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        let mut capabilities = Capabilities::new(vec![
+            MsgCommand::GetStatus,
+            MsgCommand::GetGpuLimits,
+            MsgCommand::SetFreq,
+            MsgCommand::SetMemFreq,
+            MsgCommand::SetPowerLimit,
+            MsgCommand::ResetGpuLimits,
+        ]);
+        if let Ok(device) = self.device() {
+            if let Ok(max_clock) = device.max_clock_info(Clock::Graphics) {
+                capabilities.set_freq(Range::new(Megahertz::default(), Megahertz::new(max_clock)));
+            }
+            if let Ok(max_clock) = device.max_clock_info(Clock::Memory) {
+                capabilities
+                    .set_mem_freq(Range::new(Megahertz::default(), Megahertz::new(max_clock)));
+            }
+        }
+        capabilities
+    }
+
+    fn set_freq(&mut self, target: &TargetFreq) -> Result<TargetFreq> {
+        self.check_max_clock(Clock::Graphics, target)?;
+        self.device()?
+            .set_gpu_locked_clocks(GpuLockedClocksSetting::Numeric {
+                min_clock_mhz: target.get_min().get_value(),
+                max_clock_mhz: target.get_max().get_value(),
+            })?;
+        let applied = self.applied_gpu_clock(target);
+        self.gpu_clock = Some(applied.clone());
+        Ok(applied)
+    }
+
+    fn limits(&self) -> Result<GpuLimits> {
+        let device = self.device()?;
+        let constraints = device.power_management_limit_constraints()?;
+        Ok(GpuLimits::new(
+            Power::new(Milliwatts::new(device.enforced_power_limit()? as u64)),
+            Power::new(Milliwatts::new(constraints.min_limit as u64)),
+            Power::new(Milliwatts::new(constraints.max_limit as u64)),
+            Power::new(Milliwatts::new(
+                device.power_management_limit_default()? as u64
+            )),
+            self.gpu_clock.clone(),
+            self.mem_clock.clone(),
+        ))
+    }
+
+    fn set_power_limit(&mut self, power_limit: Milliwatts) -> Result<()> {
+        self.device()?
+            .set_power_management_limit(power_limit.get_value() as u32)?;
+        Ok(())
+    }
+
+    fn set_mem_freq(&mut self, target: &TargetFreq) -> Result<()> {
+        self.check_max_clock(Clock::Memory, target)?;
+        self.device()?
+            .set_mem_locked_clocks(target.get_min().get_value(), target.get_max().get_value())?;
+        self.mem_clock = Some(target.clone());
+        Ok(())
+    }
 
-        Ok(gpu)
-    }
-
-    pub fn refresh(&mut self) -> Result<()> {
-        let device = self.nvml.device_by_index(0)?;
-        // refresh freq
-        self.freq = device.clock(
-            nvml_wrapper::enum_wrappers::device::Clock::Graphics,
-            nvml_wrapper::enum_wrappers::device::ClockId::Current,
-        )? as u64;
-        // refresh temp
-        self.temp =
-            device.temperature(nvml_wrapper::enum_wrappers::device::TemperatureSensor::Gpu)? as u64;
-        // refresh power
-        self.power = device.power_usage()? as u64;
+    fn reset_limits(&mut self) -> Result<()> {
+        // memory clock locking only exists since Ampere, only reset it when it was set
+        let mem_clock_locked = self.mem_clock.is_some();
+        let mut device = self.device()?;
+        let default_power_limit = device.power_management_limit_default()?;
+        device.set_power_management_limit(default_power_limit)?;
+        device.reset_gpu_locked_clocks()?;
+        if mem_clock_locked {
+            device.reset_mem_locked_clocks()?;
+        }
+        self.gpu_clock = None;
+        self.mem_clock = None;
         Ok(())
     }
 }
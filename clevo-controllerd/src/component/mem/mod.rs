@@ -0,0 +1,118 @@
+use crate::component::{Component, ComponentError, unsupported};
+use crate::lowlevel::procfs;
+use lib::field::{
+    MemStatus,
+    category::Category,
+    desc::Desc,
+    mem::{MemUsage, Pressure},
+};
+use lib::proto::{MsgError, Request, Response, Status};
+use std::collections::HashMap;
+
+#[derive(Debug, thiserror::Error)]
+pub enum MemError {
+    #[error("read {0} error: {1}")]
+    ReadError(String, std::io::Error),
+    #[error("parse error: {0}")]
+    ParseError(String),
+}
+
+type Result<T> = std::result::Result<T, MemError>;
+
+impl From<MemError> for ComponentError {
+    fn from(err: MemError) -> Self {
+        ComponentError::LowerlevelError(err.to_string())
+    }
+}
+
+fn read_proc(name: &str) -> Result<String> {
+    let path = procfs::proc_path(name);
+    std::fs::read_to_string(&path).map_err(|e| MemError::ReadError(path, e))
+}
+
+// "MemTotal:       16318460 kB" lines, values converted to bytes
+fn parse_meminfo(content: &str) -> HashMap<&str, u64> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            let mut parts = value.split_whitespace();
+            let value: u64 = parts.next()?.parse().ok()?;
+            let value = if parts.next() == Some("kB") {
+                value * 1024
+            } else {
+                value
+            };
+            Some((key, value))
+        })
+        .collect()
+}
+
+/// Memory, swap and pressure stall information from /proc
+pub struct Mem {
+    index: u8,
+    status: MemStatus,
+}
+
+impl Mem {
+    pub fn init(index: u8) -> Result<Self> {
+        let mut mem = Self {
+            index,
+            status: MemStatus::default(),
+        };
+        mem.refresh()?;
+        Ok(mem)
+    }
+
+    fn read_usage() -> Result<MemUsage> {
+        let content = read_proc("meminfo")?;
+        let meminfo = parse_meminfo(&content);
+        let get = |key: &str| {
+            meminfo
+                .get(key)
+                .copied()
+                .ok_or(MemError::ParseError(format!("{} missing in meminfo", key)))
+        };
+        Ok(MemUsage::new(
+            get("MemTotal")?,
+            get("MemAvailable")?,
+            get("Cached")? + meminfo.get("SReclaimable").copied().unwrap_or_default(),
+            get("SwapTotal")?,
+            get("SwapFree")?,
+        ))
+    }
+
+    // None if the kernel has no PSI, or has it disabled with psi=0
+    fn read_pressure(resource: &str) -> Option<Pressure> {
+        let content = read_proc(&format!("pressure/{}", resource)).ok()?;
+        Pressure::parse(&content).ok()
+    }
+
+    fn refresh(&mut self) -> Result<()> {
+        self.status = MemStatus {
+            usage: Self::read_usage()?,
+            memory_pressure: Self::read_pressure("memory"),
+            cpu_pressure: Self::read_pressure("cpu"),
+            io_pressure: Self::read_pressure("io"),
+        };
+        Ok(())
+    }
+}
+
+impl Component for Mem {
+    fn get_desc(&self) -> Desc {
+        Desc::new(Category::Memory, self.index, "Memory")
+    }
+
+    fn refresh_status(&mut self) -> std::result::Result<(), ComponentError> {
+        self.refresh()?;
+        Ok(())
+    }
+
+    fn handle_request(&mut self, request: &Request) -> std::result::Result<Response, MsgError> {
+        match request {
+            Request::GetStatus => Ok(Response::Status(Status::Memory(self.status.clone()))),
+            _ => Err(unsupported(request)),
+        }
+    }
+}
@@ -1,10 +1,16 @@
 pub mod cpu;
 pub mod fan;
 pub mod gpu;
+pub mod mem;
+pub mod net;
+pub mod power_supply;
+pub mod storage;
 
+use crate::lowlevel::accessor::ec::EcError;
 use cpu::CpuError;
-use lib::field::{FieldError, desc::Desc};
-use lib::proto::{MsgCommand, MsgError};
+use gpu::GpuError;
+use lib::field::{FieldError, capability::Capabilities, desc::Desc, event::Event};
+use lib::proto::{MsgCommand, MsgError, Request, Response};
 use lib::stream::StreamError;
 
 #[derive(Debug, thiserror::Error)]
@@ -27,6 +33,18 @@ impl From<CpuError> for crate::component::ComponentError {
     }
 }
 
+impl From<EcError> for ComponentError {
+    fn from(err: EcError) -> Self {
+        ComponentError::LowerlevelError(err.to_string())
+    }
+}
+
+impl From<GpuError> for ComponentError {
+    fn from(err: GpuError) -> Self {
+        ComponentError::LowerlevelError(err.to_string())
+    }
+}
+
 impl From<FieldError> for ComponentError {
     fn from(err: FieldError) -> Self {
         ComponentError::FieldError(String::from(err))
@@ -53,14 +71,24 @@ pub trait Component {
         Ok(())
     }
 
-    fn handle_command(
-        &mut self,
-        command: &MsgCommand,
-        payload: &[Vec<u8>],
-    ) -> Result<Vec<Vec<u8>>, MsgError> {
-        Err(MsgError::UnsupportedOperation(format!(
-            "Operation not supported by the hardware:{}",
-            command
-        )))
+    // Events since the last poll, the monitor polls right after refresh_status
+    fn poll_events(&mut self) -> Vec<Event> {
+        vec![]
     }
+
+    // What handle_request accepts, most components only report their status
+    fn get_capabilities(&self) -> Capabilities {
+        Capabilities::new(vec![MsgCommand::GetStatus])
+    }
+
+    fn handle_request(&mut self, request: &Request) -> Result<Response, MsgError> {
+        Err(unsupported(request))
+    }
+}
+
+pub fn unsupported(request: &Request) -> MsgError {
+    MsgError::UnsupportedOperation(format!(
+        "{} is not supported by this component",
+        request.get_command()
+    ))
 }
@@ -0,0 +1,185 @@
+use crate::component::{Component, ComponentError, unsupported};
+use crate::lowlevel::procfs;
+use lib::field::{
+    category::Category,
+    desc::Desc,
+    net_speed::{InterfaceSpeed, NetSpeed},
+};
+use lib::proto::{MsgError, Request, Response, Status};
+use std::collections::HashMap;
+use std::time::Instant;
+
+#[derive(Debug, thiserror::Error)]
+pub enum NetError {
+    #[error("read {0} error: {1}")]
+    ReadError(String, std::io::Error),
+    #[error("parse error: {0}")]
+    ParseError(String),
+}
+
+type Result<T> = std::result::Result<T, NetError>;
+
+impl From<NetError> for ComponentError {
+    fn from(err: NetError) -> Self {
+        ComponentError::LowerlevelError(err.to_string())
+    }
+}
+
+// Cumulative counters of one interface since boot
+#[derive(Debug, Clone, Copy, Default)]
+struct Counters {
+    rx_bytes: u64,
+    rx_packets: u64,
+    tx_bytes: u64,
+    tx_packets: u64,
+}
+
+// /proc/net/dev has two header lines, then "<iface>: <8 rx fields> <8 tx fields>"
+fn parse_net_dev(content: &str) -> Result<HashMap<String, Counters>> {
+    let mut interfaces = HashMap::new();
+    for line in content.lines().skip(2) {
+        let Some((name, fields)) = line.split_once(':') else {
+            continue;
+        };
+        let fields: Vec<u64> = fields
+            .split_whitespace()
+            .map(|field| field.parse())
+            .collect::<std::result::Result<_, _>>()
+            .map_err(|e| NetError::ParseError(format!("{}: {}", name.trim(), e)))?;
+        if fields.len() < 10 {
+            return Err(NetError::ParseError(format!(
+                "{}: expected at least 10 fields, got {}",
+                name.trim(),
+                fields.len()
+            )));
+        }
+        interfaces.insert(
+            name.trim().to_string(),
+            Counters {
+                rx_bytes: fields[0],
+                rx_packets: fields[1],
+                tx_bytes: fields[8],
+                tx_packets: fields[9],
+            },
+        );
+    }
+    Ok(interfaces)
+}
+
+fn rate(current: u64, last: u64, elapsed: f64) -> u64 {
+    // counters restart from 0 when an interface is recreated
+    (current.saturating_sub(last) as f64 / elapsed) as u64
+}
+
+/// Per interface throughput, from the difference of /proc/net/dev counters between refreshes
+pub struct Net {
+    index: u8,
+    net_dev_path: String,
+    last: Option<(HashMap<String, Counters>, Instant)>,
+    status: NetSpeed,
+}
+
+impl Net {
+    pub fn init(index: u8) -> Result<Self> {
+        let mut net = Self {
+            index,
+            net_dev_path: procfs::proc_path("net/dev"),
+            last: None,
+            status: NetSpeed::default(),
+        };
+        // first sample, so the first refresh has something to diff against
+        net.refresh()?;
+        Ok(net)
+    }
+
+    fn refresh(&mut self) -> Result<()> {
+        let content = std::fs::read_to_string(&self.net_dev_path)
+            .map_err(|e| NetError::ReadError(self.net_dev_path.clone(), e))?;
+        let counters = parse_net_dev(&content)?;
+        let now = Instant::now();
+        if let Some((last_counters, last_time)) = &self.last {
+            let elapsed = now.duration_since(*last_time).as_secs_f64();
+            if elapsed > 0.0 {
+                let mut interfaces: Vec<InterfaceSpeed> = counters
+                    .iter()
+                    .map(|(name, current)| {
+                        // an interface that just showed up starts from 0
+                        let last = last_counters.get(name).copied().unwrap_or(*current);
+                        InterfaceSpeed::new(
+                            name,
+                            rate(current.rx_bytes, last.rx_bytes, elapsed),
+                            rate(current.tx_bytes, last.tx_bytes, elapsed),
+                            rate(current.rx_packets, last.rx_packets, elapsed),
+                            rate(current.tx_packets, last.tx_packets, elapsed),
+                        )
+                    })
+                    .collect();
+                interfaces.sort_by(|a, b| a.get_name().cmp(b.get_name()));
+                self.status = NetSpeed::new(interfaces);
+            }
+        }
+        self.last = Some((counters, now));
+        Ok(())
+    }
+}
+
+impl Component for Net {
+    fn get_desc(&self) -> Desc {
+        Desc::new(Category::Network, self.index, "Network")
+    }
+
+    fn refresh_status(&mut self) -> std::result::Result<(), ComponentError> {
+        self.refresh()?;
+        Ok(())
+    }
+
+    fn handle_request(&mut self, request: &Request) -> std::result::Result<Response, MsgError> {
+        match request {
+            Request::GetStatus => Ok(Response::Status(Status::Network(self.status.clone()))),
+            _ => Err(unsupported(request)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NET_DEV: &str = "\
+Inter-|   Receive                                                |  Transmit
+ face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed
+    lo: 1096254    9812    0    0    0     0          0         0  1096254    9812    0    0    0     0       0          0
+wlp0s20f3: 823409171  645377    0  121    0     0          0         0 41271688  238815    0    0    0     0       0          0
+";
+
+    #[test]
+    fn parse_net_dev_sample() {
+        let interfaces = parse_net_dev(NET_DEV).unwrap();
+        // the header lines aren't interfaces
+        assert_eq!(interfaces.len(), 2);
+        let wlan = interfaces["wlp0s20f3"];
+        assert_eq!(wlan.rx_bytes, 823409171);
+        assert_eq!(wlan.rx_packets, 645377);
+        assert_eq!(wlan.tx_bytes, 41271688);
+        assert_eq!(wlan.tx_packets, 238815);
+        assert_eq!(interfaces["lo"].tx_bytes, 1096254);
+    }
+
+    #[test]
+    fn parse_net_dev_short_line() {
+        let content = format!("{}  eth0: 1 2 3 4 5 6 7 8 9\n", NET_DEV);
+        let Err(NetError::ParseError(message)) = parse_net_dev(&content) else {
+            panic!("a line of 9 fields parsed");
+        };
+        assert_eq!(message, "eth0: expected at least 10 fields, got 9");
+    }
+
+    #[test]
+    fn parse_net_dev_not_a_number() {
+        let content = format!("{}  eth0: 1 2 x 4 5 6 7 8 9 10\n", NET_DEV);
+        assert!(matches!(
+            parse_net_dev(&content),
+            Err(NetError::ParseError(_))
+        ));
+    }
+}
@@ -0,0 +1,190 @@
+use crate::component::{Component, ComponentError, unsupported};
+use crate::lowlevel::accessor::fd;
+use lib::field::{
+    PowerSupplyStatus, category::Category, desc::Desc, event::Event, power::Power,
+    power_supply::PowerSource, unit::Milliwatts,
+};
+use lib::proto::{MsgError, Request, Response, Status};
+
+pub const POWER_SUPPLY_SYSFS_PATH: &str = "/sys/class/power_supply";
+
+#[derive(Debug, thiserror::Error)]
+pub enum PowerSupplyError {
+    #[error("no power supply found")]
+    NoSupply,
+}
+
+type Result<T> = std::result::Result<T, PowerSupplyError>;
+
+impl From<PowerSupplyError> for ComponentError {
+    fn from(err: PowerSupplyError) -> Self {
+        ComponentError::LowerlevelError(err.to_string())
+    }
+}
+
+fn read_value(path: &str) -> Option<String> {
+    fd::Fd::new(path, libc::O_RDONLY)
+        .and_then(|fd| fd.read(64))
+        .ok()
+}
+
+fn read_u64(path: &str) -> Option<u64> {
+    read_value(path).and_then(|value| value.parse().ok())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SupplyType {
+    Mains,
+    Usb,
+    Battery,
+}
+
+#[derive(Debug)]
+struct Supply {
+    name: String,
+    path: String,
+    supply_type: SupplyType,
+}
+
+impl Supply {
+    fn file(&self, name: &str) -> String {
+        format!("{}/{}", self.path, name)
+    }
+
+    fn is_online(&self) -> bool {
+        read_value(&self.file("online")).as_deref() == Some("1")
+    }
+
+    // The negotiated usb type is the one in brackets, e.g. "C [PD] PD_PPS"
+    fn is_pd(&self) -> bool {
+        read_value(&self.file("usb_type")).is_some_and(|usb_type| usb_type.contains("[PD"))
+    }
+
+    // Rated power from the voltage and the current limit, uV * uA = 1e-9 mW
+    fn rated_power(&self) -> Option<Milliwatts> {
+        let voltage = read_u64(&self.file("voltage_now"))
+            .filter(|voltage| *voltage > 0)
+            .or_else(|| read_u64(&self.file("voltage_max")))?;
+        let current = read_u64(&self.file("current_max"))?;
+        Some(voltage * current / 1_000_000_000)
+            .filter(|power| *power > 0)
+            .map(Milliwatts::new)
+    }
+}
+
+// Every supply under /sys/class/power_supply with a type we understand, sorted by name
+fn list_supplies() -> Vec<Supply> {
+    let mut supplies: Vec<Supply> = match std::fs::read_dir(POWER_SUPPLY_SYSFS_PATH) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .filter_map(|name| {
+                let path = format!("{}/{}", POWER_SUPPLY_SYSFS_PATH, name);
+                let supply_type = match read_value(&format!("{}/type", path)).as_deref() {
+                    Some("Mains") => SupplyType::Mains,
+                    Some("USB") => SupplyType::Usb,
+                    Some("Battery") => SupplyType::Battery,
+                    // some AC adapters don't report a type, they are still named AC/ACAD
+                    _ if name.starts_with("AC") => SupplyType::Mains,
+                    _ => return None,
+                };
+                Some(Supply {
+                    name,
+                    path,
+                    supply_type,
+                })
+            })
+            .collect(),
+        Err(_) => vec![],
+    };
+    supplies.sort_by(|a, b| a.name.cmp(&b.name));
+    supplies
+}
+
+/// Where the machine draws its power from, AC adapter, USB-C PD or battery
+pub struct PowerSupply {
+    index: u8,
+    supplies: Vec<Supply>,
+    status: PowerSupplyStatus,
+    events: Vec<Event>, // Not yet polled
+}
+
+impl PowerSupply {
+    pub fn init(index: u8) -> Result<Self> {
+        let supplies = list_supplies();
+        if supplies.is_empty() {
+            return Err(PowerSupplyError::NoSupply);
+        }
+        let mut power_supply = Self {
+            index,
+            supplies,
+            status: PowerSupplyStatus::default(),
+            events: vec![],
+        };
+        power_supply.status = power_supply.read_status();
+        Ok(power_supply)
+    }
+
+    fn read_status(&self) -> PowerSupplyStatus {
+        let online: Vec<&Supply> = self
+            .supplies
+            .iter()
+            .filter(|supply| supply.supply_type != SupplyType::Battery && supply.is_online())
+            .collect();
+        // the barrel jack wins over usb-c when both are plugged in
+        let source = if online.iter().any(|s| s.supply_type == SupplyType::Mains) {
+            PowerSource::Mains
+        } else if online
+            .iter()
+            .any(|s| s.supply_type == SupplyType::Usb && s.is_pd())
+        {
+            PowerSource::UsbPd
+        } else if self
+            .supplies
+            .iter()
+            .any(|supply| supply.supply_type == SupplyType::Battery)
+        {
+            PowerSource::Battery
+        } else {
+            PowerSource::Unknown
+        };
+        PowerSupplyStatus {
+            source,
+            adapter_power: online
+                .iter()
+                .filter_map(|supply| supply.rated_power())
+                .max()
+                .map(Power::new),
+            supplies: online.iter().map(|supply| supply.name.clone()).collect(),
+        }
+    }
+}
+
+impl Component for PowerSupply {
+    fn get_desc(&self) -> Desc {
+        Desc::new(Category::PowerSupply, self.index, "Power Supply")
+    }
+
+    fn refresh_status(&mut self) -> std::result::Result<(), ComponentError> {
+        let status = self.read_status();
+        if status.source != self.status.source {
+            self.events.push(Event::PowerSourceChanged {
+                from: self.status.source,
+                to: status.source,
+            });
+        }
+        self.status = status;
+        Ok(())
+    }
+
+    fn poll_events(&mut self) -> Vec<Event> {
+        std::mem::take(&mut self.events)
+    }
+
+    fn handle_request(&mut self, request: &Request) -> std::result::Result<Response, MsgError> {
+        match request {
+            Request::GetStatus => Ok(Response::Status(Status::PowerSupply(self.status.clone()))),
+            _ => Err(unsupported(request)),
+        }
+    }
+}
@@ -0,0 +1,292 @@
+use crate::component::{Component, ComponentError, unsupported};
+use crate::lowlevel::accessor::fd;
+use lib::field::{
+    StorageStatus, category::Category, desc::Desc, event::Event, storage::DriveTemp, temp::Temp,
+    unit::MilliCelsius,
+};
+use lib::proto::{MsgError, Request, Response, Status};
+
+pub const NVME_SYSFS_PATH: &str = "/sys/class/nvme";
+
+#[derive(Debug, thiserror::Error)]
+pub enum StorageError {
+    #[error("no nvme drive with a hwmon sensor found")]
+    NoDrive,
+    #[error("sysfs error: {0}")]
+    FdError(#[from] fd::FdError),
+    #[error("parse error: {0}")]
+    ParseError(#[from] std::num::ParseIntError),
+}
+
+type Result<T> = std::result::Result<T, StorageError>;
+
+impl From<StorageError> for ComponentError {
+    fn from(err: StorageError) -> Self {
+        ComponentError::LowerlevelError(err.to_string())
+    }
+}
+
+fn read_value(path: &str) -> Option<String> {
+    fd::Fd::new(path, libc::O_RDONLY)
+        .and_then(|fd| fd.read(64))
+        .ok()
+}
+
+// The hwmon directory of an nvme controller, /sys/class/nvme/nvmeN/hwmonM
+fn find_hwmon(controller_path: &str) -> Option<String> {
+    std::fs::read_dir(controller_path)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .find(|entry| entry.file_name().to_string_lossy().starts_with("hwmon"))
+        .and_then(|entry| entry.path().to_str().map(|path| path.to_string()))
+}
+
+#[derive(Debug)]
+struct Drive {
+    name: String,
+    model: String,
+    hwmon_path: String,
+    sensors: Vec<u32>, // N of every tempN_input but temp1, the composite temperature
+}
+
+impl Drive {
+    fn hwmon_file(&self, name: &str) -> String {
+        format!("{}/{}", self.hwmon_path, name)
+    }
+
+    fn read_temp(&self, name: &str) -> Result<Temp> {
+        let fd = fd::Fd::new(&self.hwmon_file(name), libc::O_RDONLY)?;
+        // hwmon temperatures are in millidegrees, may go below zero in theory
+        Ok(Temp::new(MilliCelsius::new(
+            fd.read(16)?.parse::<i64>()?.max(0) as u64,
+        )))
+    }
+
+    // Thresholds are optional, a drive may not report them or report 0 for unset
+    fn read_threshold(&self, name: &str) -> Option<Temp> {
+        self.read_temp(name)
+            .ok()
+            .filter(|temp| temp.get_value() > MilliCelsius::default())
+    }
+
+    fn refresh(&self) -> Result<DriveTemp> {
+        let sensors = self
+            .sensors
+            .iter()
+            .map(|sensor| self.read_temp(&format!("temp{}_input", sensor)))
+            .collect::<Result<Vec<Temp>>>()?;
+        Ok(DriveTemp::new(
+            &self.name,
+            &self.model,
+            self.read_temp("temp1_input")?,
+            sensors,
+            self.read_threshold("temp1_max"),
+            self.read_threshold("temp1_crit"),
+        ))
+    }
+}
+
+// The sensor numbers besides the composite one, sorted. The numbering may have gaps, a
+// drive can leave out a sensor it doesn't implement
+fn list_sensors(hwmon_path: &str) -> Vec<u32> {
+    let mut sensors: Vec<u32> = match std::fs::read_dir(hwmon_path) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                entry
+                    .file_name()
+                    .to_str()
+                    .and_then(|name| name.strip_prefix("temp")?.strip_suffix("_input"))
+                    .and_then(|sensor| sensor.parse().ok())
+            })
+            .filter(|sensor| *sensor != 1)
+            .collect(),
+        Err(_) => vec![],
+    };
+    sensors.sort();
+    sensors
+}
+
+// Every nvme controller exposing a hwmon device, sorted by name
+fn list_drives() -> Vec<Drive> {
+    let mut names: Vec<String> = match std::fs::read_dir(NVME_SYSFS_PATH) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .collect(),
+        Err(_) => vec![],
+    };
+    names.sort();
+    names
+        .into_iter()
+        .filter_map(|name| {
+            let controller_path = format!("{}/{}", NVME_SYSFS_PATH, name);
+            let hwmon_path = find_hwmon(&controller_path)?;
+            let sensors = list_sensors(&hwmon_path);
+            Some(Drive {
+                model: read_value(&format!("{}/model", controller_path)).unwrap_or_default(),
+                name,
+                hwmon_path,
+                sensors,
+            })
+        })
+        .collect()
+}
+
+/// Temperatures of the nvme drives, they throttle once the composite temperature passes
+/// the warning threshold
+pub struct Storage {
+    index: u8,
+    drives: Vec<Drive>,
+    status: StorageStatus,
+    events: Vec<Event>, // Not yet polled
+}
+
+impl Storage {
+    pub fn init(index: u8) -> Result<Self> {
+        let drives = list_drives();
+        if drives.is_empty() {
+            return Err(StorageError::NoDrive);
+        }
+        let mut storage = Self {
+            index,
+            drives,
+            status: StorageStatus::default(),
+            events: vec![],
+        };
+        storage.refresh()?;
+        Ok(storage)
+    }
+
+    // A drive that fails to read is left out, the others are still reported
+    fn refresh(&mut self) -> Result<()> {
+        let mut failed = false;
+        let drives: Vec<DriveTemp> = self
+            .drives
+            .iter()
+            .filter_map(|drive| match drive.refresh() {
+                Ok(temp) => Some(temp),
+                Err(e) => {
+                    println!("nvme {}: {}", drive.name, e);
+                    failed = true;
+                    None
+                }
+            })
+            .collect();
+        // the drive may have been removed or come back under another hwmon, look again
+        if failed {
+            self.drives = list_drives();
+        }
+        for drive in drives.iter().filter(|drive| drive.is_over_warning()) {
+            // only alert on the transition, not every refresh
+            let was_over_warning = self
+                .status
+                .drives
+                .iter()
+                .any(|last| last.get_name() == drive.get_name() && last.is_over_warning());
+            if !was_over_warning {
+                self.events.push(Event::ThermalAlert {
+                    source: format!("{} ({})", drive.get_name(), drive.get_model()),
+                    temp: drive.get_composite().clone(),
+                });
+            }
+        }
+        self.status = StorageStatus { drives };
+        Ok(())
+    }
+}
+
+impl Component for Storage {
+    fn get_desc(&self) -> Desc {
+        Desc::new(Category::Storage, self.index, "Storage")
+    }
+
+    fn refresh_status(&mut self) -> std::result::Result<(), ComponentError> {
+        self.refresh()?;
+        Ok(())
+    }
+
+    fn poll_events(&mut self) -> Vec<Event> {
+        std::mem::take(&mut self.events)
+    }
+
+    fn handle_request(&mut self, request: &Request) -> std::result::Result<Response, MsgError> {
+        match request {
+            Request::GetStatus => Ok(Response::Status(Status::Storage(self.status.clone()))),
+            _ => Err(unsupported(request)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A hwmon directory with the given files, removed on drop
+    struct FakeHwmon {
+        root: std::path::PathBuf,
+    }
+
+    impl FakeHwmon {
+        fn new(name: &str, files: &[(&str, &str)]) -> Self {
+            let root = std::env::temp_dir().join(format!("hwmon-{}-{}", name, std::process::id()));
+            std::fs::create_dir_all(&root).unwrap();
+            for (file, value) in files {
+                std::fs::write(root.join(file), value).unwrap();
+            }
+            FakeHwmon { root }
+        }
+
+        fn path(&self) -> String {
+            self.root.to_str().unwrap().to_string()
+        }
+
+        fn drive(&self, name: &str) -> Drive {
+            Drive {
+                name: name.to_string(),
+                model: "model".to_string(),
+                hwmon_path: self.path(),
+                sensors: list_sensors(&self.path()),
+            }
+        }
+    }
+
+    impl Drop for FakeHwmon {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.root);
+        }
+    }
+
+    #[test]
+    fn list_sensors_past_a_gap() {
+        let hwmon = FakeHwmon::new(
+            "gap",
+            &[
+                ("temp1_input", "40850"),
+                ("temp3_input", "45850"),
+                ("temp3_label", "Sensor 2"),
+                ("temp1_max", "81850"),
+            ],
+        );
+        assert_eq!(list_sensors(&hwmon.path()), vec![3]);
+    }
+
+    #[test]
+    fn failing_drive_leaves_the_others() {
+        let good = FakeHwmon::new("good", &[("temp1_input", "40850")]);
+        let bad = FakeHwmon::new("bad", &[("temp1_input", "not a number")]);
+        let mut storage = Storage {
+            index: 0,
+            drives: vec![bad.drive("nvme0"), good.drive("nvme1")],
+            status: StorageStatus::default(),
+            events: vec![],
+        };
+        storage.refresh().unwrap();
+        assert_eq!(storage.status.drives.len(), 1);
+        assert_eq!(storage.status.drives[0].get_name(), "nvme1");
+        assert_eq!(
+            storage.status.drives[0].get_composite().get_value(),
+            MilliCelsius::new(40850)
+        );
+    }
+}
@@ -1,6 +1,23 @@
+use lib::proto::MsgError;
 use std::{thread, time::Duration};
 use x86::io;
 
+#[derive(Debug, thiserror::Error)]
+pub enum EcError {
+    #[error("EC didn't get ready on port {0:#x}")]
+    Timeout(u16), // The status bit never reached the value polled for
+}
+
+impl From<EcError> for MsgError {
+    fn from(err: EcError) -> Self {
+        match err {
+            EcError::Timeout(_) => MsgError::Timeout(err.to_string()),
+        }
+    }
+}
+
+type Result<T> = std::result::Result<T, EcError>;
+
 #[derive(Debug)]
 pub struct EcAccessor {}
 
@@ -46,43 +63,44 @@ impl EcAccessor {
         }
     }
 
-    fn poll_ready(&self, addr: u16, bit: u8, value: bool) {
+    // Carrying on after a timeout would hand the EC a byte it isn't waiting for
+    fn poll_ready(&self, addr: u16, bit: u8, value: bool) -> Result<()> {
         let mut max_tries = 1000;
         while max_tries > 0 {
             let status = self.inb(addr);
             if ((status >> bit) & 1) == value as u8 {
-                return;
+                return Ok(());
             }
             max_tries -= 1;
             thread::sleep(Duration::from_millis(1));
         }
-        eprintln!("EC poll ready timeout");
+        Err(EcError::Timeout(addr))
     }
 
-    pub fn read_byte(&self, addr: u8) -> u8 {
+    pub fn read_byte(&self, addr: u8) -> Result<u8> {
         self.cmd_read(EC_READ_CMD, addr)
     }
 
-    pub fn write_byte(&self, addr: u8, byte: u8) {
-        self.cmd_write(EC_WRITE_CMD, addr, byte);
+    pub fn write_byte(&self, addr: u8, byte: u8) -> Result<()> {
+        self.cmd_write(EC_WRITE_CMD, addr, byte)
     }
 
-    pub fn cmd_read(&self, cmd: u8, addr: u8) -> u8 {
-        self.poll_ready(EC_SC_REG, EC_SC_IBF_INDEX, false);
+    pub fn cmd_read(&self, cmd: u8, addr: u8) -> Result<u8> {
+        self.poll_ready(EC_SC_REG, EC_SC_IBF_INDEX, false)?;
         self.outb(EC_SC_REG, cmd);
-        self.poll_ready(EC_SC_REG, EC_SC_IBF_INDEX, false);
+        self.poll_ready(EC_SC_REG, EC_SC_IBF_INDEX, false)?;
         self.outb(EC_DATA_REG, addr);
-        self.poll_ready(EC_SC_REG, EC_SC_OBF_INDEX, true);
-        self.inb(EC_DATA_REG)
+        self.poll_ready(EC_SC_REG, EC_SC_OBF_INDEX, true)?;
+        Ok(self.inb(EC_DATA_REG))
     }
 
-    pub fn cmd_write(&self, cmd: u8, addr: u8, byte: u8) {
-        self.poll_ready(EC_SC_REG, EC_SC_IBF_INDEX, false);
+    pub fn cmd_write(&self, cmd: u8, addr: u8, byte: u8) -> Result<()> {
+        self.poll_ready(EC_SC_REG, EC_SC_IBF_INDEX, false)?;
         self.outb(EC_SC_REG, cmd);
-        self.poll_ready(EC_SC_REG, EC_SC_IBF_INDEX, false);
+        self.poll_ready(EC_SC_REG, EC_SC_IBF_INDEX, false)?;
         self.outb(EC_DATA_REG, addr);
-        self.poll_ready(EC_SC_REG, EC_SC_IBF_INDEX, false);
+        self.poll_ready(EC_SC_REG, EC_SC_IBF_INDEX, false)?;
         self.outb(EC_DATA_REG, byte);
-        self.poll_ready(EC_SC_REG, EC_SC_IBF_INDEX, false);
+        self.poll_ready(EC_SC_REG, EC_SC_IBF_INDEX, false)
     }
 }
@@ -1,16 +1,38 @@
+use lib::proto::MsgError;
 use libc::c_int;
-use std::ffi::CString;
+use std::{ffi::CString, io};
 
 const BYTES_PER_READ: usize = 50;
 
 #[derive(Debug, thiserror::Error)]
 pub enum FdError {
-    #[error("Failed to open file descriptor")]
-    OpenError,
-    #[error("Failed to read from file descriptor")]
-    ReadError,
-    #[error("Failed to write to file descriptor")]
-    WriteError,
+    #[error("Failed to open file descriptor: {0}")]
+    OpenError(io::Error),
+    #[error("Failed to read from file descriptor: {0}")]
+    ReadError(io::Error),
+    #[error("Failed to write to file descriptor: {0}")]
+    WriteError(io::Error),
+}
+
+impl FdError {
+    pub fn io_error(&self) -> &io::Error {
+        match self {
+            FdError::OpenError(e) | FdError::ReadError(e) | FdError::WriteError(e) => e,
+        }
+    }
+}
+
+// sysfs answers a value it doesn't take with EINVAL and a file root may not write with EACCES
+impl From<FdError> for MsgError {
+    fn from(err: FdError) -> Self {
+        match err.io_error().raw_os_error() {
+            Some(libc::EACCES) | Some(libc::EPERM) => MsgError::AccessDenied(err.to_string()),
+            Some(libc::EINVAL) | Some(libc::ERANGE) => MsgError::InvalidCommand(err.to_string()),
+            Some(libc::EOPNOTSUPP) => MsgError::UnsupportedOperation(err.to_string()),
+            Some(libc::ETIMEDOUT) => MsgError::Timeout(err.to_string()),
+            _ => MsgError::DeviceError(err.to_string()),
+        }
+    }
 }
 
 type Result<T> = std::result::Result<T, FdError>;
@@ -30,7 +52,7 @@ impl Fd {
     pub fn new(path: &str, mode: c_int) -> Result<Self> {
         let fd = unsafe { libc::open(CString::new(path).unwrap().as_ptr(), mode) };
         if fd < 0 {
-            return Err(FdError::OpenError);
+            return Err(FdError::OpenError(io::Error::last_os_error()));
         }
         Ok(Self { fd })
     }
@@ -50,7 +72,7 @@ impl Fd {
                 )
             };
             if ret < 0 {
-                return Err(FdError::ReadError);
+                return Err(FdError::ReadError(io::Error::last_os_error()));
             } else if ret < BYTES_PER_READ as isize {
                 // EOF
                 data.extend_from_slice(&buffer[..ret as usize]);
@@ -63,7 +85,12 @@ impl Fd {
         Ok(str.trim().to_string())
     }
 
-    pub fn write(&self, _buf: &[u8]) -> usize {
-        unimplemented!()
+    pub fn write(&self, buf: &[u8]) -> Result<usize> {
+        unsafe { libc::lseek(self.fd, 0, libc::SEEK_SET) };
+        let ret = unsafe { libc::write(self.fd, buf.as_ptr() as *const libc::c_void, buf.len()) };
+        if ret < 0 {
+            return Err(FdError::WriteError(io::Error::last_os_error()));
+        }
+        Ok(ret as usize)
     }
 }
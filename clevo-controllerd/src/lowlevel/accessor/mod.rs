@@ -1,3 +1,4 @@
 pub mod ec;
 pub mod fd;
+pub mod msr;
 // pub mod intel_rpal;
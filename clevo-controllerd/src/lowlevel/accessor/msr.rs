@@ -0,0 +1,345 @@
+use std::fs::{File, OpenOptions};
+use std::os::unix::fs::FileExt;
+
+pub const MSR_DEV_PATH: &str = "/dev/cpu";
+
+pub const IA32_THERM_STATUS: u32 = 0x19C;
+pub const IA32_PACKAGE_THERM_STATUS: u32 = 0x1B1;
+pub const MSR_TEMPERATURE_TARGET: u32 = 0x1A2;
+pub const MSR_VOLTAGE_OFFSET_MAILBOX: u32 = 0x150;
+
+const MAILBOX_READ_CMD: u64 = 0x8000_0010_0000_0000;
+const MAILBOX_WRITE_CMD: u64 = 0x8000_0011_0000_0000;
+// Only allow undervolting, and not so much that the machine is unlikely to boot
+const MIN_VOLTAGE_OFFSET_MV: i32 = -250;
+const MAX_VOLTAGE_OFFSET_MV: i32 = 0;
+
+#[derive(Debug, thiserror::Error)]
+pub enum MsrError {
+    #[error("Failed to open msr device {0}: {1}")]
+    OpenError(String, std::io::Error),
+    #[error("Failed to read msr 0x{0:x}: {1}")]
+    ReadError(u32, std::io::Error),
+    #[error("Failed to write msr 0x{0:x}: {1}")]
+    WriteError(u32, std::io::Error),
+    #[error("Writing msr is not enabled")]
+    WriteDisabled,
+    #[error("Invalid value: {0}")]
+    InvalidValue(String),
+}
+
+type Result<T> = std::result::Result<T, MsrError>;
+
+fn bit(value: u64, index: u8) -> bool {
+    (value >> index) & 1 == 1
+}
+
+/// IA32_THERM_STATUS, thermal status of a single core
+#[derive(Debug, Clone, Copy)]
+pub struct ThermStatus(pub u64);
+
+impl ThermStatus {
+    pub fn thermal(&self) -> bool {
+        bit(self.0, 0)
+    }
+    pub fn prochot(&self) -> bool {
+        bit(self.0, 2)
+    }
+    pub fn critical(&self) -> bool {
+        bit(self.0, 4)
+    }
+    pub fn power_limit(&self) -> bool {
+        bit(self.0, 10)
+    }
+    // Degrees Celsius below TjMax, None if the reading is not valid
+    pub fn readout(&self) -> Option<u8> {
+        bit(self.0, 31).then_some(((self.0 >> 16) & 0x7F) as u8)
+    }
+}
+
+/// IA32_PACKAGE_THERM_STATUS, thermal status of the whole package
+#[derive(Debug, Clone, Copy)]
+pub struct PackageThermStatus(pub u64);
+
+impl PackageThermStatus {
+    pub fn thermal(&self) -> bool {
+        bit(self.0, 0)
+    }
+    pub fn prochot(&self) -> bool {
+        bit(self.0, 2)
+    }
+    pub fn critical(&self) -> bool {
+        bit(self.0, 4)
+    }
+    pub fn power_limit(&self) -> bool {
+        bit(self.0, 10)
+    }
+    // Degrees Celsius below TjMax
+    pub fn readout(&self) -> u8 {
+        ((self.0 >> 16) & 0x7F) as u8
+    }
+}
+
+/// MSR_TEMPERATURE_TARGET, TjMax and the offset applied to it
+#[derive(Debug, Clone, Copy)]
+pub struct TemperatureTarget(pub u64);
+
+impl TemperatureTarget {
+    // TjMax in degrees Celsius
+    pub fn tj_max(&self) -> u8 {
+        ((self.0 >> 16) & 0xFF) as u8
+    }
+    // Degrees Celsius subtracted from TjMax before throttling starts
+    pub fn offset(&self) -> u8 {
+        ((self.0 >> 24) & 0x3F) as u8
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VoltagePlane {
+    Core = 0,
+    Gpu = 1,
+    Cache = 2,
+    Uncore = 3,
+    AnalogIo = 4,
+}
+
+// The mailbox holds an 11 bits signed offset in 1/1024 V at bit 21
+fn encode_voltage_offset(plane: VoltagePlane, offset_mv: i32) -> u64 {
+    let units = (offset_mv as f32 * 1.024).round() as i32;
+    MAILBOX_WRITE_CMD | ((plane as u64) << 40) | ((((units as u32) & 0x7FF) as u64) << 21)
+}
+
+fn decode_voltage_offset(value: u64) -> i32 {
+    let mut units = ((value >> 21) & 0x7FF) as i32;
+    if units & 0x400 != 0 {
+        units -= 0x800;
+    }
+    (units as f32 / 1.024).round() as i32
+}
+
+/// Read and write model specific registers through the msr driver (`modprobe msr`, needs root).
+/// Every cpu is a file at `<root>/<cpu>/msr` and each register is 8 bytes at its own offset,
+/// so a sparse file can stand in for the device.
+#[derive(Debug)]
+pub struct MsrAccessor {
+    root: String,
+    write_enabled: bool,
+}
+
+impl Default for MsrAccessor {
+    fn default() -> Self {
+        MsrAccessor::new()
+    }
+}
+
+impl MsrAccessor {
+    pub fn new() -> Self {
+        MsrAccessor::with_root(MSR_DEV_PATH)
+    }
+
+    pub fn with_root(root: &str) -> Self {
+        MsrAccessor {
+            root: root.to_string(),
+            write_enabled: false,
+        }
+    }
+
+    // Writes are refused until explicitly enabled
+    pub fn enable_write(&mut self, enable: bool) {
+        self.write_enabled = enable;
+    }
+
+    pub fn is_write_enabled(&self) -> bool {
+        self.write_enabled
+    }
+
+    fn path(&self, cpu: usize) -> String {
+        format!("{}/{}/msr", self.root, cpu)
+    }
+
+    // Check the device is there and readable
+    pub fn is_available(&self, cpu: usize) -> bool {
+        File::open(self.path(cpu)).is_ok()
+    }
+
+    pub fn read(&self, cpu: usize, offset: u32) -> Result<u64> {
+        let path = self.path(cpu);
+        let file = File::open(&path).map_err(|e| MsrError::OpenError(path, e))?;
+        let mut buffer = [0u8; 8];
+        file.read_exact_at(&mut buffer, offset as u64)
+            .map_err(|e| MsrError::ReadError(offset, e))?;
+        Ok(u64::from_le_bytes(buffer))
+    }
+
+    pub fn write(&self, cpu: usize, offset: u32, value: u64) -> Result<()> {
+        if !self.write_enabled {
+            return Err(MsrError::WriteDisabled);
+        }
+        self.write_unchecked(cpu, offset, value)
+    }
+
+    fn write_unchecked(&self, cpu: usize, offset: u32, value: u64) -> Result<()> {
+        let path = self.path(cpu);
+        let file = OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .map_err(|e| MsrError::OpenError(path, e))?;
+        file.write_all_at(&value.to_le_bytes(), offset as u64)
+            .map_err(|e| MsrError::WriteError(offset, e))
+    }
+
+    pub fn therm_status(&self, cpu: usize) -> Result<ThermStatus> {
+        Ok(ThermStatus(self.read(cpu, IA32_THERM_STATUS)?))
+    }
+
+    pub fn package_therm_status(&self) -> Result<PackageThermStatus> {
+        Ok(PackageThermStatus(self.read(0, IA32_PACKAGE_THERM_STATUS)?))
+    }
+
+    pub fn temperature_target(&self) -> Result<TemperatureTarget> {
+        Ok(TemperatureTarget(self.read(0, MSR_TEMPERATURE_TARGET)?))
+    }
+
+    /// Voltage offset of the plane in mV, reading goes through the mailbox as well,
+    /// the read command doesn't change any setting so it's allowed without write enabled
+    pub fn voltage_offset(&self, plane: VoltagePlane) -> Result<i32> {
+        self.write_unchecked(
+            0,
+            MSR_VOLTAGE_OFFSET_MAILBOX,
+            MAILBOX_READ_CMD | ((plane as u64) << 40),
+        )?;
+        Ok(decode_voltage_offset(
+            self.read(0, MSR_VOLTAGE_OFFSET_MAILBOX)?,
+        ))
+    }
+
+    pub fn set_voltage_offset(&self, plane: VoltagePlane, offset_mv: i32) -> Result<()> {
+        if !(MIN_VOLTAGE_OFFSET_MV..=MAX_VOLTAGE_OFFSET_MV).contains(&offset_mv) {
+            return Err(MsrError::InvalidValue(format!(
+                "voltage offset {} mV out of range {}..={} mV",
+                offset_mv, MIN_VOLTAGE_OFFSET_MV, MAX_VOLTAGE_OFFSET_MV
+            )));
+        }
+        self.write(
+            0,
+            MSR_VOLTAGE_OFFSET_MAILBOX,
+            encode_voltage_offset(plane, offset_mv),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A sparse file standing in for /dev/cpu/0/msr, removed on drop
+    struct FakeMsr {
+        root: std::path::PathBuf,
+    }
+
+    impl FakeMsr {
+        fn new(name: &str) -> Self {
+            let root = std::env::temp_dir().join(format!("msr-{}-{}", name, std::process::id()));
+            std::fs::create_dir_all(root.join("0")).unwrap();
+            let file = File::create(root.join("0/msr")).unwrap();
+            file.set_len(0x1000).unwrap();
+            FakeMsr { root }
+        }
+
+        fn accessor(&self) -> MsrAccessor {
+            MsrAccessor::with_root(self.root.to_str().unwrap())
+        }
+
+        fn poke(&self, offset: u32, value: u64) {
+            let file = OpenOptions::new()
+                .write(true)
+                .open(self.root.join("0/msr"))
+                .unwrap();
+            file.write_all_at(&value.to_le_bytes(), offset as u64)
+                .unwrap();
+        }
+    }
+
+    impl Drop for FakeMsr {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.root);
+        }
+    }
+
+    #[test]
+    fn read_write_at_offset() {
+        let fake = FakeMsr::new("offset");
+        let mut msr = fake.accessor();
+        assert!(msr.is_available(0));
+        assert!(!msr.is_available(1));
+        msr.enable_write(true);
+        msr.write(0, 0x10, 0x1122_3344_5566_7788).unwrap();
+        assert_eq!(msr.read(0, 0x10).unwrap(), 0x1122_3344_5566_7788);
+        // the neighbouring registers are untouched
+        assert_eq!(msr.read(0, 0x08).unwrap(), 0);
+        assert_eq!(msr.read(0, 0x18).unwrap(), 0);
+        assert!(matches!(msr.read(1, 0x10), Err(MsrError::OpenError(..))));
+    }
+
+    #[test]
+    fn write_refused_until_enabled() {
+        let fake = FakeMsr::new("write");
+        let mut msr = fake.accessor();
+        assert!(!msr.is_write_enabled());
+        assert!(matches!(
+            msr.write(0, 0x10, 1),
+            Err(MsrError::WriteDisabled)
+        ));
+        assert!(matches!(
+            msr.set_voltage_offset(VoltagePlane::Core, -50),
+            Err(MsrError::WriteDisabled)
+        ));
+        assert_eq!(msr.read(0, 0x10).unwrap(), 0);
+        msr.enable_write(true);
+        msr.write(0, 0x10, 1).unwrap();
+        assert_eq!(msr.read(0, 0x10).unwrap(), 1);
+    }
+
+    #[test]
+    fn decode_therm_status() {
+        let fake = FakeMsr::new("therm");
+        // thermal, power limit, valid reading 20 degrees below TjMax
+        fake.poke(IA32_THERM_STATUS, 1 << 31 | 20 << 16 | 1 << 10 | 1);
+        let status = fake.accessor().therm_status(0).unwrap();
+        assert!(status.thermal());
+        assert!(!status.prochot());
+        assert!(!status.critical());
+        assert!(status.power_limit());
+        assert_eq!(status.readout(), Some(20));
+        // without the valid bit there is no reading
+        fake.poke(IA32_THERM_STATUS, 20 << 16 | 1 << 2);
+        let status = fake.accessor().therm_status(0).unwrap();
+        assert!(status.prochot());
+        assert_eq!(status.readout(), None);
+    }
+
+    #[test]
+    fn decode_temperature_target() {
+        let fake = FakeMsr::new("target");
+        fake.poke(MSR_TEMPERATURE_TARGET, 5 << 24 | 100 << 16);
+        let target = fake.accessor().temperature_target().unwrap();
+        assert_eq!(target.tj_max(), 100);
+        assert_eq!(target.offset(), 5);
+    }
+
+    #[test]
+    fn voltage_offset_round_trip() {
+        for offset_mv in [0, -1, -50, -125, -250] {
+            let value = encode_voltage_offset(VoltagePlane::Cache, offset_mv);
+            assert_eq!((value >> 40) & 0x7, VoltagePlane::Cache as u64);
+            assert_eq!(decode_voltage_offset(value), offset_mv);
+        }
+        let mut msr = MsrAccessor::with_root("/nonexistent");
+        msr.enable_write(true);
+        assert!(matches!(
+            msr.set_voltage_offset(VoltagePlane::Core, 10),
+            Err(MsrError::InvalidValue(_))
+        ));
+    }
+}
@@ -1 +1,3 @@
 pub mod accessor;
+pub mod peer;
+pub mod procfs;
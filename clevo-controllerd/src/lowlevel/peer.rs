@@ -0,0 +1,139 @@
+use libc::{c_char, c_int, c_void, gid_t};
+use std::{
+    ffi::CString,
+    os::fd::{AsFd, AsRawFd},
+};
+
+// Large enough for the passwd and group entries of a usual system, grown when it isn't
+const ENTRY_BUFFER_SIZE: usize = 1024;
+
+#[derive(Debug, thiserror::Error)]
+pub enum PeerError {
+    #[error("Failed to read peer credentials: {0}")]
+    CredentialsError(std::io::Error),
+    #[error("No group named {0}")]
+    UnknownGroup(String),
+}
+
+type Result<T> = std::result::Result<T, PeerError>;
+
+/// The process on the other end of a unix socket, as the kernel saw it when it connected
+#[derive(Debug, Clone, Copy)]
+pub struct PeerCredentials {
+    pid: i32,
+    uid: u32,
+    gid: u32, // Primary group
+}
+
+impl PeerCredentials {
+    /// SO_PEERCRED of a connected socket
+    pub fn of(socket: &impl AsFd) -> Result<Self> {
+        let mut cred = libc::ucred {
+            pid: 0,
+            uid: 0,
+            gid: 0,
+        };
+        let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+        let ret = unsafe {
+            libc::getsockopt(
+                socket.as_fd().as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_PEERCRED,
+                &mut cred as *mut _ as *mut c_void,
+                &mut len,
+            )
+        };
+        if ret != 0 {
+            return Err(PeerError::CredentialsError(std::io::Error::last_os_error()));
+        }
+        Ok(Self {
+            pid: cred.pid,
+            uid: cred.uid,
+            gid: cred.gid,
+        })
+    }
+
+    pub fn get_pid(&self) -> i32 {
+        self.pid
+    }
+    pub fn get_uid(&self) -> u32 {
+        self.uid
+    }
+    pub fn get_gid(&self) -> u32 {
+        self.gid
+    }
+    pub fn is_root(&self) -> bool {
+        self.uid == 0
+    }
+
+    /// Whether the peer's user is in the group, as its primary group or a supplementary one
+    /// from the group database
+    pub fn in_group(&self, gid: u32) -> bool {
+        self.gid == gid || self.groups().is_some_and(|groups| groups.contains(&gid))
+    }
+
+    // Supplementary groups of the user, None if the user has no passwd entry
+    fn groups(&self) -> Option<Vec<gid_t>> {
+        let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+        let mut buffer = vec![0 as c_char; ENTRY_BUFFER_SIZE];
+        let mut entry = std::ptr::null_mut();
+        loop {
+            let ret = unsafe {
+                libc::getpwuid_r(
+                    self.uid,
+                    &mut passwd,
+                    buffer.as_mut_ptr(),
+                    buffer.len(),
+                    &mut entry,
+                )
+            };
+            if ret != libc::ERANGE {
+                break;
+            }
+            buffer.resize(buffer.len() * 2, 0);
+        }
+        if entry.is_null() {
+            return None;
+        }
+        let mut groups: Vec<gid_t> = vec![0; 32];
+        loop {
+            let mut count = groups.len() as c_int;
+            // fails when the list is too short, with count set to the length it needs
+            let ret = unsafe {
+                libc::getgrouplist(passwd.pw_name, self.gid, groups.as_mut_ptr(), &mut count)
+            };
+            if ret >= 0 {
+                groups.truncate(count as usize);
+                return Some(groups);
+            }
+            groups.resize((count as usize).max(groups.len() * 2), 0);
+        }
+    }
+}
+
+/// Id of the group called `name`
+pub fn group_id(name: &str) -> Result<u32> {
+    let c_name = CString::new(name).map_err(|_| PeerError::UnknownGroup(name.to_string()))?;
+    let mut group: libc::group = unsafe { std::mem::zeroed() };
+    let mut buffer = vec![0 as c_char; ENTRY_BUFFER_SIZE];
+    let mut entry = std::ptr::null_mut();
+    loop {
+        let ret = unsafe {
+            libc::getgrnam_r(
+                c_name.as_ptr(),
+                &mut group,
+                buffer.as_mut_ptr(),
+                buffer.len(),
+                &mut entry,
+            )
+        };
+        if ret != libc::ERANGE {
+            break;
+        }
+        buffer.resize(buffer.len() * 2, 0);
+    }
+    if entry.is_null() {
+        return Err(PeerError::UnknownGroup(name.to_string()));
+    }
+    Ok(group.gr_gid)
+}
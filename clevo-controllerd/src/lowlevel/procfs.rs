@@ -0,0 +1,9 @@
+/// Root of the proc filesystem, `PROC_ROOT` points it at a captured copy for testing
+pub fn proc_root() -> String {
+    dotenv::var("PROC_ROOT").unwrap_or_else(|_| "/proc".to_string())
+}
+
+/// Path of a file under the proc root, e.g. `proc_path("net/dev")`
+pub fn proc_path(name: &str) -> String {
+    format!("{}/{}", proc_root().trim_end_matches('/'), name)
+}
@@ -1,25 +1,187 @@
 use clevo_controllerd::{
-    component::{cpu::intel::IntelCpu, fan::Fan},
-    service::core::Service,
+    component::{
+        Component,
+        cpu::intel::IntelCpu,
+        fan::Fan,
+        gpu::{self, Gpu, mock::MockGpu},
+        mem::Mem,
+        net::Net,
+        power_supply::PowerSupply,
+        storage::Storage,
+    },
+    lowlevel::{
+        accessor::msr::{MsrAccessor, VoltagePlane},
+        peer,
+    },
+    service::{activation, client::DEFAULT_LEASE_TIMEOUT, core::Service},
 };
+use lib::{client::DEFAULT_SOCKET_NAME, proto::MsgLimits, stream::SocketPermissions};
+use std::os::fd::OwnedFd;
+
+// Fixed ids, so a component keeps its id whatever else is present on this machine
+const CPU_ID: u8 = 0;
+const FAN_ID: u8 = 1;
+const NET_ID: u8 = 2;
+const MEM_ID: u8 = 3;
+const STORAGE_ID: u8 = 4;
+const POWER_SUPPLY_ID: u8 = 5;
+// Gpus take the ids from here on, derived from their pci bus
+const GPU_ID_BASE: u8 = 16;
+
+// Add a component that may be missing on this machine
+fn add_optional<T, E>(
+    service: &mut Service,
+    id: u8,
+    name: &str,
+    init: impl FnOnce(u8) -> Result<T, E>,
+) where
+    T: Component + Send + Sync + 'static,
+    E: std::fmt::Display,
+{
+    match init(id) {
+        Ok(component) => service
+            .add_hardware(id, Box::new(component))
+            .expect("Failed to add hardware"),
+        Err(e) => println!("{} not available: {}", name, e),
+    }
+}
+
+// The id of a gpu follows its pci bus ("0000:01:00.0" is 17), so adding or removing another
+// gpu doesn't change it. Two gpus on the same bus (or none, the mock gpu) take the next free id
+fn gpu_id(pci_id: Option<&str>, taken: &[u8]) -> u8 {
+    let slots = (u8::MAX - GPU_ID_BASE) as u16 + 1;
+    let bus = pci_id
+        .and_then(|pci_id| pci_id.split(':').nth(1))
+        .and_then(|bus| u8::from_str_radix(bus, 16).ok())
+        .unwrap_or(0) as u16;
+    (0..slots)
+        .map(|offset| GPU_ID_BASE + ((bus + offset) % slots) as u8)
+        .find(|id| !taken.contains(id))
+        .expect("More gpus than gpu ids")
+}
+
+// A number from the environment or .env, the default if unset or not a number
+fn env_or<T: std::str::FromStr>(name: &str, default: T) -> T {
+    dotenv::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+// Undervolt from the environment, e.g. MSR_WRITE=1 VOLTAGE_OFFSET_CORE=-50 (mV), the msr
+// stays read-only unless MSR_WRITE is set
+fn apply_voltage_offsets() {
+    let planes = [
+        ("VOLTAGE_OFFSET_CORE", VoltagePlane::Core),
+        ("VOLTAGE_OFFSET_GPU", VoltagePlane::Gpu),
+        ("VOLTAGE_OFFSET_CACHE", VoltagePlane::Cache),
+        ("VOLTAGE_OFFSET_UNCORE", VoltagePlane::Uncore),
+        ("VOLTAGE_OFFSET_ANALOG_IO", VoltagePlane::AnalogIo),
+    ];
+    let mut msr = MsrAccessor::new();
+    msr.enable_write(dotenv::var("MSR_WRITE").is_ok_and(|value| value == "1"));
+    for (name, plane) in planes {
+        let Some(offset_mv) = dotenv::var(name).ok().and_then(|value| value.parse().ok()) else {
+            continue;
+        };
+        match msr.set_voltage_offset(plane, offset_mv) {
+            Ok(()) => println!("{:?} voltage offset set to {} mV", plane, offset_mv),
+            Err(e) => println!("Failed to set {:?} voltage offset: {}", plane, e),
+        }
+    }
+}
 
 fn main() {
-    let mut service = Service::new("clevo-controler.sock").expect("Failed to create service");
+    // SAFETY: nothing else runs yet, the runtime and the monitor are started afterwards
+    let listen_fd = unsafe { activation::listen_fd() };
+    tokio::runtime::Runtime::new()
+        .expect("Failed to start the runtime")
+        .block_on(run(listen_fd));
+}
+
+async fn run(listen_fd: Option<OwnedFd>) {
+    // a path such as /run/clevo-controller.sock can be restricted with SOCKET_MODE and
+    // SOCKET_GROUP, a namespaced name can't
+    let socket_name =
+        dotenv::var("SOCKET_NAME").unwrap_or_else(|_| DEFAULT_SOCKET_NAME.to_string());
+    let mut service = Service::new(&socket_name).expect("Failed to create service");
+    let socket_mode = dotenv::var("SOCKET_MODE")
+        .ok()
+        .and_then(|mode| u32::from_str_radix(&mode, 8).ok());
+    let socket_group =
+        dotenv::var("SOCKET_GROUP")
+            .ok()
+            .and_then(|name| match peer::group_id(&name) {
+                Ok(gid) => Some(gid),
+                Err(e) => {
+                    println!("Keeping the socket group: {}", e);
+                    None
+                }
+            });
+    service.set_socket_permissions(SocketPermissions::new(
+        socket_mode.unwrap_or(SocketPermissions::default().get_mode()),
+        socket_group,
+    ));
+    let limits = MsgLimits::default();
+    service.set_limits(MsgLimits::new(
+        env_or("MAX_PACKET_SIZE", limits.get_max_packet_size()),
+        env_or("MAX_PAYLOAD_SIZE", limits.get_max_payload_size()),
+        env_or("MAX_PAYLOAD_COUNT", limits.get_max_payload_count()),
+    ));
+    // root may always change the hardware, e.g. CONTROL_GROUP=clevo lets that group too
+    if let Ok(name) = dotenv::var("CONTROL_GROUP") {
+        match peer::group_id(&name) {
+            Ok(gid) => service.set_control_group(Some(gid)),
+            Err(e) => println!("Only root may change the hardware: {}", e),
+        }
+    }
+    // a component stays controlled by a connection this long after its last control request
+    service.set_lease_timeout(std::time::Duration::from_secs(env_or(
+        "LEASE_TIMEOUT",
+        DEFAULT_LEASE_TIMEOUT.as_secs(),
+    )));
+    apply_voltage_offsets();
     let cpu = IntelCpu::init(0).unwrap();
     let fan = Fan::new();
-    std::thread::sleep(std::time::Duration::from_secs(1));
+    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
     service
-        .add_hardware(0, Box::new(cpu))
+        .add_hardware(CPU_ID, Box::new(cpu))
         .expect("Failed to add hardware");
     service
-        .add_hardware(1, Box::new(fan))
+        .add_hardware(FAN_ID, Box::new(fan))
         .expect("Failed to add hardware");
+    let mut gpus = gpu::enumerate();
+    if gpus.is_empty() && dotenv::var("MOCK_GPU").is_ok() {
+        println!("No gpu available, using mock gpu");
+        gpus.push(Box::new(MockGpu::new()));
+    }
+    let mut gpu_ids = vec![];
+    for backend in gpus {
+        let id = gpu_id(
+            backend
+                .device_info()
+                .as_ref()
+                .map(|device_info| device_info.get_pci_id()),
+            &gpu_ids,
+        );
+        gpu_ids.push(id);
+        service
+            .add_hardware(id, Box::new(Gpu::new(id, backend)))
+            .expect("Failed to add hardware");
+    }
+    add_optional(&mut service, NET_ID, "Network", Net::init);
+    add_optional(&mut service, MEM_ID, "Memory", Mem::init);
+    add_optional(&mut service, STORAGE_ID, "Storage", Storage::init);
+    add_optional(
+        &mut service,
+        POWER_SUPPLY_ID,
+        "Power supply",
+        PowerSupply::init,
+    );
     let monitor_handle = service.spawn_monitor().expect("Failed to spawn service");
     let msg_handler_handle = service
-        .spawn_msg_handler()
+        .spawn_msg_handler(listen_fd)
         .expect("Failed to spawn service");
-    msg_handler_handle
-        .join()
-        .expect("Service thread has panicked");
+    msg_handler_handle.await.expect("Service task has panicked");
     monitor_handle.join().expect("Service thread has panicked");
 }
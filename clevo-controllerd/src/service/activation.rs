@@ -0,0 +1,69 @@
+//! systemd socket activation, see sd_listen_fds(3)
+
+use libc::{c_int, c_void};
+use std::os::fd::{FromRawFd, OwnedFd, RawFd};
+
+const SD_LISTEN_FDS_START: RawFd = 3;
+
+// Whether the descriptor is a listening unix stream socket, the only kind we can serve on
+fn is_unix_listener(fd: RawFd) -> bool {
+    let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+    if unsafe { libc::fstat(fd, &mut stat) } != 0 || stat.st_mode & libc::S_IFMT != libc::S_IFSOCK {
+        return false;
+    }
+    let option = |name: c_int| {
+        let mut value: c_int = 0;
+        let mut len = std::mem::size_of::<c_int>() as libc::socklen_t;
+        let ret = unsafe {
+            libc::getsockopt(
+                fd,
+                libc::SOL_SOCKET,
+                name,
+                &mut value as *mut _ as *mut c_void,
+                &mut len,
+            )
+        };
+        (ret == 0).then_some(value)
+    };
+    option(libc::SO_ACCEPTCONN) == Some(1)
+        && option(libc::SO_DOMAIN) == Some(libc::AF_UNIX)
+        && option(libc::SO_TYPE) == Some(libc::SOCK_STREAM)
+}
+
+/// The listening socket systemd passed to this process, None when it wasn't socket activated
+/// or the descriptor isn't a listening unix socket. The activation variables are removed from
+/// the environment, they are inherited by our children too but only meant for us
+///
+/// # Safety
+///
+/// Changes the environment, no other thread may be running, call it first thing in `main`
+pub unsafe fn listen_fd() -> Option<OwnedFd> {
+    let pid = std::env::var("LISTEN_PID").ok();
+    let fds = std::env::var("LISTEN_FDS").ok();
+    for name in ["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"] {
+        // SAFETY: the caller guarantees this is the only thread
+        unsafe { std::env::remove_var(name) };
+    }
+    let pid: u32 = pid?.parse().ok()?;
+    if pid != std::process::id() {
+        return None;
+    }
+    let fds: RawFd = fds?.parse().ok()?;
+    if fds < 1 {
+        return None;
+    }
+    if fds > 1 {
+        println!("systemd passed {} sockets, only the first one is used", fds);
+    }
+    if !is_unix_listener(SD_LISTEN_FDS_START) {
+        println!(
+            "fd {} is not a listening unix socket, creating the socket file instead",
+            SD_LISTEN_FDS_START
+        );
+        return None;
+    }
+    unsafe {
+        libc::fcntl(SD_LISTEN_FDS_START, libc::F_SETFD, libc::FD_CLOEXEC);
+        Some(OwnedFd::from_raw_fd(SD_LISTEN_FDS_START))
+    }
+}
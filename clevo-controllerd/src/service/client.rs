@@ -0,0 +1,148 @@
+use crate::component::Component;
+use lib::{
+    field::{event::Event, subscription::Subscription},
+    handshake::Session,
+    proto::*,
+};
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+use tokio::sync::mpsc::{Sender, error::TrySendError};
+
+type Result<T> = std::result::Result<T, ProtoError>;
+
+/// Messages for a connection that don't answer one of its requests, e.g. pushes
+pub type Outbox = Sender<MsgBody>;
+
+/// Pushes queued for a connection before it is considered stuck and dropped
+pub const OUTBOX_CAPACITY: usize = 64;
+
+/// How long a component stays leased after the last control request of its holder
+pub const DEFAULT_LEASE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// State of one connection, shared between its task and the monitor
+pub struct Client {
+    session: Session,
+    outbox: Outbox, // Written to the socket by the connection's task
+    subscription: Option<Subscription>, // None until the client subscribes
+    last_push: Option<Instant>,
+    leases: HashMap<u8, Instant>, // Components this connection controls, by last control
+    lease_timeout: Duration,
+    may_control: bool, // Whether the peer may send control requests
+}
+
+impl Client {
+    pub fn new(
+        session: Session,
+        outbox: Outbox,
+        lease_timeout: Duration,
+        may_control: bool,
+    ) -> Self {
+        Self {
+            session,
+            outbox,
+            subscription: None,
+            last_push: None,
+            leases: HashMap::new(),
+            lease_timeout,
+            may_control,
+        }
+    }
+
+    pub fn get_session(&self) -> &Session {
+        &self.session
+    }
+
+    pub fn may_control(&self) -> bool {
+        self.may_control
+    }
+
+    pub fn subscribe(&mut self, subscription: Subscription) {
+        self.subscription = Some(subscription);
+        self.last_push = None;
+    }
+
+    pub fn unsubscribe(&mut self) {
+        self.subscription = None;
+    }
+
+    /// Whether the connection controls the component, a lease not renewed expires
+    pub fn holds(&self, id: u8) -> bool {
+        self.leases
+            .get(&id)
+            .is_some_and(|renewed| renewed.elapsed() < self.lease_timeout)
+    }
+
+    /// Take the lease on the component or renew it
+    pub fn take_lease(&mut self, id: u8) {
+        self.leases.insert(id, Instant::now());
+    }
+
+    pub fn release_lease(&mut self, id: u8) {
+        self.leases.remove(&id);
+    }
+
+    // A client that doesn't read its pushes fills the outbox and gets dropped
+    fn send(&self, body: MsgBody) -> Result<()> {
+        self.outbox.try_send(body).map_err(|e| match e {
+            TrySendError::Full(_) => ProtoError::Io("outbox full, client not reading".to_string()),
+            TrySendError::Closed(_) => ProtoError::Io("connection closed".to_string()),
+        })
+    }
+
+    /// Push the events, and every topic once the minimum interval has passed, if the client
+    /// subscribed. An error means the connection is gone
+    pub fn push(
+        &mut self,
+        hardwares: &mut HashMap<u8, Box<dyn Component + Send + Sync>>,
+        events: &[(u8, Event)],
+    ) -> Result<()> {
+        let Some(subscription) = &self.subscription else {
+            return Ok(());
+        };
+        if subscription.wants_events() {
+            for (id, event) in events {
+                let packet = MsgPacket::new(MsgMode::Notify, None, 0, *id, MsgCommand::Event);
+                self.send(MsgBody::new(packet, vec![event.serialize()?]))?;
+            }
+        }
+        let min_interval = Duration::from_millis(subscription.get_min_interval());
+        if self
+            .last_push
+            .is_some_and(|last_push| last_push.elapsed() < min_interval)
+        {
+            return Ok(());
+        }
+        // only readable topics are accepted by subscribe
+        for topic in subscription
+            .get_topics()
+            .iter()
+            .filter(|topic| topic.is_readable())
+        {
+            let mut packet = MsgPacket::new(
+                MsgMode::Notify,
+                None,
+                0,
+                topic.get_id_num(),
+                topic.get_command().clone(),
+            );
+            let response = topic.get_request().and_then(|request| {
+                match hardwares.get_mut(&topic.get_id_num()) {
+                    Some(hardware) => hardware.handle_request(&request),
+                    None => Err(MsgError::UnknownComponent(topic.get_id_num())),
+                }
+            });
+            let payload = match response {
+                Ok(response) => response.encode_payload()?,
+                Err(e) => {
+                    packet.set_error(self.session.compatible_error(e));
+                    vec![]
+                }
+            };
+            self.send(MsgBody::new(packet, payload))?;
+        }
+        self.last_push = Some(Instant::now());
+        Ok(())
+    }
+}
@@ -1,29 +1,48 @@
 use crate::component::{Component, ComponentError};
+use crate::lowlevel::peer::PeerCredentials;
+use crate::service::client::{Client, DEFAULT_LEASE_TIMEOUT, OUTBOX_CAPACITY};
 use lib::{
-    field::ComponentList,
-    proto::*,
-    stream::{SocketStream, StreamListener},
+    field::{ComponentList, fan_speed::FanIndex},
+    handshake::{Features, SUPPORTED_FEATURES, Session, server_handshake_async},
+    proto::{
+        codec::{MsgReader, send_msg},
+        *,
+    },
+    stream::{
+        SocketPermissions,
+        asynchronous::{AsyncSocketStream, AsyncStreamListener},
+    },
 };
 use std::{
     collections::HashMap,
+    os::fd::OwnedFd,
     sync::{Arc, Mutex},
-    thread::JoinHandle,
+    time::Duration,
 };
+use tokio::{sync::mpsc, task::JoinHandle};
 
 type Result<T> = std::result::Result<T, ProtoError>;
 
+type Components = HashMap<u8, Box<dyn Component + Send + Sync>>;
+
 pub enum ServiceError {
     ComponentError(ComponentError),
     ProtoError(ProtoError),
 }
 
+#[derive(Clone)]
 pub struct ServiceConfig {
     socket_name: String,
+    socket_permissions: SocketPermissions, // When socket_name is a path
+    limits: MsgLimits,                     // What a client may send
+    control_group: Option<u32>,            // Besides root, members may send control requests
+    lease_timeout: Duration,               // Without a control request, a lease expires
 }
 
 pub struct Service {
     config: ServiceConfig,
-    components: Arc<Mutex<HashMap<u8, Box<dyn Component + Send + Sync>>>>,
+    components: Arc<Mutex<Components>>,
+    clients: Arc<Mutex<HashMap<u64, Client>>>, // By connection number
 }
 
 impl Service {
@@ -31,11 +50,36 @@ impl Service {
         Ok(Self {
             config: ServiceConfig {
                 socket_name: socket_name.to_string(),
+                socket_permissions: SocketPermissions::default(),
+                limits: MsgLimits::default(),
+                control_group: None,
+                lease_timeout: DEFAULT_LEASE_TIMEOUT,
             },
             components: Arc::new(Mutex::new(HashMap::new())),
+            clients: Arc::new(Mutex::new(HashMap::new())),
         })
     }
 
+    /// Mode and group of the socket file when listening on a path
+    pub fn set_socket_permissions(&mut self, permissions: SocketPermissions) {
+        self.config.socket_permissions = permissions;
+    }
+
+    /// Limits for the messages of the connections accepted from now on
+    pub fn set_limits(&mut self, limits: MsgLimits) {
+        self.config.limits = limits;
+    }
+
+    /// Let the members of the group change the hardware, not only root
+    pub fn set_control_group(&mut self, gid: Option<u32>) {
+        self.config.control_group = gid;
+    }
+
+    /// How long a component stays controlled by a connection after its last control request
+    pub fn set_lease_timeout(&mut self, timeout: Duration) {
+        self.config.lease_timeout = timeout;
+    }
+
     pub fn add_hardware(
         &mut self,
         id: u8,
@@ -46,15 +90,36 @@ impl Service {
         Ok(())
     }
 
-    pub fn spawn_monitor(&mut self) -> Result<JoinHandle<()>> {
+    pub fn spawn_monitor(&mut self) -> Result<std::thread::JoinHandle<()>> {
         let hardwares_clone = Arc::clone(&self.components);
+        let clients_clone = Arc::clone(&self.clients);
         // thread to refresh the status of the hardware
         let handle = std::thread::spawn(move || {
             loop {
                 let mut hardwares = hardwares_clone.lock().unwrap();
-                hardwares.iter_mut().for_each(|(_, hardware)| {
-                    hardware.refresh_status().unwrap();
+                let mut events = vec![];
+                hardwares.iter_mut().for_each(|(id, hardware)| {
+                    // e.g. an EC timeout, the last status is kept until the next refresh
+                    if let Err(e) = hardware.refresh_status() {
+                        println!("component {}: failed to refresh: {}", id, e);
+                    }
+                    for event in hardware.poll_events() {
+                        println!("component {}: {}", id, event);
+                        events.push((*id, event));
+                    }
                 });
+                // lock order is components, then clients
+                let mut clients = clients_clone.lock().unwrap();
+                clients.retain(
+                    |connection, client| match client.push(&mut hardwares, &events) {
+                        Ok(()) => true,
+                        Err(e) => {
+                            println!("Dropping connection {}: {}", connection, e);
+                            false
+                        }
+                    },
+                );
+                drop(clients);
                 drop(hardwares);
                 std::thread::sleep(std::time::Duration::from_secs(3));
             }
@@ -62,73 +127,267 @@ impl Service {
         println!("hardware monitor started");
         Ok(handle)
     }
-    pub fn spawn_msg_handler(&mut self) -> Result<JoinHandle<()>> {
-        let socket_name = self.config.socket_name.clone();
-        let hardwares_clone = Arc::clone(&self.components);
-        fn handle_msg(
-            hardwares: &mut std::sync::MutexGuard<
-                '_,
-                HashMap<u8, Box<dyn Component + Send + Sync>>,
-            >,
-            stream: &mut SocketStream,
-            body: MsgBody,
-        ) -> Result<()> {
-            let mut packet = body.get_packet().clone();
-            packet.set_mode(MsgMode::Reply);
-            let mut payload = vec![];
-            // dbg!(&packet.get_command());
-            match packet.get_command() {
-                MsgCommand::GetComponentList => {
-                    let mut hardware_list = ComponentList(HashMap::new());
-                    hardwares.iter().for_each(|(id, hardware)| {
-                        hardware_list.0.insert(*id, hardware.get_desc());
-                    });
-                    dbg!(&hardware_list);
-                    payload.push(bincode::encode_to_vec(
-                        &hardware_list,
-                        bincode::config::standard(),
-                    )?);
-                }
-                _ => {
-                    let hardware = hardwares.get_mut(&packet.get_id_num()).unwrap();
-                    let payload_ret =
-                        hardware.handle_command(packet.get_command(), body.get_payload());
-                    if let Ok(payload_ret) = payload_ret {
-                        payload = payload_ret;
-                    } else {
-                        packet.set_error(MsgError::UnsupportedOperation(format!(
-                            "Operation not supported by the hardware:{}",
-                            packet.get_command()
-                        )));
+    /// Accept connections and serve each one from its own task, on the socket systemd passed
+    /// if it started the daemon, see `activation::listen_fd`. Must be called from within a
+    /// tokio runtime
+    pub fn spawn_msg_handler(&mut self, listen_fd: Option<OwnedFd>) -> Result<JoinHandle<()>> {
+        let stream_listener = match listen_fd {
+            Some(fd) => {
+                println!("Listening on the socket passed by systemd");
+                AsyncStreamListener::from_fd(fd)?
+            }
+            None => AsyncStreamListener::with_permissions(
+                self.config.socket_name.as_str(),
+                &self.config.socket_permissions,
+            )?,
+        };
+        // shared by the connections accepted from now on
+        let config = Arc::new(self.config.clone());
+        let components = Arc::clone(&self.components);
+        let clients = Arc::clone(&self.clients);
+        let handle = tokio::spawn(async move {
+            let mut connection = 0;
+            loop {
+                let stream = match stream_listener.accept().await {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        println!("Failed to accept stream connection: {}", e);
+                        continue;
                     }
-                }
+                };
+                connection += 1;
+                // only the kernel's view of the peer is trusted, read before anything is sent
+                let peer = match PeerCredentials::of(&stream) {
+                    Ok(peer) => Some(peer),
+                    Err(e) => {
+                        println!("Connection {}: {}", connection, e);
+                        None
+                    }
+                };
+                tokio::spawn(serve_connection(
+                    Arc::clone(&components),
+                    Arc::clone(&clients),
+                    connection,
+                    stream,
+                    Arc::clone(&config),
+                    peer,
+                ));
             }
-            send_msg(stream, &MsgBody::new(packet, payload))
-                .expect("Failed to send hardware list reply");
-            Ok(())
+        });
+        println!("Message handler started, waiting for connections...");
+        Ok(handle)
+    }
+}
+
+// Replies are written as soon as the request is handled, pushes queued in the outbox by the
+// monitor are written in between
+async fn serve_connection(
+    components: Arc<Mutex<Components>>,
+    clients: Arc<Mutex<HashMap<u64, Client>>>,
+    connection: u64,
+    stream: AsyncSocketStream,
+    config: Arc<ServiceConfig>,
+    peer: Option<PeerCredentials>,
+) {
+    // the group database may be behind NSS, e.g. LDAP, keep its lookups off the runtime's
+    // workers
+    let control_group = config.control_group;
+    let may_control = match peer {
+        Some(peer) if peer.is_root() => true,
+        Some(peer) => {
+            tokio::task::spawn_blocking(move || control_group.is_some_and(|gid| peer.in_group(gid)))
+                .await
+                .unwrap_or(false)
         }
-        let handle = std::thread::spawn(move || {
-            let mut stream_listener = StreamListener::new(socket_name.as_str()).unwrap();
-            loop {
-                let mut stream = stream_listener
-                    .accept()
-                    .expect("Failed to accept stream connection");
-                println!("Stream accepted, starting to handle requests...");
-                loop {
-                    match recv_msg(&mut stream) {
-                        Ok(msg) => {
-                            let mut hardwares = hardwares_clone.lock().unwrap();
-                            handle_msg(&mut hardwares, &mut stream, msg).unwrap();
+        None => false,
+    };
+    let (reader, mut writer) = stream.split();
+    // a message beyond the limits or that doesn't decode closes the connection
+    let mut reader = MsgReader::with_limits(reader, config.limits);
+    let session = match server_handshake_async(&mut reader, &mut writer, SUPPORTED_FEATURES).await {
+        Ok(session) => {
+            println!(
+                "Connection {} accepted, protocol version {}, {}, starting to handle requests...",
+                connection,
+                session.get_version(),
+                if may_control {
+                    "may control"
+                } else {
+                    "read only"
+                }
+            );
+            session
+        }
+        Err(e) => {
+            println!("Handshake failed on connection {}: {}", connection, e);
+            return;
+        }
+    };
+    // the monitor drops a client whose outbox is full
+    let (outbox, mut outbox_receiver) = mpsc::channel(OUTBOX_CAPACITY);
+    clients.lock().unwrap().insert(
+        connection,
+        Client::new(session, outbox, config.lease_timeout, may_control),
+    );
+    loop {
+        let body = tokio::select! {
+            msg = reader.recv() => match msg {
+                // components may block on the EC, keep them off the runtime's workers
+                Ok(msg) => {
+                    let components = Arc::clone(&components);
+                    let clients = Arc::clone(&clients);
+                    let reply = tokio::task::spawn_blocking(move || {
+                        handle_msg(&components, &clients, connection, &session, msg)
+                    })
+                    .await;
+                    match reply {
+                        Ok(Ok(reply)) => reply,
+                        Ok(Err(e)) => {
+                            println!("Connection {}: failed to handle message: {}", connection, e);
+                            break;
                         }
                         Err(e) => {
-                            println!("Error receiving message: {:?}", e);
-                            break; // Exit the inner loop if there's an error
+                            println!("Connection {}: message handler failed: {}", connection, e);
+                            break;
                         }
                     }
                 }
-            }
-        });
-        println!("Message handler started, waiting for connections...");
-        Ok(handle)
+                Err(e) => {
+                    println!("Connection {}: error receiving message: {}", connection, e);
+                    break;
+                }
+            },
+            push = outbox_receiver.recv() => match push {
+                Some(push) => push,
+                // the monitor dropped the client
+                None => break,
+            },
+        };
+        if let Err(e) = send_msg(&mut writer, session.get_version(), &body).await {
+            println!("Connection {}: error sending message: {}", connection, e);
+            break;
+        }
+    }
+    // drops the subscription and the leases with it
+    clients.lock().unwrap().remove(&connection);
+    println!("Connection {} closed", connection);
+}
+
+fn handle_msg(
+    components: &Mutex<Components>,
+    clients: &Mutex<HashMap<u64, Client>>,
+    connection: u64,
+    session: &Session,
+    body: MsgBody,
+) -> Result<MsgBody> {
+    let mut packet = body.get_packet().clone();
+    packet.set_mode(MsgMode::Reply);
+    let id = packet.get_id_num();
+    // lock order is components, then clients
+    let mut hardwares = components.lock().unwrap();
+    let mut clients = clients.lock().unwrap();
+    if !clients.contains_key(&connection) {
+        return Err(ProtoError::Io(format!(
+            "connection {} was dropped",
+            connection
+        )));
     }
+    // a payload that doesn't decode is answered with InvalidCommand, values are converted
+    // to the units of this build for older peers
+    let request = Request::decode(packet.get_command(), body.get_payload())
+        .map(|request| request.from_version(session.get_version()));
+    let response = request.and_then(|request| {
+        match request {
+            Request::GetComponentList => {
+                let mut hardware_list = ComponentList(HashMap::new());
+                hardwares.iter().for_each(|(id, hardware)| {
+                    hardware_list.0.insert(*id, hardware.get_desc());
+                });
+                Ok(Response::ComponentList(hardware_list))
+            }
+            Request::Subscribe(_) if !session.supports(Features::SUBSCRIBE) => Err(
+                MsgError::UnsupportedOperation("subscribe was not negotiated".to_string()),
+            ),
+            Request::Subscribe(subscription) => {
+                // a read only peer is told it may not control, whatever else is wrong
+                if let Some(topic) = subscription
+                    .get_topics()
+                    .iter()
+                    .find(|topic| topic.get_command().is_control())
+                    && !clients[&connection].may_control()
+                {
+                    return Err(MsgError::PermissionDenied(format!(
+                        "{} needs root or the control group",
+                        topic.get_command()
+                    )));
+                }
+                subscription.check_topics()?;
+                println!(
+                    "connection {} subscribed to {} topics",
+                    connection,
+                    subscription.get_topics().len()
+                );
+                clients
+                    .get_mut(&connection)
+                    .unwrap()
+                    .subscribe(subscription);
+                Ok(Response::Done)
+            }
+            Request::Unsubscribe => {
+                clients.get_mut(&connection).unwrap().unsubscribe();
+                Ok(Response::Done)
+            }
+            Request::GetCapabilities if !session.supports(Features::CAPABILITIES) => Err(
+                MsgError::UnsupportedOperation("capabilities were not negotiated".to_string()),
+            ),
+            Request::GetCapabilities => {
+                let hardware = hardwares.get(&id).ok_or(MsgError::UnknownComponent(id))?;
+                let mut capabilities = hardware.get_capabilities();
+                // commands of features the peer didn't negotiate wouldn't decode
+                capabilities.retain_commands(|command| session.knows_command(command));
+                Ok(Response::Capabilities(capabilities))
+            }
+            request => {
+                if request.is_control() && !clients[&connection].may_control() {
+                    return Err(MsgError::PermissionDenied(format!(
+                        "{} needs root or the control group",
+                        packet.get_command()
+                    )));
+                }
+                // a control request needs the component free or leased to this connection
+                if request.is_control()
+                    && let Some((holder, _)) = clients
+                        .iter()
+                        .find(|(other, client)| **other != connection && client.holds(id))
+                {
+                    return Err(MsgError::Busy(format!(
+                        "component {} is controlled by connection {}",
+                        id, holder
+                    )));
+                }
+                let hardware = hardwares
+                    .get_mut(&id)
+                    .ok_or(MsgError::UnknownComponent(id))?;
+                let response = hardware.handle_request(&request)?;
+                let client = clients.get_mut(&connection).unwrap();
+                match request {
+                    // handing the component back to its defaults ends the lease
+                    Request::SetFanAuto(FanIndex::All) | Request::ResetGpuLimits => {
+                        client.release_lease(id)
+                    }
+                    request if request.is_control() => client.take_lease(id),
+                    _ => {}
+                }
+                Ok(response)
+            }
+        }
+    });
+    let payload = match response {
+        Ok(response) => response.encode_payload()?,
+        Err(e) => {
+            packet.set_error(session.compatible_error(e));
+            vec![]
+        }
+    };
+    Ok(MsgBody::new(packet, payload))
 }
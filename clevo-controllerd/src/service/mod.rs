@@ -1 +1,3 @@
+pub mod activation;
+pub mod client;
 pub mod core;
@@ -0,0 +1,52 @@
+//! Every field decoder on arbitrary bytes, the first byte picks the decoder.
+
+#![no_main]
+
+use lib::field::{
+    ComponentList, CpuStatus, GpuStatus, MemStatus, PowerSupplyStatus, StorageStatus,
+    capability::Capabilities,
+    cores::{CoreState, TargetCoreMask},
+    event::Event,
+    fan_speed::{FanIndex, FanSpeed, TargetFanSpeed},
+    freq::{Freq, TargetFreq},
+    limit::GpuLimits,
+    net_speed::NetSpeed,
+    power::{Power, TargetPower},
+    subscription::Subscription,
+    temp::Temp,
+    throttle::Throttle,
+    usage::Usage,
+};
+use libfuzzer_sys::fuzz_target;
+
+const DECODERS: &[fn(&[u8])] = &[
+    |data| drop(ComponentList::deserialize(data)),
+    |data| drop(CpuStatus::deserialize(data)),
+    |data| drop(GpuStatus::deserialize(data)),
+    |data| drop(MemStatus::deserialize(data)),
+    |data| drop(StorageStatus::deserialize(data)),
+    |data| drop(PowerSupplyStatus::deserialize(data)),
+    |data| drop(CoreState::deserialize(data)),
+    |data| drop(TargetCoreMask::deserialize(data)),
+    |data| drop(Event::deserialize(data)),
+    |data| drop(FanIndex::deserialize(data)),
+    |data| drop(FanSpeed::deserialize(data)),
+    |data| drop(TargetFanSpeed::deserialize(data)),
+    |data| drop(Freq::try_from(data)),
+    |data| drop(TargetFreq::deserialize(data)),
+    |data| drop(GpuLimits::deserialize(data)),
+    |data| drop(NetSpeed::deserialize(data)),
+    |data| drop(Power::try_from(data)),
+    |data| drop(TargetPower::deserialize(data)),
+    |data| drop(Subscription::deserialize(data)),
+    |data| drop(Temp::try_from(data)),
+    |data| drop(Throttle::try_from(data)),
+    |data| drop(Usage::try_from(data)),
+    |data| drop(Capabilities::deserialize(data)),
+];
+
+fuzz_target!(|data: &[u8]| {
+    if let Some((selector, data)) = data.split_first() {
+        DECODERS[*selector as usize % DECODERS.len()](data);
+    }
+});
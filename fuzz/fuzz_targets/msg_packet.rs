@@ -0,0 +1,25 @@
+//! A packet and the payloads it announces from arbitrary bytes, the first byte is the length
+//! of the packet, the payloads are cut from the rest by the lengths the packet gives.
+
+#![no_main]
+
+use lib::proto::{MsgPacket, Request};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Some((packet_length, data)) = data.split_first() else {
+        return;
+    };
+    let (packet, mut rest) = data.split_at((*packet_length as usize).min(data.len()));
+    let Ok(packet) = MsgPacket::deserialize(packet) else {
+        return;
+    };
+    // a payload the input runs out for is cut short, the decode must still fail cleanly
+    let mut payload = vec![];
+    for length in packet.get_payload_length() {
+        let (bytes, next) = rest.split_at((*length as usize).min(rest.len()));
+        payload.push(bytes.to_vec());
+        rest = next;
+    }
+    let _ = Request::decode(packet.get_command(), &payload);
+});
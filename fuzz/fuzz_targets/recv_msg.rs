@@ -0,0 +1,28 @@
+//! Reads messages from arbitrary bytes until one fails, nothing may panic or allocate more
+//! than the limits allow.
+
+#![no_main]
+
+use lib::{
+    proto::recv_msg,
+    stream::{StreamError, StreamRead},
+};
+use libfuzzer_sys::fuzz_target;
+
+struct Bytes<'a>(&'a [u8]);
+
+impl StreamRead for Bytes<'_> {
+    fn read(&mut self, length: usize) -> Result<Vec<u8>, StreamError> {
+        if length > self.0.len() {
+            return Err(StreamError::Other("end of input".to_string()));
+        }
+        let (read, rest) = self.0.split_at(length);
+        self.0 = rest;
+        Ok(read.to_vec())
+    }
+}
+
+fuzz_target!(|data: &[u8]| {
+    let mut stream = Bytes(data);
+    while recv_msg(&mut stream).is_ok() {}
+});
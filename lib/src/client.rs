@@ -0,0 +1,229 @@
+//! Blocking client for tools that talk to the daemon.
+//!
+//! Every method sends one request and waits for its reply, so a tool doesn't need to build
+//! packets or keep track of sequence numbers.
+
+use crate::{
+    field::{
+        ComponentList, CpuStatus, GpuStatus,
+        capability::Capabilities,
+        category::Category,
+        fan_speed::{FanIndex, FanSpeed, FanValues, TargetFanSpeed},
+    },
+    handshake::{SUPPORTED_FEATURES, Session, client_handshake},
+    proto::*,
+    stream::SocketStream,
+};
+
+/// Socket the daemon listens on unless SOCKET_NAME says otherwise
+pub const DEFAULT_SOCKET_NAME: &str = "clevo-controler.sock";
+
+#[derive(Debug, thiserror::Error)]
+pub enum ClientError {
+    #[error("Protocol Error: {0}")]
+    ProtoError(#[from] ProtoError),
+    #[error("Unsupported operation: {0}")]
+    Unsupported(String), // The component can't do this request
+    #[error("Invalid argument: {0}")]
+    InvalidArgument(String), // e.g. a duty above 100%
+    #[error("Device error: {0}")]
+    DeviceError(String),
+    #[error("Timeout: {0}")]
+    Timeout(String), // The hardware didn't answer, trying again may work
+    #[error("Busy: {0}")]
+    Busy(String), // Another connection controls the component
+    #[error("Permission denied: {0}")]
+    PermissionDenied(String), // This user may not change the hardware
+    #[error("Access denied: {0}")]
+    AccessDenied(String), // The daemon may not open the device
+    #[error("Daemon Error: {0}")]
+    DaemonError(MsgError), // Any other failure of the daemon
+    #[error("No component {0}")]
+    NoComponent(u8),
+    #[error("No {0:?} component")]
+    NoCategory(Category), // e.g. a fan request on a machine without one
+    #[error("Unexpected reply to {0}")]
+    UnexpectedReply(MsgCommand), // The reply doesn't fit the request
+}
+
+impl From<MsgError> for ClientError {
+    fn from(err: MsgError) -> Self {
+        match err {
+            MsgError::UnsupportedOperation(reason) => ClientError::Unsupported(reason),
+            MsgError::InvalidCommand(reason) => ClientError::InvalidArgument(reason),
+            MsgError::DeviceError(reason) => ClientError::DeviceError(reason),
+            MsgError::Timeout(reason) => ClientError::Timeout(reason),
+            MsgError::Busy(reason) => ClientError::Busy(reason),
+            MsgError::PermissionDenied(reason) => ClientError::PermissionDenied(reason),
+            MsgError::AccessDenied(reason) => ClientError::AccessDenied(reason),
+            MsgError::UnknownComponent(id) => ClientError::NoComponent(id),
+            err => ClientError::DaemonError(err),
+        }
+    }
+}
+
+impl ClientError {
+    /// Code of the error the daemon replied with, None for connection and protocol errors.
+    /// An id missing from the component list has the daemon's code for it
+    pub fn get_code(&self) -> Option<ErrorCode> {
+        match self {
+            ClientError::Unsupported(_) => Some(ErrorCode::UnsupportedOperation),
+            ClientError::InvalidArgument(_) => Some(ErrorCode::InvalidCommand),
+            ClientError::DeviceError(_) => Some(ErrorCode::DeviceError),
+            ClientError::Timeout(_) => Some(ErrorCode::Timeout),
+            ClientError::Busy(_) => Some(ErrorCode::Busy),
+            ClientError::PermissionDenied(_) => Some(ErrorCode::PermissionDenied),
+            ClientError::AccessDenied(_) => Some(ErrorCode::AccessDenied),
+            ClientError::DaemonError(err) => Some(err.get_code()),
+            ClientError::NoComponent(_) => Some(ErrorCode::UnknownComponent),
+            _ => None,
+        }
+    }
+}
+
+type Result<T> = std::result::Result<T, ClientError>;
+
+pub struct Client {
+    stream: SocketStream,
+    session: Session,
+    sequence: u64,             // Of the last request
+    components: ComponentList, // Read when connecting, the daemon doesn't change it
+}
+
+impl Client {
+    /// Connect to the socket in SOCKET_NAME, or to `DEFAULT_SOCKET_NAME`
+    pub fn connect() -> Result<Self> {
+        let socket_name =
+            std::env::var("SOCKET_NAME").unwrap_or_else(|_| DEFAULT_SOCKET_NAME.to_string());
+        Self::connect_to(&socket_name)
+    }
+
+    pub fn connect_to(socket_name: &str) -> Result<Self> {
+        let mut stream = SocketStream::new(socket_name).map_err(ProtoError::from)?;
+        let session = client_handshake(&mut stream, SUPPORTED_FEATURES)?;
+        let mut client = Self {
+            stream,
+            session,
+            sequence: 0,
+            components: ComponentList(Default::default()),
+        };
+        client.components = match client.request(0, &Request::GetComponentList)? {
+            Response::ComponentList(components) => components,
+            _ => return Err(ClientError::UnexpectedReply(MsgCommand::GetComponentList)),
+        };
+        Ok(client)
+    }
+
+    pub fn get_session(&self) -> &Session {
+        &self.session
+    }
+
+    /// The components of the daemon by id
+    pub fn components(&self) -> &ComponentList {
+        &self.components
+    }
+
+    /// Send a request to the component `id_num` and wait for its reply
+    pub fn request(&mut self, id_num: u8, request: &Request) -> Result<Response> {
+        self.sequence += 1;
+        let version = self.session.get_version();
+        let mut body = MsgBody::request(id_num, &request.clone().for_version(version))?;
+        body.set_sequence(self.sequence);
+        send_msg(&mut self.stream, version, &body)?;
+        // only pushes for a subscription could come in between, this client doesn't subscribe
+        let reply = loop {
+            let reply = recv_msg(&mut self.stream)?;
+            if matches!(reply.get_packet().get_mode(), MsgMode::Reply)
+                && reply.get_packet().get_sequence() == self.sequence
+            {
+                break reply;
+            }
+        };
+        if let Some(error) = reply.get_packet().get_error() {
+            return Err(error.clone().into());
+        }
+        // the component list is read before the categories are known, it has none
+        let category = match request {
+            Request::GetComponentList => Category::default(),
+            _ => self.get_category(id_num)?,
+        };
+        Ok(Response::decode(
+            reply.get_packet().get_command(),
+            &category,
+            reply.get_payload(),
+        )?)
+    }
+
+    pub fn status(&mut self, id_num: u8) -> Result<Status> {
+        match self.request(id_num, &Request::GetStatus)? {
+            Response::Status(status) => Ok(status),
+            _ => Err(ClientError::UnexpectedReply(MsgCommand::GetStatus)),
+        }
+    }
+
+    pub fn cpu_status(&mut self, id_num: u8) -> Result<CpuStatus> {
+        match self.status(id_num)? {
+            Status::Cpu(status) => Ok(status),
+            _ => Err(ClientError::UnexpectedReply(MsgCommand::GetStatus)),
+        }
+    }
+
+    pub fn gpu_status(&mut self, id_num: u8) -> Result<GpuStatus> {
+        match self.status(id_num)? {
+            Status::Gpu(status) => Ok(status),
+            _ => Err(ClientError::UnexpectedReply(MsgCommand::GetStatus)),
+        }
+    }
+
+    /// Commands and settable ranges of the component `id_num`, needs a daemon with the
+    /// CAPABILITIES feature
+    pub fn capabilities(&mut self, id_num: u8) -> Result<Capabilities> {
+        match self.request(id_num, &Request::GetCapabilities)? {
+            Response::Capabilities(capabilities) => Ok(capabilities),
+            _ => Err(ClientError::UnexpectedReply(MsgCommand::GetCapabilities)),
+        }
+    }
+
+    /// Speed of both fans
+    pub fn fan_speeds(&mut self) -> Result<FanValues<FanSpeed>> {
+        let fan = self.find(Category::Fan)?;
+        match self.request(fan, &Request::GetFanSpeed(FanIndex::All))? {
+            Response::FanSpeed(speeds) => Ok(speeds),
+            _ => Err(ClientError::UnexpectedReply(MsgCommand::GetFanSpeed)),
+        }
+    }
+
+    /// Set the duty of a fan, or of both with `FanIndex::All`, in percent
+    pub fn set_fan_duty(&mut self, fan: FanIndex, duty: u32) -> Result<()> {
+        let id_num = self.find(Category::Fan)?;
+        let duty = FanValues::with_index(fan, TargetFanSpeed::new(duty));
+        self.request(id_num, &Request::SetFanSpeed(duty))?;
+        Ok(())
+    }
+
+    /// Give a fan, or both with `FanIndex::All`, back to the EC
+    pub fn set_fan_auto(&mut self, fan: FanIndex) -> Result<()> {
+        let id_num = self.find(Category::Fan)?;
+        self.request(id_num, &Request::SetFanAuto(fan))?;
+        Ok(())
+    }
+
+    fn get_category(&self, id_num: u8) -> Result<Category> {
+        self.components
+            .0
+            .get(&id_num)
+            .map(|desc| desc.get_category().clone())
+            .ok_or(ClientError::NoComponent(id_num))
+    }
+
+    // Id of the first component of the category
+    fn find(&self, category: Category) -> Result<u8> {
+        self.components
+            .0
+            .iter()
+            .filter(|(_, desc)| *desc.get_category() == category)
+            .map(|(id, _)| *id)
+            .min()
+            .ok_or(ClientError::NoCategory(category))
+    }
+}
@@ -0,0 +1,118 @@
+use crate::{
+    field::{
+        FieldError,
+        fan_speed::FanIndex,
+        unit::{Megahertz, Milliwatts},
+    },
+    proto::MsgCommand,
+};
+use bincode::{Decode, Encode};
+
+type Result<T> = std::result::Result<T, FieldError>;
+
+/// Inclusive range of values a setting accepts
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+pub struct Range<T> {
+    min: T,
+    max: T,
+}
+
+impl<T: Copy + PartialOrd> Range<T> {
+    pub fn new(min: T, max: T) -> Self {
+        Self { min, max }
+    }
+    pub fn get_min(&self) -> T {
+        self.min
+    }
+    pub fn get_max(&self) -> T {
+        self.max
+    }
+    pub fn contains(&self, value: T) -> bool {
+        self.min <= value && value <= self.max
+    }
+}
+
+/// What a component can do on this machine, so a client only offers what works.
+/// A range is None when the command it belongs to isn't supported
+#[derive(Debug, Default, Clone, Encode, Decode)]
+pub struct Capabilities {
+    commands: Vec<MsgCommand>,              // Requests the component handles
+    fans: Vec<FanIndex>,                    // Fans SetFanSpeed and SetFanAuto accept, without All
+    duty: Option<Range<u32>>,               // SetFanSpeed, in percent
+    freq: Option<Range<Megahertz>>,         // SetFreq
+    mem_freq: Option<Range<Megahertz>>,     // SetMemFreq
+    power_limit: Option<Range<Milliwatts>>, // SetPowerLimit
+    governors: Vec<String>,                 // cpufreq scaling governors the kernel offers
+    power_modes: Vec<String>,               // ACPI platform profiles the firmware offers
+}
+
+impl Capabilities {
+    pub fn new(commands: Vec<MsgCommand>) -> Self {
+        Self {
+            commands,
+            ..Default::default()
+        }
+    }
+
+    pub fn get_commands(&self) -> &Vec<MsgCommand> {
+        &self.commands
+    }
+    pub fn supports(&self, command: &MsgCommand) -> bool {
+        self.commands.contains(command)
+    }
+    pub fn get_fans(&self) -> &Vec<FanIndex> {
+        &self.fans
+    }
+    pub fn get_duty(&self) -> Option<&Range<u32>> {
+        self.duty.as_ref()
+    }
+    pub fn get_freq(&self) -> Option<&Range<Megahertz>> {
+        self.freq.as_ref()
+    }
+    pub fn get_mem_freq(&self) -> Option<&Range<Megahertz>> {
+        self.mem_freq.as_ref()
+    }
+    pub fn get_power_limit(&self) -> Option<&Range<Milliwatts>> {
+        self.power_limit.as_ref()
+    }
+    pub fn get_governors(&self) -> &Vec<String> {
+        &self.governors
+    }
+    pub fn get_power_modes(&self) -> &Vec<String> {
+        &self.power_modes
+    }
+
+    /// Keep only the commands for which `keep` is true, e.g. the ones a peer can decode
+    pub fn retain_commands(&mut self, keep: impl Fn(&MsgCommand) -> bool) {
+        self.commands.retain(keep);
+    }
+    pub fn set_fans(&mut self, fans: Vec<FanIndex>) {
+        self.fans = fans;
+    }
+    pub fn set_duty(&mut self, duty: Range<u32>) {
+        self.duty = Some(duty);
+    }
+    pub fn set_freq(&mut self, freq: Range<Megahertz>) {
+        self.freq = Some(freq);
+    }
+    pub fn set_mem_freq(&mut self, mem_freq: Range<Megahertz>) {
+        self.mem_freq = Some(mem_freq);
+    }
+    pub fn set_power_limit(&mut self, power_limit: Range<Milliwatts>) {
+        self.power_limit = Some(power_limit);
+    }
+    pub fn set_governors(&mut self, governors: Vec<String>) {
+        self.governors = governors;
+    }
+    pub fn set_power_modes(&mut self, power_modes: Vec<String>) {
+        self.power_modes = power_modes;
+    }
+
+    pub fn serialize(&self) -> Result<Vec<u8>> {
+        Ok(bincode::encode_to_vec(self, bincode::config::standard())?)
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<Self> {
+        Ok(bincode::decode_from_slice(data, crate::field::DECODE_CONFIG)?.0)
+    }
+}
@@ -1,9 +1,13 @@
 use bincode::{Decode, Encode};
 
-#[derive(Debug, Default, Clone, Encode, Decode)]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Encode, Decode)]
 pub enum Category {
     #[default]
     Cpu = 1,
     Gpu,
     Fan,
+    Network,
+    Memory,
+    Storage,
+    PowerSupply,
 }
@@ -0,0 +1,53 @@
+use crate::field::FieldError;
+use bincode::{Decode, Encode};
+type Result<T> = std::result::Result<T, FieldError>;
+
+#[derive(Debug, Default, Clone, Encode, Decode)]
+pub struct CoreState {
+    online: Vec<bool>,       // Online state, indexed by cpu number
+    hotpluggable: Vec<bool>, // Whether the core can be taken offline
+}
+
+impl CoreState {
+    pub fn new(online: Vec<bool>, hotpluggable: Vec<bool>) -> Self {
+        Self {
+            online,
+            hotpluggable,
+        }
+    }
+    pub fn get_online(&self) -> &Vec<bool> {
+        &self.online
+    }
+    pub fn get_hotpluggable(&self) -> &Vec<bool> {
+        &self.hotpluggable
+    }
+    pub fn online_count(&self) -> usize {
+        self.online.iter().filter(|online| **online).count()
+    }
+    pub fn serialize(&self) -> Result<Vec<u8>> {
+        Ok(bincode::encode_to_vec(self, bincode::config::standard())?)
+    }
+    pub fn deserialize(data: &[u8]) -> Result<Self> {
+        Ok(bincode::decode_from_slice(data, crate::field::DECODE_CONFIG)?.0)
+    }
+}
+
+#[derive(Debug, Default, Clone, Encode, Decode)]
+pub struct TargetCoreMask {
+    mask: Vec<bool>, // true to bring the core online, cores past the end are left unchanged
+}
+
+impl TargetCoreMask {
+    pub fn new(mask: Vec<bool>) -> Self {
+        Self { mask }
+    }
+    pub fn get_mask(&self) -> &Vec<bool> {
+        &self.mask
+    }
+    pub fn serialize(&self) -> Result<Vec<u8>> {
+        Ok(bincode::encode_to_vec(self, bincode::config::standard())?)
+    }
+    pub fn deserialize(data: &[u8]) -> Result<Self> {
+        Ok(bincode::decode_from_slice(data, crate::field::DECODE_CONFIG)?.0)
+    }
+}
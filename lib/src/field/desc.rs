@@ -1,11 +1,52 @@
 use super::category::Category;
 use bincode::{Decode, Encode};
 
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct DeviceInfo {
+    vendor: String,
+    pci_id: String,    // PCI address as in sysfs, e.g. "0000:01:00.0"
+    vram: Option<u64>, // Dedicated video memory in bytes, None if shared with the system
+}
+
+impl DeviceInfo {
+    pub fn new(vendor: &str, pci_id: &str, vram: Option<u64>) -> Self {
+        Self {
+            vendor: vendor.to_string(),
+            pci_id: pci_id.to_string(),
+            vram,
+        }
+    }
+
+    pub fn get_vendor(&self) -> &str {
+        &self.vendor
+    }
+
+    pub fn get_pci_id(&self) -> &str {
+        &self.pci_id
+    }
+
+    pub fn get_vram(&self) -> Option<u64> {
+        self.vram
+    }
+
+    // Integrated devices share the system memory. Intel reports no dedicated memory for any
+    // of its gpus, its integrated one is the one on the root bus (discrete ones sit behind a
+    // PCIe port), while an AMD APU can sit on any bus
+    pub fn is_integrated(&self) -> bool {
+        match self.vendor.as_str() {
+            "NVIDIA" => false,
+            "Intel" => self.pci_id.split(':').nth(1) == Some("00"),
+            _ => self.vram.is_none(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Encode, Decode)]
 pub struct Desc {
     category: Category,
     index: u8,
     name: String,
+    device_info: Option<DeviceInfo>,
 }
 
 impl Desc {
@@ -14,6 +55,7 @@ impl Desc {
             category,
             index,
             name: name.to_string(),
+            device_info: None,
         }
     }
 
@@ -28,4 +70,12 @@ impl Desc {
     pub fn get_desc(&self) -> &str {
         &self.name
     }
+
+    pub fn get_device_info(&self) -> Option<&DeviceInfo> {
+        self.device_info.as_ref()
+    }
+
+    pub fn set_device_info(&mut self, device_info: DeviceInfo) {
+        self.device_info = Some(device_info);
+    }
 }
@@ -0,0 +1,44 @@
+use crate::field::{
+    FieldError,
+    fan_speed::{FanIndex, FanMode},
+    power_supply::PowerSource,
+    temp::Temp,
+};
+use bincode::{Decode, Encode};
+use std::fmt::Display;
+
+type Result<T> = std::result::Result<T, FieldError>;
+
+/// Something that happened on a component between two refreshes
+#[derive(Debug, Clone, Encode, Decode)]
+pub enum Event {
+    PowerSourceChanged { from: PowerSource, to: PowerSource },
+    FanModeChanged { fan: FanIndex, mode: FanMode },
+    ThermalAlert { source: String, temp: Temp }, // Something started throttling on heat
+}
+
+impl Event {
+    pub fn serialize(&self) -> Result<Vec<u8>> {
+        Ok(bincode::encode_to_vec(self, bincode::config::standard())?)
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<Self> {
+        Ok(bincode::decode_from_slice(data, crate::field::DECODE_CONFIG)?.0)
+    }
+}
+
+impl Display for Event {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Event::PowerSourceChanged { from, to } => {
+                write!(f, "power source changed from {} to {}", from, to)
+            }
+            Event::FanModeChanged { fan, mode } => {
+                write!(f, "{:?} fan switched to {:?} mode", fan, mode)
+            }
+            Event::ThermalAlert { source, temp } => {
+                write!(f, "thermal alert on {} at {}", source, temp.get_value())
+            }
+        }
+    }
+}
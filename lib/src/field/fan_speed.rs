@@ -25,7 +25,7 @@ impl FanSpeed {
     }
 
     pub fn deserialize(payload: &[u8]) -> Result<Self> {
-        let (value, _) = bincode::decode_from_slice(payload, bincode::config::standard())?;
+        let (value, _) = bincode::decode_from_slice(payload, crate::field::DECODE_CONFIG)?;
         Ok(value)
     }
 }
@@ -33,12 +33,12 @@ impl FanSpeed {
 impl TryFrom<&[u8]> for FanSpeed {
     type Error = FieldError;
     fn try_from(value: &[u8]) -> Result<Self> {
-        let (value, _) = bincode::decode_from_slice(value, bincode::config::standard())?;
+        let (value, _) = bincode::decode_from_slice(value, crate::field::DECODE_CONFIG)?;
         Ok(value)
     }
 }
 
-#[derive(Debug, Default, Clone, Encode, Decode)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Encode, Decode)]
 pub enum FanIndex {
     #[default]
     All = 0,
@@ -53,7 +53,7 @@ impl FanIndex {
     }
 
     pub fn deserialize(payload: &[u8]) -> Result<Self> {
-        let (value, _) = bincode::decode_from_slice(payload, bincode::config::standard())?;
+        let (value, _) = bincode::decode_from_slice(payload, crate::field::DECODE_CONFIG)?;
         Ok(value)
     }
 }
@@ -76,7 +76,62 @@ impl TargetFanSpeed {
     }
 
     pub fn deserialize(payload: &[u8]) -> Result<Self> {
-        let (value, _) = bincode::decode_from_slice(payload, bincode::config::standard())?;
+        let (value, _) = bincode::decode_from_slice(payload, crate::field::DECODE_CONFIG)?;
         Ok(value)
     }
 }
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+pub enum FanMode {
+    #[default]
+    Auto, // Controlled by the EC
+    Manual, // Duty set by the client
+}
+
+/// A value for one fan or for both, e.g. the duty to set or the speed read back
+#[derive(Debug, Clone)]
+pub enum FanValues<T> {
+    Cpu(T),
+    Gpu(T),
+    All { cpu: T, gpu: T },
+}
+
+impl<T: Clone> FanValues<T> {
+    /// The same value for the fan, or for both with FanIndex::All
+    pub fn with_index(index: FanIndex, value: T) -> Self {
+        match index {
+            FanIndex::Cpu => FanValues::Cpu(value),
+            FanIndex::Gpu => FanValues::Gpu(value),
+            FanIndex::All => FanValues::All {
+                cpu: value.clone(),
+                gpu: value,
+            },
+        }
+    }
+}
+
+impl<T> FanValues<T> {
+    pub fn get_index(&self) -> FanIndex {
+        match self {
+            FanValues::Cpu(_) => FanIndex::Cpu,
+            FanValues::Gpu(_) => FanIndex::Gpu,
+            FanValues::All { .. } => FanIndex::All,
+        }
+    }
+    pub fn get_cpu(&self) -> Option<&T> {
+        match self {
+            FanValues::Cpu(cpu) | FanValues::All { cpu, .. } => Some(cpu),
+            FanValues::Gpu(_) => None,
+        }
+    }
+    pub fn get_gpu(&self) -> Option<&T> {
+        match self {
+            FanValues::Gpu(gpu) | FanValues::All { gpu, .. } => Some(gpu),
+            FanValues::Cpu(_) => None,
+        }
+    }
+    // In wire order, cpu before gpu
+    pub fn values(&self) -> Vec<&T> {
+        self.get_cpu().into_iter().chain(self.get_gpu()).collect()
+    }
+}
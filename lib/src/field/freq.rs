@@ -1,21 +1,21 @@
-use crate::field::FieldError;
+use crate::field::{FieldError, unit::Megahertz};
 use bincode::{Decode, Encode};
 
 type Result<T> = std::result::Result<T, FieldError>;
 
 #[derive(Debug, Default, Clone, Encode, Decode)]
 pub struct Freq {
-    value: Vec<u64>, // Frequency in MHz
+    value: Vec<Option<Megahertz>>, // None for an offline core
 }
 
 impl Freq {
-    pub fn new(value: Vec<u64>) -> Self {
+    pub fn new(value: Vec<Option<Megahertz>>) -> Self {
         Self { value }
     }
-    pub fn get_value(&self) -> &Vec<u64> {
+    pub fn get_value(&self) -> &Vec<Option<Megahertz>> {
         &self.value
     }
-    pub fn set_value(&mut self, value: Vec<u64>) {
+    pub fn set_value(&mut self, value: Vec<Option<Megahertz>>) {
         self.value = value;
     }
 }
@@ -23,25 +23,25 @@ impl Freq {
 impl TryFrom<&[u8]> for Freq {
     type Error = FieldError;
     fn try_from(value: &[u8]) -> Result<Self> {
-        let (value, _) = bincode::decode_from_slice(value, bincode::config::standard())?;
+        let (value, _) = bincode::decode_from_slice(value, crate::field::DECODE_CONFIG)?;
         Ok(value)
     }
 }
 
 #[derive(Debug, Default, Clone, Encode, Decode)]
 pub struct TargetFreq {
-    min: u32,
-    max: u32,
+    min: Megahertz,
+    max: Megahertz,
 }
 
 impl TargetFreq {
-    pub fn new(min: u32, max: u32) -> Self {
+    pub fn new(min: Megahertz, max: Megahertz) -> Self {
         Self { min, max }
     }
-    pub fn get_min(&self) -> u32 {
+    pub fn get_min(&self) -> Megahertz {
         self.min
     }
-    pub fn get_max(&self) -> u32 {
+    pub fn get_max(&self) -> Megahertz {
         self.max
     }
 
@@ -50,6 +50,6 @@ impl TargetFreq {
     }
 
     pub fn deserialize(data: &[u8]) -> Result<Self> {
-        Ok(bincode::decode_from_slice(data, bincode::config::standard())?.0)
+        Ok(bincode::decode_from_slice(data, crate::field::DECODE_CONFIG)?.0)
     }
 }
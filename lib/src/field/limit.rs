@@ -0,0 +1,59 @@
+use crate::field::{FieldError, freq::TargetFreq, power::Power};
+use bincode::{Decode, Encode};
+type Result<T> = std::result::Result<T, FieldError>;
+
+#[derive(Debug, Default, Clone, Encode, Decode)]
+pub struct GpuLimits {
+    power_limit: Power,            // Enforced power limit in mW
+    min_power_limit: Power,        // Lowest power limit the driver accepts
+    max_power_limit: Power,        // Highest power limit the driver accepts
+    default_power_limit: Power,    // Power limit after a reset
+    gpu_clock: Option<TargetFreq>, // Locked graphics clock range, None if not locked
+    mem_clock: Option<TargetFreq>, // Locked memory clock range, None if not locked
+}
+
+impl GpuLimits {
+    pub fn new(
+        power_limit: Power,
+        min_power_limit: Power,
+        max_power_limit: Power,
+        default_power_limit: Power,
+        gpu_clock: Option<TargetFreq>,
+        mem_clock: Option<TargetFreq>,
+    ) -> Self {
+        Self {
+            power_limit,
+            min_power_limit,
+            max_power_limit,
+            default_power_limit,
+            gpu_clock,
+            mem_clock,
+        }
+    }
+    pub fn get_power_limit(&self) -> &Power {
+        &self.power_limit
+    }
+    pub fn get_min_power_limit(&self) -> &Power {
+        &self.min_power_limit
+    }
+    pub fn get_max_power_limit(&self) -> &Power {
+        &self.max_power_limit
+    }
+    pub fn get_default_power_limit(&self) -> &Power {
+        &self.default_power_limit
+    }
+    pub fn get_gpu_clock(&self) -> Option<&TargetFreq> {
+        self.gpu_clock.as_ref()
+    }
+    pub fn get_mem_clock(&self) -> Option<&TargetFreq> {
+        self.mem_clock.as_ref()
+    }
+
+    pub fn serialize(&self) -> Result<Vec<u8>> {
+        Ok(bincode::encode_to_vec(self, bincode::config::standard())?)
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<Self> {
+        Ok(bincode::decode_from_slice(data, crate::field::DECODE_CONFIG)?.0)
+    }
+}
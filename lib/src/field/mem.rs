@@ -0,0 +1,93 @@
+use crate::field::FieldError;
+use bincode::{Decode, Encode};
+
+type Result<T> = std::result::Result<T, FieldError>;
+
+/// Pressure stall averages from /proc/pressure, percentage of time over 10s, 60s and 300s
+#[derive(Debug, Default, Clone, Encode, Decode)]
+pub struct Pressure {
+    some: [f32; 3],         // At least one task stalled
+    full: Option<[f32; 3]>, // Every task stalled, None for cpu on older kernels
+}
+
+impl Pressure {
+    pub fn new(some: [f32; 3], full: Option<[f32; 3]>) -> Self {
+        Self { some, full }
+    }
+    pub fn get_some(&self) -> &[f32; 3] {
+        &self.some
+    }
+    pub fn get_full(&self) -> Option<&[f32; 3]> {
+        self.full.as_ref()
+    }
+
+    // Parse "some avg10=0.00 avg60=0.00 avg300=0.00 total=0" and the optional "full" line
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut some = None;
+        let mut full = None;
+        for line in content.lines() {
+            let mut parts = line.split_whitespace();
+            let kind = parts.next();
+            let mut avgs = [0.0; 3];
+            for (avg, part) in avgs.iter_mut().zip(parts) {
+                *avg = part
+                    .split_once('=')
+                    .and_then(|(_, value)| value.parse().ok())
+                    .ok_or(FieldError::ParseError(format!(
+                        "bad pressure line: {}",
+                        line
+                    )))?;
+            }
+            match kind {
+                Some("some") => some = Some(avgs),
+                Some("full") => full = Some(avgs),
+                _ => {}
+            }
+        }
+        Ok(Self {
+            some: some.ok_or(FieldError::ParseError("missing some line".to_string()))?,
+            full,
+        })
+    }
+}
+
+#[derive(Debug, Default, Clone, Encode, Decode)]
+pub struct MemUsage {
+    total: u64,      // Bytes
+    used: u64,       // Total minus available
+    available: u64,  // Bytes that can be allocated without swapping
+    cached: u64,     // Page cache and reclaimable slab
+    swap_total: u64, // Bytes
+    swap_used: u64,  // Bytes
+}
+
+impl MemUsage {
+    pub fn new(total: u64, available: u64, cached: u64, swap_total: u64, swap_free: u64) -> Self {
+        Self {
+            total,
+            used: total.saturating_sub(available),
+            available,
+            cached,
+            swap_total,
+            swap_used: swap_total.saturating_sub(swap_free),
+        }
+    }
+    pub fn get_total(&self) -> u64 {
+        self.total
+    }
+    pub fn get_used(&self) -> u64 {
+        self.used
+    }
+    pub fn get_available(&self) -> u64 {
+        self.available
+    }
+    pub fn get_cached(&self) -> u64 {
+        self.cached
+    }
+    pub fn get_swap_total(&self) -> u64 {
+        self.swap_total
+    }
+    pub fn get_swap_used(&self) -> u64 {
+        self.swap_used
+    }
+}
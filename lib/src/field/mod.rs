@@ -1,13 +1,58 @@
+//! Status and command payloads exchanged between the daemon and the client.
+//!
+//! # Compatibility policy
+//!
+//! Payloads are bincode encoded, which is not self-describing: the peer can only decode a
+//! type if it has exactly the same layout. Changes to the types in this module (and to
+//! `proto::MsgPacket`) therefore follow these rules:
+//!
+//! - Adding, removing, reordering or retyping a struct field is a breaking change. Bump
+//!   `proto::PROTO_VERSION`, and raise `proto::MIN_PROTO_VERSION` as well unless the old
+//!   layout is still encoded for peers that negotiated it.
+//! - Every value is in the unit `unit` gives its quantity. Changing the unit of a field is a
+//!   breaking change like a layout change, the conversion for older peers lives in `proto`.
+//! - Optional data goes into a new `Option` field at the end of a struct, which is still a
+//!   layout change and still needs the version bump.
+//! - New enum variants and new commands are appended at the end, so existing variants keep
+//!   their index. Peers that predate them can't decode them, so anything a peer would not
+//!   expect is gated behind a `handshake::Features` flag and only sent when the handshake
+//!   agreed on it.
+//! - `proto::MsgHeader`, the layout of `proto::MsgPacket` and `handshake::Hello`/`Welcome`
+//!   and the position of `MsgCommand::Hello` never change, they are how two versions find
+//!   out they don't match.
+
+pub mod capability;
 pub mod category;
+pub mod cores;
 pub mod desc;
+pub mod event;
 pub mod fan_speed;
 pub mod freq;
+pub mod limit;
+pub mod mem;
+pub mod net_speed;
 pub mod power;
+pub mod power_supply;
+pub mod storage;
+pub mod subscription;
 pub mod temp;
+pub mod throttle;
+pub mod unit;
 pub mod usage;
-use bincode::{Decode, Encode};
+use bincode::{
+    Decode, Encode,
+    config::{Configuration, Limit, LittleEndian, Varint},
+};
 use desc::Desc;
 use std::collections::HashMap;
+
+/// Most memory one decode may claim for its containers, a length read from malformed data
+/// fails the decode instead of being allocated
+pub const DECODE_LIMIT: usize = 1 << 20;
+
+// Every decode goes through this, encoding still uses `bincode::config::standard()`
+pub(crate) const DECODE_CONFIG: Configuration<LittleEndian, Varint, Limit<DECODE_LIMIT>> =
+    bincode::config::standard().with_limit::<DECODE_LIMIT>();
 use std::fmt::Display;
 
 #[derive(Debug, Clone)]
@@ -54,6 +99,8 @@ pub struct CpuStatus {
     pub usage: usage::Usage,
     pub power: power::Power,
     pub temp: temp::Temp,
+    pub throttle: throttle::Throttle,
+    pub cores: cores::CoreState,
 }
 
 impl CpuStatus {
@@ -61,17 +108,20 @@ impl CpuStatus {
         Ok(bincode::encode_to_vec(self, bincode::config::standard())?)
     }
     pub fn deserialize(data: &[u8]) -> Result<Self, FieldError> {
-        Ok(bincode::decode_from_slice(data, bincode::config::standard())?.0)
+        Ok(bincode::decode_from_slice(data, DECODE_CONFIG)?.0)
     }
 }
 
-#[derive(Debug, Clone, Encode, Decode)]
+#[derive(Debug, Default, Clone, Encode, Decode)]
 pub struct GpuStatus {
     pub freq: freq::Freq,
     pub power: power::Power,
     pub temp: temp::Temp,
     pub usage: usage::Usage,
     pub fan_speed: fan_speed::FanSpeed,
+    pub freq_limit: freq::TargetFreq, // Current min/max frequency setting
+    pub rc6_residency: Option<f32>,   // Percentage of time in rc6, None if not reported
+    pub power_state: power::PowerState,
 }
 
 impl GpuStatus {
@@ -79,26 +129,68 @@ impl GpuStatus {
         Ok(bincode::encode_to_vec(self, bincode::config::standard())?)
     }
     pub fn deserialize(data: &[u8]) -> Result<Self, FieldError> {
-        Ok(bincode::decode_from_slice(data, bincode::config::standard())?.0)
+        Ok(bincode::decode_from_slice(data, DECODE_CONFIG)?.0)
     }
 }
 
-#[derive(Debug, Clone, Encode, Decode)]
-pub struct SetCpuFreq(pub freq::TargetFreq);
+#[derive(Debug, Default, Clone, Encode, Decode)]
+pub struct MemStatus {
+    pub usage: mem::MemUsage,
+    // Pressure stall information, None if the kernel is built without PSI
+    pub memory_pressure: Option<mem::Pressure>,
+    pub cpu_pressure: Option<mem::Pressure>,
+    pub io_pressure: Option<mem::Pressure>,
+}
 
-#[derive(Debug, Clone, Encode, Decode)]
-pub struct SetFanSpeed(pub fan_speed::TargetFanSpeed);
+impl MemStatus {
+    pub fn serialize(&self) -> Result<Vec<u8>, FieldError> {
+        Ok(bincode::encode_to_vec(self, bincode::config::standard())?)
+    }
+    pub fn deserialize(data: &[u8]) -> Result<Self, FieldError> {
+        Ok(bincode::decode_from_slice(data, DECODE_CONFIG)?.0)
+    }
+}
+
+#[derive(Debug, Default, Clone, Encode, Decode)]
+pub struct StorageStatus {
+    pub drives: Vec<storage::DriveTemp>, // Sorted by controller name
+}
+
+impl StorageStatus {
+    pub fn serialize(&self) -> Result<Vec<u8>, FieldError> {
+        Ok(bincode::encode_to_vec(self, bincode::config::standard())?)
+    }
+    pub fn deserialize(data: &[u8]) -> Result<Self, FieldError> {
+        Ok(bincode::decode_from_slice(data, DECODE_CONFIG)?.0)
+    }
+}
+
+#[derive(Debug, Default, Clone, Encode, Decode)]
+pub struct PowerSupplyStatus {
+    pub source: power_supply::PowerSource,
+    pub adapter_power: Option<power::Power>, // Rated power of the adapter, None if not reported
+    pub supplies: Vec<String>,               // Names of the online supplies, e.g. "AC0"
+}
+
+impl PowerSupplyStatus {
+    pub fn serialize(&self) -> Result<Vec<u8>, FieldError> {
+        Ok(bincode::encode_to_vec(self, bincode::config::standard())?)
+    }
+    pub fn deserialize(data: &[u8]) -> Result<Self, FieldError> {
+        Ok(bincode::decode_from_slice(data, DECODE_CONFIG)?.0)
+    }
+}
 
 #[derive(Debug, Clone, Encode, Decode)]
-pub struct SetGpuFreq(pub freq::TargetFreq);
+pub struct SetCpuFreq(pub freq::TargetFreq);
 
 #[derive(Debug, Clone, Encode, Decode)]
-pub struct SetGpuFanSpeed(pub fan_speed::TargetFanSpeed);
+pub struct SetFanSpeed(pub fan_speed::TargetFanSpeed);
 
 #[derive(Debug, Clone, Encode, Decode)]
 pub struct ComponentList(pub HashMap<u8, Desc>);
 impl ComponentList {
     pub fn deserialize(data: &[u8]) -> Result<Self, FieldError> {
-        Ok(bincode::decode_from_slice(data, bincode::config::standard())?.0)
+        Ok(bincode::decode_from_slice(data, DECODE_CONFIG)?.0)
     }
 }
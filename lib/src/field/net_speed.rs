@@ -0,0 +1,67 @@
+use crate::field::FieldError;
+use bincode::{Decode, Encode};
+
+type Result<T> = std::result::Result<T, FieldError>;
+
+#[derive(Debug, Default, Clone, Encode, Decode)]
+pub struct InterfaceSpeed {
+    name: String,
+    rx_bytes: u64,   // Received bytes per second
+    tx_bytes: u64,   // Transmitted bytes per second
+    rx_packets: u64, // Received packets per second
+    tx_packets: u64, // Transmitted packets per second
+}
+
+impl InterfaceSpeed {
+    pub fn new(name: &str, rx_bytes: u64, tx_bytes: u64, rx_packets: u64, tx_packets: u64) -> Self {
+        Self {
+            name: name.to_string(),
+            rx_bytes,
+            tx_bytes,
+            rx_packets,
+            tx_packets,
+        }
+    }
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+    pub fn get_rx_bytes(&self) -> u64 {
+        self.rx_bytes
+    }
+    pub fn get_tx_bytes(&self) -> u64 {
+        self.tx_bytes
+    }
+    pub fn get_rx_packets(&self) -> u64 {
+        self.rx_packets
+    }
+    pub fn get_tx_packets(&self) -> u64 {
+        self.tx_packets
+    }
+}
+
+#[derive(Debug, Default, Clone, Encode, Decode)]
+pub struct NetSpeed {
+    interfaces: Vec<InterfaceSpeed>, // Sorted by interface name
+}
+
+impl NetSpeed {
+    pub fn new(interfaces: Vec<InterfaceSpeed>) -> Self {
+        Self { interfaces }
+    }
+    pub fn get_interfaces(&self) -> &Vec<InterfaceSpeed> {
+        &self.interfaces
+    }
+    pub fn get_interface(&self, name: &str) -> Option<&InterfaceSpeed> {
+        self.interfaces
+            .iter()
+            .find(|interface| interface.name == name)
+    }
+
+    pub fn serialize(&self) -> Result<Vec<u8>> {
+        Ok(bincode::encode_to_vec(self, bincode::config::standard())?)
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<Self> {
+        Ok(bincode::decode_from_slice(data, crate::field::DECODE_CONFIG)?.0)
+    }
+}
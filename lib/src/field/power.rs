@@ -1,20 +1,20 @@
-use crate::field::FieldError;
+use crate::field::{FieldError, unit::Milliwatts};
 use bincode::{Decode, Encode};
 type Result<T> = std::result::Result<T, FieldError>;
 
 #[derive(Debug, Default, Clone, Decode, Encode)]
 pub struct Power {
-    value: u64, // Power consumption in mWatts
+    value: Milliwatts, // Power consumption
 }
 
 impl Power {
-    pub fn new(value: u64) -> Self {
+    pub fn new(value: Milliwatts) -> Self {
         Self { value }
     }
-    pub fn get_value(&self) -> u64 {
+    pub fn get_value(&self) -> Milliwatts {
         self.value
     }
-    pub fn set_value(&mut self, value: u64) {
+    pub fn set_value(&mut self, value: Milliwatts) {
         self.value = value;
     }
 }
@@ -22,21 +22,37 @@ impl Power {
 impl TryFrom<&[u8]> for Power {
     type Error = FieldError;
     fn try_from(value: &[u8]) -> Result<Self> {
-        let (value, _) = bincode::decode_from_slice(value, bincode::config::standard())?;
+        let (value, _) = bincode::decode_from_slice(value, crate::field::DECODE_CONFIG)?;
         Ok(value)
     }
 }
 
 #[derive(Debug, Default, Clone, Decode, Encode)]
 pub struct TargetPower {
-    max: u64, // Power consumption in Watts
+    max: Milliwatts, // Power limit, in W before protocol version 3
 }
 
 impl TargetPower {
-    pub fn new(value: u64) -> Self {
+    pub fn new(value: Milliwatts) -> Self {
         Self { max: value }
     }
-    pub fn get_max(&self) -> u64 {
+    pub fn get_max(&self) -> Milliwatts {
         self.max
     }
+
+    pub fn serialize(&self) -> Result<Vec<u8>> {
+        Ok(bincode::encode_to_vec(self, bincode::config::standard())?)
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<Self> {
+        Ok(bincode::decode_from_slice(data, crate::field::DECODE_CONFIG)?.0)
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Decode, Encode)]
+pub enum PowerState {
+    #[default]
+    Unknown,
+    Active,
+    Suspended, // Runtime suspended, querying the device would wake it up
 }
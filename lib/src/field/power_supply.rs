@@ -0,0 +1,22 @@
+use bincode::{Decode, Encode};
+use std::fmt::Display;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Decode, Encode)]
+pub enum PowerSource {
+    #[default]
+    Unknown, // No supply and no battery found
+    Battery,
+    Mains, // Barrel jack AC adapter
+    UsbPd, // USB-C power delivery
+}
+
+impl Display for PowerSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PowerSource::Unknown => write!(f, "unknown"),
+            PowerSource::Battery => write!(f, "battery"),
+            PowerSource::Mains => write!(f, "AC adapter"),
+            PowerSource::UsbPd => write!(f, "USB-C PD"),
+        }
+    }
+}
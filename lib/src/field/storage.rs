@@ -0,0 +1,58 @@
+use crate::field::temp::Temp;
+use bincode::{Decode, Encode};
+
+/// Temperatures of one NVMe drive
+#[derive(Debug, Default, Clone, Encode, Decode)]
+pub struct DriveTemp {
+    name: String,           // Controller name, e.g. "nvme0"
+    model: String,          // Model as reported by the drive
+    composite: Temp,        // Composite temperature the drive throttles on
+    sensors: Vec<Temp>,     // Additional sensors, "Sensor 1" onwards
+    warning: Option<Temp>,  // Warning composite temperature (WCTEMP), throttling starts here
+    critical: Option<Temp>, // Critical composite temperature (CCTEMP)
+}
+
+impl DriveTemp {
+    pub fn new(
+        name: &str,
+        model: &str,
+        composite: Temp,
+        sensors: Vec<Temp>,
+        warning: Option<Temp>,
+        critical: Option<Temp>,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            model: model.to_string(),
+            composite,
+            sensors,
+            warning,
+            critical,
+        }
+    }
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+    pub fn get_model(&self) -> &str {
+        &self.model
+    }
+    pub fn get_composite(&self) -> &Temp {
+        &self.composite
+    }
+    pub fn get_sensors(&self) -> &Vec<Temp> {
+        &self.sensors
+    }
+    pub fn get_warning(&self) -> Option<&Temp> {
+        self.warning.as_ref()
+    }
+    pub fn get_critical(&self) -> Option<&Temp> {
+        self.critical.as_ref()
+    }
+
+    // The drive is throttling, or about to, once the composite reaches the warning threshold
+    pub fn is_over_warning(&self) -> bool {
+        self.warning
+            .as_ref()
+            .is_some_and(|warning| self.composite.get_value() >= warning.get_value())
+    }
+}
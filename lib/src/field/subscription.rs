@@ -0,0 +1,134 @@
+use crate::{
+    field::FieldError,
+    proto::{MsgCommand, MsgError, ProtoError, Request},
+};
+use bincode::{Decode, Encode};
+
+type Result<T> = std::result::Result<T, FieldError>;
+
+/// A request the daemon repeats after every sample and pushes as a Notify, the payload is
+/// the same as the reply to the request would be
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct Topic {
+    id_num: u8,
+    command: MsgCommand,   // A Get command, e.g. GetStatus
+    payload: Vec<Vec<u8>>, // Arguments of the command, e.g. the FanIndex of GetFanSpeed
+}
+
+impl Topic {
+    pub fn new(id_num: u8, request: &Request) -> std::result::Result<Self, ProtoError> {
+        Ok(Self {
+            id_num,
+            command: request.get_command(),
+            payload: request.encode_payload()?,
+        })
+    }
+    pub fn get_id_num(&self) -> u8 {
+        self.id_num
+    }
+    pub fn get_command(&self) -> &MsgCommand {
+        &self.command
+    }
+    pub fn get_request(&self) -> std::result::Result<Request, MsgError> {
+        Request::decode(&self.command, &self.payload)
+    }
+
+    /// Only reads may be repeated by the daemon, a Set topic would change the hardware on
+    /// every sample
+    pub fn is_readable(&self) -> bool {
+        matches!(
+            self.command,
+            MsgCommand::GetStatus | MsgCommand::GetFanSpeed | MsgCommand::GetGpuLimits
+        )
+    }
+}
+
+#[derive(Debug, Default, Clone, Encode, Decode)]
+pub struct Subscription {
+    topics: Vec<Topic>,
+    min_interval: u64, // Minimum time between two pushes in ms, samples in between are skipped
+    events: bool,      // Also push component events as they happen
+}
+
+impl Subscription {
+    pub fn new(topics: Vec<Topic>, min_interval: u64, events: bool) -> Self {
+        Self {
+            topics,
+            min_interval,
+            events,
+        }
+    }
+    pub fn get_topics(&self) -> &Vec<Topic> {
+        &self.topics
+    }
+    pub fn get_min_interval(&self) -> u64 {
+        self.min_interval
+    }
+    pub fn wants_events(&self) -> bool {
+        self.events
+    }
+
+    /// Reject a subscription with a topic the daemon won't repeat
+    pub fn check_topics(&self) -> std::result::Result<(), MsgError> {
+        match self.topics.iter().find(|topic| !topic.is_readable()) {
+            Some(topic) => Err(MsgError::InvalidCommand(format!(
+                "{} can't be subscribed to",
+                topic.get_command()
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    pub fn serialize(&self) -> Result<Vec<u8>> {
+        Ok(bincode::encode_to_vec(self, bincode::config::standard())?)
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<Self> {
+        Ok(bincode::decode_from_slice(data, crate::field::DECODE_CONFIG)?.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::{
+        cores::TargetCoreMask,
+        fan_speed::{FanIndex, FanValues, TargetFanSpeed},
+        power::TargetPower,
+        unit::Milliwatts,
+    };
+
+    fn subscription(request: &Request) -> Subscription {
+        let status = Topic::new(0, &Request::GetStatus).unwrap();
+        let topic = Topic::new(1, request).unwrap();
+        Subscription::new(vec![status, topic], 1000, false)
+    }
+
+    #[test]
+    fn read_topics_accepted() {
+        for request in [
+            Request::GetStatus,
+            Request::GetFanSpeed(FanIndex::All),
+            Request::GetGpuLimits,
+        ] {
+            assert!(subscription(&request).check_topics().is_ok());
+        }
+    }
+
+    #[test]
+    fn control_topics_rejected() {
+        for request in [
+            Request::SetFanSpeed(FanValues::Cpu(TargetFanSpeed::new(100))),
+            Request::SetFanAuto(FanIndex::All),
+            Request::SetPowerLimit(TargetPower::new(Milliwatts::from_watts(50))),
+            Request::SetCoreMask(TargetCoreMask::default()),
+            Request::ResetGpuLimits,
+            Request::Unsubscribe,
+        ] {
+            assert!(matches!(
+                subscription(&request).check_topics(),
+                Err(MsgError::InvalidCommand(_))
+            ));
+        }
+    }
+}
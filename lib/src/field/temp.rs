@@ -1,20 +1,20 @@
-use crate::field::FieldError;
+use crate::field::{FieldError, unit::MilliCelsius};
 use bincode::{Decode, Encode};
 type Result<T> = std::result::Result<T, FieldError>;
 
 #[derive(Debug, Default, Clone, Encode, Decode)]
 pub struct Temp {
-    value: u64, // Temperature in Celsius
+    value: MilliCelsius,
 }
 
 impl Temp {
-    pub fn new(value: u64) -> Self {
+    pub fn new(value: MilliCelsius) -> Self {
         Self { value }
     }
-    pub fn get_value(&self) -> u64 {
+    pub fn get_value(&self) -> MilliCelsius {
         self.value
     }
-    pub fn set_value(&mut self, value: u64) {
+    pub fn set_value(&mut self, value: MilliCelsius) {
         self.value = value;
     }
 }
@@ -22,7 +22,7 @@ impl Temp {
 impl TryFrom<&[u8]> for Temp {
     type Error = FieldError;
     fn try_from(value: &[u8]) -> Result<Self> {
-        let (value, _) = bincode::decode_from_slice(value, bincode::config::standard())?;
+        let (value, _) = bincode::decode_from_slice(value, crate::field::DECODE_CONFIG)?;
         Ok(value)
     }
 }
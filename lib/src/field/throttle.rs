@@ -0,0 +1,59 @@
+use crate::field::FieldError;
+use bincode::{Decode, Encode};
+type Result<T> = std::result::Result<T, FieldError>;
+
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub enum ThrottleReason {
+    Thermal,      // Package/core reached its thermal limit
+    Prochot,      // PROCHOT# asserted, e.g. by the EC or the GPU
+    CriticalTemp, // Critical temperature reached
+    PowerLimit,   // Power limit notification from the package
+    Pl1,          // Long term package power limit (PL1)
+    Pl2,          // Short term package power limit (PL2)
+    VrThermal,    // Voltage regulator thermal alert
+    VrCurrent,    // Voltage regulator current limit (TDC)
+    MaxTurbo,     // Multi-core turbo limit
+    Other,        // Any other reason reported by the hardware
+}
+
+#[derive(Debug, Default, Clone, Encode, Decode)]
+pub struct Throttle {
+    throttled: bool,
+    core_events: Vec<u64>, // Throttle events per core since last refresh
+    package_events: u64,   // Package throttle events since last refresh
+    reasons: Vec<ThrottleReason>,
+}
+
+impl Throttle {
+    pub fn new(core_events: Vec<u64>, package_events: u64, reasons: Vec<ThrottleReason>) -> Self {
+        let throttled = package_events > 0
+            || core_events.iter().any(|events| *events > 0)
+            || !reasons.is_empty();
+        Self {
+            throttled,
+            core_events,
+            package_events,
+            reasons,
+        }
+    }
+    pub fn is_throttled(&self) -> bool {
+        self.throttled
+    }
+    pub fn get_core_events(&self) -> &Vec<u64> {
+        &self.core_events
+    }
+    pub fn get_package_events(&self) -> u64 {
+        self.package_events
+    }
+    pub fn get_reasons(&self) -> &Vec<ThrottleReason> {
+        &self.reasons
+    }
+}
+
+impl TryFrom<&[u8]> for Throttle {
+    type Error = FieldError;
+    fn try_from(value: &[u8]) -> Result<Self> {
+        let (value, _) = bincode::decode_from_slice(value, crate::field::DECODE_CONFIG)?;
+        Ok(value)
+    }
+}
@@ -0,0 +1,111 @@
+//! Units of the values exchanged with the daemon.
+//!
+//! Every quantity has one unit on the wire: temperatures in milli-Celsius, power in
+//! milliwatts and frequencies in MHz. A unit type encodes exactly like the integer it wraps,
+//! so wrapping a field doesn't change its layout. Values in other units, e.g. kHz from
+//! cpufreq or W in a config, go through the conversion methods instead of bare arithmetic.
+
+use bincode::{Decode, Encode};
+use std::fmt::Display;
+
+/// Temperature in thousandths of a degree Celsius, the unit of sysfs thermal zones and hwmon
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Encode, Decode)]
+pub struct MilliCelsius(u64);
+
+impl MilliCelsius {
+    pub const fn new(value: u64) -> Self {
+        Self(value)
+    }
+    // saturates, the value may come from a peer
+    pub const fn from_celsius(celsius: u64) -> Self {
+        Self(celsius.saturating_mul(1000))
+    }
+    pub fn get_value(&self) -> u64 {
+        self.0
+    }
+    pub fn as_celsius(&self) -> f32 {
+        self.0 as f32 / 1000.0
+    }
+}
+
+// Whole degrees, the sensors aren't more precise than that
+impl Display for MilliCelsius {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} °C", self.0 / 1000)
+    }
+}
+
+/// Power in milliwatts, the unit of nvml and of RAPL energy over time
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Encode, Decode)]
+pub struct Milliwatts(u64);
+
+impl Milliwatts {
+    pub const fn new(value: u64) -> Self {
+        Self(value)
+    }
+    // saturates, a version 2 peer sends its power limit in W
+    pub const fn from_watts(watts: u64) -> Self {
+        Self(watts.saturating_mul(1000))
+    }
+    // hwmon power1_average is in microwatts
+    pub const fn from_microwatts(microwatts: u64) -> Self {
+        Self(microwatts / 1000)
+    }
+    pub fn get_value(&self) -> u64 {
+        self.0
+    }
+    pub fn as_watts(&self) -> f32 {
+        self.0 as f32 / 1000.0
+    }
+}
+
+impl Display for Milliwatts {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} mW", self.0)
+    }
+}
+
+/// Frequency in MHz, the unit of nvml and of the i915 and amdgpu sysfs files
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Encode, Decode)]
+pub struct Megahertz(u32);
+
+impl Megahertz {
+    pub const fn new(value: u32) -> Self {
+        Self(value)
+    }
+    pub fn get_value(&self) -> u32 {
+        self.0
+    }
+    pub fn as_khz(&self) -> Kilohertz {
+        Kilohertz(self.0 as u64 * 1000)
+    }
+}
+
+impl Display for Megahertz {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} MHz", self.0)
+    }
+}
+
+/// Frequency in kHz, the unit of cpufreq. Not sent to the daemon's peers, convert it with
+/// `as_mhz` first
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Kilohertz(u64);
+
+impl Kilohertz {
+    pub const fn new(value: u64) -> Self {
+        Self(value)
+    }
+    pub fn get_value(&self) -> u64 {
+        self.0
+    }
+    pub fn as_mhz(&self) -> Megahertz {
+        Megahertz((self.0 / 1000).min(u32::MAX as u64) as u32)
+    }
+}
+
+impl Display for Kilohertz {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} kHz", self.0)
+    }
+}
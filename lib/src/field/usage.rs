@@ -4,17 +4,17 @@ type Result<T> = std::result::Result<T, FieldError>;
 
 #[derive(Debug, Default, Clone, Encode, Decode)]
 pub struct Usage {
-    value: Vec<f32>, // CPU usage in percentage
+    value: Vec<Option<f32>>, // CPU usage in percentage, None for an offline core
 }
 
 impl Usage {
-    pub fn new(value: Vec<f32>) -> Self {
+    pub fn new(value: Vec<Option<f32>>) -> Self {
         Self { value }
     }
-    pub fn get_value(&self) -> &Vec<f32> {
+    pub fn get_value(&self) -> &Vec<Option<f32>> {
         &self.value
     }
-    pub fn set_value(&mut self, value: Vec<f32>) {
+    pub fn set_value(&mut self, value: Vec<Option<f32>>) {
         self.value = value;
     }
 }
@@ -22,7 +22,7 @@ impl Usage {
 impl TryFrom<&[u8]> for Usage {
     type Error = FieldError;
     fn try_from(value: &[u8]) -> Result<Self> {
-        let (value, _) = bincode::decode_from_slice(value, bincode::config::standard())?;
+        let (value, _) = bincode::decode_from_slice(value, crate::field::DECODE_CONFIG)?;
         Ok(value)
     }
 }
@@ -0,0 +1,253 @@
+use crate::{
+    proto::{
+        MIN_PROTO_VERSION, MsgBody, MsgCommand, MsgError, MsgMode, MsgPacket, PROTO_VERSION,
+        ProtoError,
+        codec::{self, MsgReader},
+        recv_handshake_msg, send_msg,
+    },
+    stream::{StreamRead, StreamWrite},
+};
+use bincode::{Decode, Encode};
+use tokio::io::{AsyncRead, AsyncWrite};
+
+type Result<T> = std::result::Result<T, ProtoError>;
+
+/// Optional protocol features, a feature is only used when both sides announce it
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+pub struct Features(u64);
+
+impl Features {
+    pub const CORE_HOTPLUG: Features = Features(1 << 0); // SetCoreMask
+    pub const GPU_LIMITS: Features = Features(1 << 1); // GetGpuLimits, SetPowerLimit, ...
+    pub const SUBSCRIBE: Features = Features(1 << 2); // Subscribe and Notify pushes
+    pub const LEASES: Features = Features(1 << 3); // MsgError::Busy for a leased component
+    pub const PERMISSIONS: Features = Features(1 << 4); // MsgError::PermissionDenied
+    pub const ERROR_CODES: Features = Features(1 << 5); // UnknownComponent, AccessDenied
+    pub const CAPABILITIES: Features = Features(1 << 6); // GetCapabilities
+
+    pub const fn empty() -> Self {
+        Features(0)
+    }
+    pub const fn union(self, other: Features) -> Self {
+        Features(self.0 | other.0)
+    }
+    pub const fn intersection(self, other: Features) -> Self {
+        Features(self.0 & other.0)
+    }
+    pub fn contains(&self, other: Features) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+/// Every feature this build implements
+pub const SUPPORTED_FEATURES: Features = Features::CORE_HOTPLUG
+    .union(Features::GPU_LIMITS)
+    .union(Features::SUBSCRIBE)
+    .union(Features::LEASES)
+    .union(Features::PERMISSIONS)
+    .union(Features::ERROR_CODES)
+    .union(Features::CAPABILITIES);
+
+/// Sent by the client right after connecting
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct Hello {
+    min_version: u8,
+    max_version: u8,
+    features: Features,
+}
+
+/// The daemon's answer to Hello, with its own version range and features
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct Welcome {
+    min_version: u8,
+    max_version: u8,
+    features: Features,
+}
+
+/// What both sides agreed on
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Session {
+    version: u8,
+    features: Features,
+}
+
+impl Session {
+    pub fn get_version(&self) -> u8 {
+        self.version
+    }
+    pub fn get_features(&self) -> Features {
+        self.features
+    }
+    pub fn supports(&self, feature: Features) -> bool {
+        self.features.contains(feature)
+    }
+
+    /// Whether the peer can decode the command, commands added with a feature are only known
+    /// to peers that negotiated it
+    pub fn knows_command(&self, command: &MsgCommand) -> bool {
+        match command {
+            MsgCommand::SetCoreMask => self.supports(Features::CORE_HOTPLUG),
+            MsgCommand::GetGpuLimits
+            | MsgCommand::SetMemFreq
+            | MsgCommand::SetPowerLimit
+            | MsgCommand::ResetGpuLimits => self.supports(Features::GPU_LIMITS),
+            MsgCommand::Subscribe | MsgCommand::Unsubscribe | MsgCommand::Event => {
+                self.supports(Features::SUBSCRIBE)
+            }
+            MsgCommand::GetCapabilities => self.supports(Features::CAPABILITIES),
+            _ => true,
+        }
+    }
+
+    /// The error as the peer understands it: errors added after it was built become the
+    /// variant that was sent for them before, unless it negotiated the feature introducing them
+    pub fn compatible_error(&self, error: MsgError) -> MsgError {
+        match error {
+            MsgError::Busy(reason) if !self.supports(Features::LEASES) => {
+                MsgError::UnsupportedOperation(reason)
+            }
+            MsgError::PermissionDenied(reason) if !self.supports(Features::PERMISSIONS) => {
+                MsgError::UnsupportedOperation(reason)
+            }
+            MsgError::UnknownComponent(id) if !self.supports(Features::ERROR_CODES) => {
+                MsgError::InvalidCommand(format!("no component {}", id))
+            }
+            MsgError::AccessDenied(reason) if !self.supports(Features::ERROR_CODES) => {
+                MsgError::DeviceError(reason)
+            }
+            error => error,
+        }
+    }
+}
+
+// Highest version both ranges contain
+fn negotiate(remote: (u8, u8)) -> Result<u8> {
+    let version = PROTO_VERSION.min(remote.1);
+    if version < MIN_PROTO_VERSION.max(remote.0) {
+        return Err(ProtoError::VersionMismatch {
+            supported: (MIN_PROTO_VERSION, PROTO_VERSION),
+            remote,
+        });
+    }
+    Ok(version)
+}
+
+fn encode<T: Encode>(value: &T) -> Result<Vec<u8>> {
+    Ok(bincode::encode_to_vec(value, bincode::config::standard())?)
+}
+
+fn decode<T: Decode<()>>(body: &MsgBody) -> Result<T> {
+    let payload = body.get_payload().first().ok_or(ProtoError::Handshake(
+        "missing handshake payload".to_string(),
+    ))?;
+    Ok(bincode::decode_from_slice(payload, crate::field::DECODE_CONFIG)?.0)
+}
+
+fn hello_msg(features: Features) -> Result<MsgBody> {
+    let hello = Hello {
+        min_version: MIN_PROTO_VERSION,
+        max_version: PROTO_VERSION,
+        features,
+    };
+    let packet = MsgPacket::new(MsgMode::Request, None, 0, 0, MsgCommand::Hello);
+    Ok(MsgBody::new(packet, vec![encode(&hello)?]))
+}
+
+// A daemon from before the handshake can't decode Hello and drops the connection
+fn closed_before_welcome(err: ProtoError) -> ProtoError {
+    match err {
+        ProtoError::Io(msg) => ProtoError::Handshake(format!(
+            "daemon closed the connection ({}), it may not speak protocol version {}-{}",
+            msg, MIN_PROTO_VERSION, PROTO_VERSION
+        )),
+        err => err,
+    }
+}
+
+fn read_welcome(body: &MsgBody, features: Features) -> Result<Session> {
+    let packet = body.get_packet();
+    if *packet.get_command() != MsgCommand::Hello || *packet.get_mode() != MsgMode::Reply {
+        return Err(ProtoError::Handshake(format!(
+            "expected a Welcome, got {}",
+            packet.get_command()
+        )));
+    }
+    let welcome: Welcome = decode(body)?;
+    Ok(Session {
+        version: negotiate((welcome.min_version, welcome.max_version))?,
+        features: features.intersection(welcome.features),
+    })
+}
+
+// The Welcome to send back and the session, the Welcome is sent even on a version mismatch
+// so the client can report it
+fn answer_hello(body: &MsgBody, features: Features) -> Result<(MsgBody, Result<Session>)> {
+    let packet = body.get_packet();
+    if *packet.get_command() != MsgCommand::Hello {
+        return Err(ProtoError::Handshake(format!(
+            "expected a Hello, got {}",
+            packet.get_command()
+        )));
+    }
+    let hello: Hello = decode(body)?;
+    let welcome = Welcome {
+        min_version: MIN_PROTO_VERSION,
+        max_version: PROTO_VERSION,
+        features,
+    };
+    let mut reply = packet.clone();
+    reply.set_mode(MsgMode::Reply);
+    let session = negotiate((hello.min_version, hello.max_version)).map(|version| Session {
+        version,
+        features: features.intersection(hello.features),
+    });
+    Ok((MsgBody::new(reply, vec![encode(&welcome)?]), session))
+}
+
+/// Client side of the handshake, fails if the daemon shares no protocol version with us
+pub fn client_handshake(
+    stream: &mut (impl StreamRead + StreamWrite),
+    features: Features,
+) -> Result<Session> {
+    // the handshake always carries our newest version, its layout never changes
+    send_msg(stream, PROTO_VERSION, &hello_msg(features)?)?;
+    let body = recv_handshake_msg(stream).map_err(closed_before_welcome)?;
+    read_welcome(&body, features)
+}
+
+/// Daemon side of the handshake
+pub fn server_handshake(
+    stream: &mut (impl StreamRead + StreamWrite),
+    features: Features,
+) -> Result<Session> {
+    let body = recv_handshake_msg(stream)?;
+    let (welcome, session) = answer_hello(&body, features)?;
+    send_msg(stream, PROTO_VERSION, &welcome)?;
+    session
+}
+
+/// `client_handshake` on a tokio stream
+pub async fn client_handshake_async(
+    reader: &mut MsgReader<impl AsyncRead + Unpin>,
+    writer: &mut (impl AsyncWrite + Unpin),
+    features: Features,
+) -> Result<Session> {
+    codec::send_msg(writer, PROTO_VERSION, &hello_msg(features)?).await?;
+    let body = reader
+        .recv_handshake()
+        .await
+        .map_err(closed_before_welcome)?;
+    read_welcome(&body, features)
+}
+
+/// `server_handshake` on a tokio stream
+pub async fn server_handshake_async(
+    reader: &mut MsgReader<impl AsyncRead + Unpin>,
+    writer: &mut (impl AsyncWrite + Unpin),
+    features: Features,
+) -> Result<Session> {
+    let body = reader.recv_handshake().await?;
+    let (welcome, session) = answer_hello(&body, features)?;
+    codec::send_msg(writer, PROTO_VERSION, &welcome).await?;
+    session
+}
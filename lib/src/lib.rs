@@ -1,4 +1,6 @@
+pub mod client;
 pub mod field;
+pub mod handshake;
 pub mod proto;
 pub mod stream;
 
@@ -0,0 +1,132 @@
+//! Messages on tokio streams, framed the same way as `recv_msg`/`send_msg` do on blocking
+//! ones so both kinds of peers can talk to each other.
+
+use crate::proto::{
+    MsgBody, MsgHeader, MsgLimits, MsgPacket, ProtoError, decode_header, encode_header,
+};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+type Result<T> = std::result::Result<T, ProtoError>;
+
+impl From<std::io::Error> for ProtoError {
+    fn from(err: std::io::Error) -> Self {
+        ProtoError::Io(err.to_string())
+    }
+}
+
+// A whole message at the start of `buffer` and its length, None if more bytes are needed
+fn decode_frame(
+    buffer: &[u8],
+    check_version: bool,
+    limits: &MsgLimits,
+) -> Result<Option<(MsgBody, usize)>> {
+    if buffer.len() < MsgHeader::FIELD_SIZE {
+        return Ok(None);
+    }
+    let msg_header = decode_header(&buffer[..MsgHeader::FIELD_SIZE], check_version)?;
+    limits.check_header(&msg_header)?;
+    let packet_end = MsgHeader::FIELD_SIZE + msg_header.get_packet_length() as usize;
+    if buffer.len() < packet_end {
+        return Ok(None);
+    }
+    let msg_packet = MsgPacket::deserialize(&buffer[MsgHeader::FIELD_SIZE..packet_end])?;
+    limits.check_packet(&msg_packet)?;
+    let end = packet_end
+        + msg_packet
+            .get_payload_length()
+            .iter()
+            .map(|length| *length as usize)
+            .sum::<usize>();
+    if buffer.len() < end {
+        return Ok(None);
+    }
+    let mut start = packet_end;
+    let payload = msg_packet
+        .get_payload_length()
+        .iter()
+        .map(|length| {
+            let payload = buffer[start..start + *length as usize].to_vec();
+            start += *length as usize;
+            payload
+        })
+        .collect();
+    Ok(Some((
+        MsgBody {
+            packet: msg_packet,
+            payload,
+        },
+        end,
+    )))
+}
+
+/// Reads messages from the receiving side of a tokio stream.
+///
+/// Bytes are kept in an internal buffer until a whole message arrived, so `recv` is cancel
+/// safe: it can be used in `tokio::select!` and a dropped call loses nothing.
+pub struct MsgReader<R> {
+    reader: R,
+    buffer: Vec<u8>, // Received, not yet returned
+    limits: MsgLimits,
+}
+
+impl<R: AsyncRead + Unpin> MsgReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self::with_limits(reader, MsgLimits::default())
+    }
+
+    /// A reader rejecting messages beyond `limits` instead of the default ones
+    pub fn with_limits(reader: R, limits: MsgLimits) -> Self {
+        Self {
+            reader,
+            buffer: vec![],
+            limits,
+        }
+    }
+
+    /// The underlying stream, e.g. to write on a stream that wasn't split
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    /// Receive a message, rejecting it if the peer speaks a protocol version we don't
+    pub async fn recv(&mut self) -> Result<MsgBody> {
+        self.read_msg(true).await
+    }
+
+    // Receive a message of any version, only for the handshake
+    pub(crate) async fn recv_handshake(&mut self) -> Result<MsgBody> {
+        self.read_msg(false).await
+    }
+
+    async fn read_msg(&mut self, check_version: bool) -> Result<MsgBody> {
+        loop {
+            if let Some((body, length)) = decode_frame(&self.buffer, check_version, &self.limits)? {
+                self.buffer.drain(..length);
+                return Ok(body);
+            }
+            // read_buf only appends what it got, cancelling here keeps the buffer intact
+            if self.reader.read_buf(&mut self.buffer).await? == 0 {
+                return Err(ProtoError::Io("connection closed by peer".to_string()));
+            }
+        }
+    }
+}
+
+/// Send a message in protocol `version`, see `proto::send_msg`. Not cancel safe, a send
+/// dropped half way leaves part of a message on the stream, so give each stream a single
+/// writer that runs sends to completion
+pub async fn send_msg(
+    writer: &mut (impl AsyncWrite + Unpin),
+    version: u8,
+    body: &MsgBody,
+) -> Result<()> {
+    let msg_packet_bin = body.packet.serialize()?;
+    let mut msg = encode_header(version, &msg_packet_bin)?;
+    msg.extend_from_slice(&msg_packet_bin);
+    for payload in &body.payload {
+        msg.extend_from_slice(payload);
+    }
+    writer.write_all(&msg).await?;
+    writer.flush().await?;
+    Ok(())
+}
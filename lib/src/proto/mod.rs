@@ -0,0 +1,624 @@
+use std::fmt::Display;
+use crate::{
+    field::FieldError,
+    stream::{StreamError, StreamRead, StreamWrite},
+};
+use bincode::{Decode, Encode};
+
+pub mod codec;
+mod request;
+pub use request::{MILLIWATT_POWER_VERSION, Request, Response, Status};
+
+/// Newest protocol version of this build, see the compatibility policy in `field`. The
+/// handshake is sent with it, every later `MsgHeader` carries the version of the session
+pub const PROTO_VERSION: u8 = 3;
+/// Oldest protocol version this build still understands
+pub const MIN_PROTO_VERSION: u8 = 2;
+
+#[derive(Debug, Clone, Encode, Decode, thiserror::Error)]
+pub enum ProtoError {
+    #[error("IO Error: {0}")]
+    Io(String), // IO error
+    #[error("Parse Error: {0}")]
+    Parse(String), // Parsing error
+    #[error("Unsupported Operation: {0}")]
+    Other(String), // Other errors
+    #[error(
+        "Protocol version mismatch: supported {}-{}, peer speaks {}-{}",
+        supported.0, supported.1, remote.0, remote.1
+    )]
+    VersionMismatch {
+        supported: (u8, u8), // (min, max) of this side
+        remote: (u8, u8),    // (min, max) of the peer
+    },
+    #[error("Handshake Error: {0}")]
+    Handshake(String), // Peer didn't follow the Hello/Welcome exchange
+    #[error("Limit Exceeded: {0}")]
+    LimitExceeded(String), // Message larger than the receiver's MsgLimits
+}
+
+impl From<StreamError> for ProtoError {
+    fn from(err: StreamError) -> Self {
+        match err {
+            StreamError::Io(msg) => ProtoError::Io(msg.to_string()),
+            StreamError::Other(msg) => ProtoError::Other(msg),
+        }
+    }
+}
+
+impl From<FieldError> for ProtoError {
+    fn from(err: FieldError) -> Self {
+        ProtoError::Parse(format!("Field error: {}", err))
+    }
+}
+
+impl From<bincode::error::DecodeError> for ProtoError {
+    fn from(err: bincode::error::DecodeError) -> Self {
+        ProtoError::Parse(format!("Decode error: {}", err))
+    }
+}
+
+impl From<bincode::error::EncodeError> for ProtoError {
+    fn from(err: bincode::error::EncodeError) -> Self {
+        ProtoError::Parse(format!("Encode error: {}", err))
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Decode, Encode)]
+pub enum MsgMode {
+    Request,
+    Reply,
+    Notify,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Decode, Encode, thiserror::Error)]
+pub enum MsgError {
+    #[error("Unsupported operation: {0}")]
+    UnsupportedOperation(String), // The component can't do this request
+    #[error("Invalid command: {0}")]
+    InvalidCommand(String), // Invalid parameter, or a payload that doesn't decode
+    #[error("Device error: {0}")]
+    DeviceError(String), // The hardware or its driver failed the request
+    #[error("Timeout: {0}")]
+    Timeout(String), // The hardware didn't answer in time, e.g. the EC
+    #[error("Server error: {0}")]
+    ServerError(String), // The daemon failed, not the hardware
+    #[error("Unknown error: {0}")]
+    Unknown(String),
+    #[error("Busy: {0}")]
+    Busy(String), // Component controlled by another connection, only sent with LEASES
+    #[error("Permission denied: {0}")]
+    PermissionDenied(String), // Peer may not change the hardware, only sent with PERMISSIONS
+    #[error("No component {0}")]
+    UnknownComponent(u8), // Only sent with ERROR_CODES, InvalidCommand before
+    #[error("Access denied: {0}")]
+    AccessDenied(String), // The daemon may not open the device, only sent with ERROR_CODES
+}
+
+/// Stable number for each kind of MsgError, for scripts and logs that act on the kind of
+/// failure rather than on its message. Codes are never reused
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u16)]
+pub enum ErrorCode {
+    UnsupportedOperation = 1,
+    InvalidCommand = 2,
+    DeviceError = 3,
+    Timeout = 4,
+    ServerError = 5,
+    Unknown = 6,
+    Busy = 7,
+    PermissionDenied = 8,
+    UnknownComponent = 9,
+    AccessDenied = 10,
+}
+
+impl ErrorCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::UnsupportedOperation => "unsupported_operation",
+            ErrorCode::InvalidCommand => "invalid_command",
+            ErrorCode::DeviceError => "device_error",
+            ErrorCode::Timeout => "timeout",
+            ErrorCode::ServerError => "server_error",
+            ErrorCode::Unknown => "unknown",
+            ErrorCode::Busy => "busy",
+            ErrorCode::PermissionDenied => "permission_denied",
+            ErrorCode::UnknownComponent => "unknown_component",
+            ErrorCode::AccessDenied => "access_denied",
+        }
+    }
+}
+
+impl Display for ErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl MsgError {
+    pub fn get_code(&self) -> ErrorCode {
+        match self {
+            MsgError::UnsupportedOperation(_) => ErrorCode::UnsupportedOperation,
+            MsgError::InvalidCommand(_) => ErrorCode::InvalidCommand,
+            MsgError::DeviceError(_) => ErrorCode::DeviceError,
+            MsgError::Timeout(_) => ErrorCode::Timeout,
+            MsgError::ServerError(_) => ErrorCode::ServerError,
+            MsgError::Unknown(_) => ErrorCode::Unknown,
+            MsgError::Busy(_) => ErrorCode::Busy,
+            MsgError::PermissionDenied(_) => ErrorCode::PermissionDenied,
+            MsgError::UnknownComponent(_) => ErrorCode::UnknownComponent,
+            MsgError::AccessDenied(_) => ErrorCode::AccessDenied,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Decode, Encode)]
+pub enum MsgCommand {
+    // Handshake, first message on every connection, must stay the first variant
+    Hello,
+
+    // Get
+    GetComponentList, // Get current enabled hardwares' index
+    GetStatus,
+    GetFanSpeed,
+
+    // Set
+    SetFreq,
+    SetFanSpeed,
+    SetFanAuto,
+    SetCoreMask,
+    SetMemFreq,
+    SetPowerLimit,
+    ResetGpuLimits,
+
+    // Push, only with the SUBSCRIBE feature
+    Subscribe,   // Replace the subscription of this connection
+    Unsubscribe, // Stop all pushes
+    Event,       // Notify carrying a component event
+
+    // Only with the CAPABILITIES feature
+    GetCapabilities, // Commands and settable ranges of a component
+
+    // Only with the GPU_LIMITS feature
+    GetGpuLimits, // Power limit and locked clocks with their ranges
+}
+
+impl MsgCommand {
+    /// Whether the command changes the hardware, the daemon gives a component to one
+    /// connection at a time for these
+    pub fn is_control(&self) -> bool {
+        matches!(
+            self,
+            MsgCommand::SetFreq
+                | MsgCommand::SetFanSpeed
+                | MsgCommand::SetFanAuto
+                | MsgCommand::SetCoreMask
+                | MsgCommand::SetMemFreq
+                | MsgCommand::SetPowerLimit
+                | MsgCommand::ResetGpuLimits
+        )
+    }
+}
+
+impl Display for MsgCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MsgCommand::GetComponentList => write!(f, "GetComponentList"),
+            MsgCommand::GetStatus => write!(f, "GetCpuStatus"),
+            MsgCommand::SetFreq => write!(f, "SetCpuFreq"),
+            MsgCommand::SetFanSpeed => write!(f, "SetCpuFanSpeed"),
+            MsgCommand::GetFanSpeed => write!(f, "GetCpuFanSpeed"),
+            MsgCommand::SetFanAuto => write!(f, "SetCpuAuto"),
+            MsgCommand::SetCoreMask => write!(f, "SetCoreMask"),
+            MsgCommand::GetGpuLimits => write!(f, "GetGpuLimits"),
+            MsgCommand::SetMemFreq => write!(f, "SetMemFreq"),
+            MsgCommand::SetPowerLimit => write!(f, "SetPowerLimit"),
+            MsgCommand::ResetGpuLimits => write!(f, "ResetGpuLimits"),
+            MsgCommand::Hello => write!(f, "Hello"),
+            MsgCommand::Subscribe => write!(f, "Subscribe"),
+            MsgCommand::Unsubscribe => write!(f, "Unsubscribe"),
+            MsgCommand::Event => write!(f, "Event"),
+            MsgCommand::GetCapabilities => write!(f, "GetCapabilities"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct MsgHeader {
+    version: u8,
+    timestamp: u64,
+    packet_length: u32,
+}
+
+// NOTE: std::mem::size_of considers the allignment padding, can't use it
+impl MsgHeader {
+    const FIELD_SIZE: usize = std::mem::size_of::<u8>()    // Size of `version`
+        + std::mem::size_of::<u64>() // Size of `timestamp`
+        + std::mem::size_of::<u32>(); // Size of `length`
+
+    pub fn new(version: u8, packet_length: usize) -> Self {
+        Self {
+            version,
+            timestamp: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+            packet_length: packet_length as u32,
+        }
+    }
+    pub fn get_version(&self) -> u8 {
+        self.version
+    }
+    pub fn get_timestamp(&self) -> u64 {
+        self.timestamp
+    }
+    pub fn get_packet_length(&self) -> u32 {
+        self.packet_length
+    }
+}
+
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct MsgPacket {
+    mode: MsgMode,
+    error: Option<MsgError>,
+    sequence: u64, // Unique sequence number for the message
+    id_num: u8,
+    command: MsgCommand,
+    payload_length: Vec<u32>, // Maybe multiple payloads
+}
+
+impl MsgPacket {
+    pub fn new(
+        mode: MsgMode,
+        error: Option<MsgError>,
+        sequence: u64,
+        id_num: u8,
+        command: MsgCommand,
+    ) -> Self {
+        Self {
+            mode,
+            error,
+            sequence,
+            id_num,
+            command,
+            payload_length: vec![], // Placeholder, will be set later
+        }
+    }
+    pub fn get_mode(&self) -> &MsgMode {
+        &self.mode
+    }
+    pub fn get_error(&self) -> &Option<MsgError> {
+        &self.error
+    }
+    pub fn get_sequence(&self) -> u64 {
+        self.sequence
+    }
+    pub fn get_command(&self) -> &MsgCommand {
+        &self.command
+    }
+    pub fn get_id_num(&self) -> u8 {
+        self.id_num
+    }
+    pub fn get_payload_length(&self) -> &Vec<u32> {
+        &self.payload_length
+    }
+    pub fn set_id_num(&mut self, id_num: u8) {
+        self.id_num = id_num;
+    }
+    pub fn set_mode(&mut self, mode: MsgMode) {
+        self.mode = mode;
+    }
+    pub fn set_error(&mut self, error: MsgError) {
+        self.error = Some(error);
+    }
+    pub fn set_sequence(&mut self, sequence: u64) {
+        self.sequence = sequence;
+    }
+    pub fn serialize(&self) -> Result<Vec<u8>> {
+        Ok(bincode::encode_to_vec(self, bincode::config::standard())?)
+    }
+    pub fn deserialize(data: &[u8]) -> Result<Self> {
+        Ok(bincode::decode_from_slice(data, crate::field::DECODE_CONFIG)?.0)
+    }
+}
+
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct MsgBody {
+    packet: MsgPacket,
+    payload: Vec<Vec<u8>>,
+}
+impl MsgBody {
+    pub fn new(packet: MsgPacket, payload: Vec<Vec<u8>>) -> Self {
+        let mut packet = packet;
+        // Set the payload length
+        packet.payload_length = payload.iter().map(|p| p.len() as u32).collect();
+        Self { packet, payload }
+    }
+    pub fn get_packet(&self) -> &MsgPacket {
+        &self.packet
+    }
+    pub fn set_sequence(&mut self, sequence: u64) {
+        self.packet.set_sequence(sequence);
+    }
+    pub fn get_payload(&self) -> &Vec<Vec<u8>> {
+        &self.payload
+    }
+}
+
+// Msg = MsgHeader + MsgBody(MsgPacket + payload)
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct Msg {
+    pub header: MsgHeader,
+    pub body: MsgBody,
+}
+
+type Result<T> = std::result::Result<T, ProtoError>;
+
+/// Bounds on a received message, checked against the lengths the peer announces before
+/// anything is read for them
+#[derive(Debug, Clone, Copy)]
+pub struct MsgLimits {
+    max_packet_size: u32,     // Serialized MsgPacket, in bytes
+    max_payload_size: u32,    // Each payload, in bytes
+    max_payload_count: usize, // Payloads in one message
+}
+
+impl Default for MsgLimits {
+    fn default() -> Self {
+        Self {
+            max_packet_size: 4096,
+            max_payload_size: 64 * 1024,
+            max_payload_count: 16,
+        }
+    }
+}
+
+impl MsgLimits {
+    pub fn new(max_packet_size: u32, max_payload_size: u32, max_payload_count: usize) -> Self {
+        Self {
+            max_packet_size,
+            max_payload_size,
+            max_payload_count,
+        }
+    }
+    pub fn get_max_packet_size(&self) -> u32 {
+        self.max_packet_size
+    }
+    pub fn get_max_payload_size(&self) -> u32 {
+        self.max_payload_size
+    }
+    pub fn get_max_payload_count(&self) -> usize {
+        self.max_payload_count
+    }
+
+    // Before reading the packet
+    pub(crate) fn check_header(&self, msg_header: &MsgHeader) -> Result<()> {
+        if msg_header.packet_length > self.max_packet_size {
+            return Err(ProtoError::LimitExceeded(format!(
+                "packet of {} bytes, at most {}",
+                msg_header.packet_length, self.max_packet_size
+            )));
+        }
+        Ok(())
+    }
+
+    // Before reading the payloads
+    pub(crate) fn check_packet(&self, msg_packet: &MsgPacket) -> Result<()> {
+        if msg_packet.payload_length.len() > self.max_payload_count {
+            return Err(ProtoError::LimitExceeded(format!(
+                "{} payloads, at most {}",
+                msg_packet.payload_length.len(),
+                self.max_payload_count
+            )));
+        }
+        if let Some(length) = msg_packet
+            .payload_length
+            .iter()
+            .find(|length| **length > self.max_payload_size)
+        {
+            return Err(ProtoError::LimitExceeded(format!(
+                "payload of {} bytes, at most {}",
+                length, self.max_payload_size
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Receive a message, rejecting it if the peer speaks a protocol version we don't
+pub fn recv_msg(stream: &mut impl StreamRead) -> Result<MsgBody> {
+    read_msg(stream, true, &MsgLimits::default())
+}
+
+/// `recv_msg` with other bounds than the default ones
+pub fn recv_msg_with_limits(stream: &mut impl StreamRead, limits: &MsgLimits) -> Result<MsgBody> {
+    read_msg(stream, true, limits)
+}
+
+// Receive a message of any version, only for the handshake whose layout never changes
+pub(crate) fn recv_handshake_msg(stream: &mut impl StreamRead) -> Result<MsgBody> {
+    read_msg(stream, false, &MsgLimits::default())
+}
+
+// The header has a fixed size, checked before the packet whose layout may differ between
+// versions
+fn decode_header(msg_header_bin: &[u8], check_version: bool) -> Result<MsgHeader> {
+    let (msg_header, _): (MsgHeader, _) = bincode::decode_from_slice(
+        msg_header_bin,
+        bincode::config::standard().with_fixed_int_encoding(),
+    )?;
+    if check_version && !(MIN_PROTO_VERSION..=PROTO_VERSION).contains(&msg_header.version) {
+        return Err(ProtoError::VersionMismatch {
+            supported: (MIN_PROTO_VERSION, PROTO_VERSION),
+            remote: (msg_header.version, msg_header.version),
+        });
+    }
+    Ok(msg_header)
+}
+
+fn encode_header(version: u8, msg_packet_bin: &[u8]) -> Result<Vec<u8>> {
+    let msg_header = MsgHeader::new(version, msg_packet_bin.len());
+    Ok(bincode::encode_to_vec(
+        &msg_header,
+        bincode::config::standard().with_fixed_int_encoding(),
+    )?)
+}
+
+fn read_msg(
+    stream: &mut impl StreamRead,
+    check_version: bool,
+    limits: &MsgLimits,
+) -> Result<MsgBody> {
+    let msg_header = decode_header(&stream.read(MsgHeader::FIELD_SIZE)?, check_version)?;
+    limits.check_header(&msg_header)?;
+    let msg_packet =
+        MsgPacket::deserialize(stream.read(msg_header.packet_length as usize)?.as_slice())?;
+    limits.check_packet(&msg_packet)?;
+    let payload = if !msg_packet.payload_length.is_empty() {
+        let mut payload = Vec::new();
+        for length in &msg_packet.payload_length {
+            let payload_bin = stream.read(*length as usize)?;
+            payload.push(payload_bin);
+        }
+        payload
+    } else {
+        vec![]
+    };
+    Ok(MsgBody {
+        packet: msg_packet,
+        payload,
+    })
+}
+
+/// Send a message in protocol `version`, the one negotiated for the stream. A peer older than
+/// this build rejects a header with a version it doesn't know
+pub fn send_msg(stream: &mut impl StreamWrite, version: u8, body: &MsgBody) -> Result<()> {
+    let msg_packet_bin = body.packet.serialize()?;
+    stream.write(&encode_header(version, &msg_packet_bin)?)?;
+    stream.write(msg_packet_bin.as_slice())?;
+    if !body.packet.payload_length.is_empty() {
+        for payload in &body.payload {
+            stream.write(payload)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handshake::Session;
+
+    // Both ends of a connection in one buffer
+    #[derive(Default)]
+    struct Buffer(Vec<u8>);
+
+    impl StreamWrite for Buffer {
+        fn write(&mut self, buffer: &[u8]) -> std::result::Result<(), StreamError> {
+            self.0.extend_from_slice(buffer);
+            Ok(())
+        }
+    }
+
+    impl StreamRead for Buffer {
+        fn read(&mut self, length: usize) -> std::result::Result<Vec<u8>, StreamError> {
+            if length > self.0.len() {
+                return Err(StreamError::Other("end of buffer".to_string()));
+            }
+            Ok(self.0.drain(..length).collect())
+        }
+    }
+
+    fn body(payload: Vec<Vec<u8>>) -> MsgBody {
+        let packet = MsgPacket::new(MsgMode::Request, None, 1, 0, MsgCommand::GetStatus);
+        MsgBody::new(packet, payload)
+    }
+
+    fn header(packet_length: usize) -> MsgHeader {
+        MsgHeader::new(PROTO_VERSION, packet_length)
+    }
+
+    #[test]
+    fn check_header_bounds_the_packet_size() {
+        let limits = MsgLimits::new(100, 10, 2);
+        assert!(limits.check_header(&header(100)).is_ok());
+        assert!(matches!(
+            limits.check_header(&header(101)),
+            Err(ProtoError::LimitExceeded(_))
+        ));
+    }
+
+    #[test]
+    fn check_packet_bounds_the_payloads() {
+        let limits = MsgLimits::new(100, 10, 2);
+        assert!(limits.check_packet(body(vec![]).get_packet()).is_ok());
+        assert!(
+            limits
+                .check_packet(body(vec![vec![0; 10], vec![0; 10]]).get_packet())
+                .is_ok()
+        );
+        // one payload too many
+        assert!(matches!(
+            limits.check_packet(body(vec![vec![]; 3]).get_packet()),
+            Err(ProtoError::LimitExceeded(_))
+        ));
+        // one byte too many in the last payload
+        assert!(matches!(
+            limits.check_packet(body(vec![vec![0; 10], vec![0; 11]]).get_packet()),
+            Err(ProtoError::LimitExceeded(_))
+        ));
+    }
+
+    #[test]
+    fn limits_apply_before_reading() {
+        let mut buffer = Buffer::default();
+        send_msg(&mut buffer, PROTO_VERSION, &body(vec![vec![1; 32]])).unwrap();
+        // the payload is never read, the announced length is enough
+        let limits = MsgLimits::new(4096, 16, 16);
+        assert!(matches!(
+            recv_msg_with_limits(&mut buffer, &limits),
+            Err(ProtoError::LimitExceeded(_))
+        ));
+
+        let mut buffer = Buffer::default();
+        send_msg(&mut buffer, PROTO_VERSION, &body(vec![vec![1; 32]])).unwrap();
+        let limits = MsgLimits::new(4, 1024, 16);
+        assert!(matches!(
+            recv_msg_with_limits(&mut buffer, &limits),
+            Err(ProtoError::LimitExceeded(_))
+        ));
+
+        let mut buffer = Buffer::default();
+        send_msg(&mut buffer, PROTO_VERSION, &body(vec![vec![1; 32]])).unwrap();
+        let received = recv_msg(&mut buffer).unwrap();
+        assert_eq!(received.get_payload(), &vec![vec![1; 32]]);
+    }
+
+    #[test]
+    fn header_carries_the_session_version() {
+        let mut buffer = Buffer::default();
+        send_msg(&mut buffer, MIN_PROTO_VERSION, &body(vec![])).unwrap();
+        // the version is the first field of the header
+        assert_eq!(buffer.0[0], MIN_PROTO_VERSION);
+        assert!(recv_msg(&mut buffer).is_ok());
+    }
+
+    #[test]
+    fn unknown_component_reaches_the_peer() {
+        let mut packet = MsgPacket::new(MsgMode::Reply, None, 1, 42, MsgCommand::GetStatus);
+        packet.set_error(MsgError::UnknownComponent(42));
+        let mut buffer = Buffer::default();
+        send_msg(&mut buffer, PROTO_VERSION, &MsgBody::new(packet, vec![])).unwrap();
+        let received = recv_msg(&mut buffer).unwrap();
+        let error = received.get_packet().get_error().clone().unwrap();
+        assert_eq!(error, MsgError::UnknownComponent(42));
+        assert_eq!(error.get_code(), ErrorCode::UnknownComponent);
+        assert_eq!(error.get_code() as u16, 9);
+    }
+
+    #[test]
+    fn unknown_component_is_invalid_command_without_error_codes() {
+        let error = Session::default().compatible_error(MsgError::UnknownComponent(42));
+        assert!(matches!(error, MsgError::InvalidCommand(_)));
+    }
+}
@@ -0,0 +1,739 @@
+//! Typed requests and responses.
+//!
+//! On the wire a message is still a `MsgCommand` followed by a list of payloads, this module
+//! is the only place that knows which payload sits at which position. Handlers match on
+//! `Request`, and a payload that doesn't decode is rejected here with
+//! `MsgError::InvalidCommand` instead of reaching them.
+
+use crate::{
+    field::{
+        ComponentList, CpuStatus, GpuStatus, MemStatus, PowerSupplyStatus, StorageStatus,
+        capability::Capabilities,
+        category::Category,
+        cores::{CoreState, TargetCoreMask},
+        event::Event,
+        fan_speed::{FanIndex, FanSpeed, FanValues, TargetFanSpeed},
+        freq::TargetFreq,
+        limit::GpuLimits,
+        net_speed::NetSpeed,
+        power::TargetPower,
+        subscription::Subscription,
+        unit::Milliwatts,
+    },
+    proto::{MsgBody, MsgCommand, MsgError, MsgMode, MsgPacket, ProtoError},
+};
+use bincode::{Decode, Encode};
+
+type Result<T> = std::result::Result<T, ProtoError>;
+
+/// First protocol version with the power limit of SetPowerLimit in mW, version 2 sent W
+pub const MILLIWATT_POWER_VERSION: u8 = 3;
+
+#[derive(Debug, Clone)]
+pub enum Request {
+    GetComponentList,
+    GetStatus,
+    GetGpuLimits,
+    GetFanSpeed(FanIndex),                  // [fan]
+    SetFreq(TargetFreq),                    // [target], in MHz
+    SetFanSpeed(FanValues<TargetFanSpeed>), // [fan, duty] or [All, cpu duty, gpu duty]
+    SetFanAuto(FanIndex),                   // [fan]
+    SetCoreMask(TargetCoreMask),            // [mask]
+    SetMemFreq(TargetFreq),                 // [target], in MHz
+    SetPowerLimit(TargetPower),             // [target]
+    Subscribe(Subscription),                // [subscription]
+    ResetGpuLimits,
+    Unsubscribe,
+    GetCapabilities,
+}
+
+/// Status of a component, its type follows the category of the component
+#[derive(Debug, Clone)]
+pub enum Status {
+    Cpu(CpuStatus),
+    Gpu(GpuStatus),
+    Network(NetSpeed),
+    Memory(MemStatus),
+    Storage(StorageStatus),
+    PowerSupply(PowerSupplyStatus),
+}
+
+#[derive(Debug, Clone)]
+pub enum Response {
+    ComponentList(ComponentList),  // GetComponentList
+    Status(Status),                // GetStatus
+    FanSpeed(FanValues<FanSpeed>), // GetFanSpeed, [fan, speed] or [All, cpu speed, gpu speed]
+    GpuLimits(GpuLimits),          // GetGpuLimits and every command changing them
+    Freq(TargetFreq),              // SetFreq, the limits actually applied
+    CoreState(CoreState),          // SetCoreMask
+    Done,                          // Commands without a result, no payload
+    Event(Event),                  // Only pushed as a Notify
+    Capabilities(Capabilities),    // GetCapabilities
+}
+
+fn encode<T: Encode>(value: &T) -> Result<Vec<u8>> {
+    Ok(bincode::encode_to_vec(value, bincode::config::standard())?)
+}
+
+// Decode the payload at `index`, the error says which one was wrong
+fn decode_at<T: Decode<()>>(
+    command: &MsgCommand,
+    payload: &[Vec<u8>],
+    index: usize,
+) -> std::result::Result<T, String> {
+    let data = payload
+        .get(index)
+        .ok_or_else(|| format!("{} is missing payload {}", command, index))?;
+    bincode::decode_from_slice(data, crate::field::DECODE_CONFIG)
+        .map(|(value, _)| value)
+        .map_err(|e| format!("{} payload {}: {}", command, index, e))
+}
+
+// [fan, value] or [All, cpu value, gpu value]
+fn encode_fan_values<T: Encode>(values: &FanValues<T>) -> Result<Vec<Vec<u8>>> {
+    let mut payload = vec![encode(&values.get_index())?];
+    for value in values.values() {
+        payload.push(encode(value)?);
+    }
+    Ok(payload)
+}
+
+fn decode_fan_values<T: Decode<()>>(
+    command: &MsgCommand,
+    payload: &[Vec<u8>],
+) -> std::result::Result<FanValues<T>, String> {
+    Ok(match decode_at(command, payload, 0)? {
+        FanIndex::Cpu => FanValues::Cpu(decode_at(command, payload, 1)?),
+        FanIndex::Gpu => FanValues::Gpu(decode_at(command, payload, 1)?),
+        FanIndex::All => FanValues::All {
+            cpu: decode_at(command, payload, 1)?,
+            gpu: decode_at(command, payload, 2)?,
+        },
+    })
+}
+
+impl Request {
+    pub fn get_command(&self) -> MsgCommand {
+        match self {
+            Request::GetComponentList => MsgCommand::GetComponentList,
+            Request::GetStatus => MsgCommand::GetStatus,
+            Request::GetFanSpeed(_) => MsgCommand::GetFanSpeed,
+            Request::GetGpuLimits => MsgCommand::GetGpuLimits,
+            Request::SetFreq(_) => MsgCommand::SetFreq,
+            Request::SetFanSpeed(_) => MsgCommand::SetFanSpeed,
+            Request::SetFanAuto(_) => MsgCommand::SetFanAuto,
+            Request::SetCoreMask(_) => MsgCommand::SetCoreMask,
+            Request::SetMemFreq(_) => MsgCommand::SetMemFreq,
+            Request::SetPowerLimit(_) => MsgCommand::SetPowerLimit,
+            Request::ResetGpuLimits => MsgCommand::ResetGpuLimits,
+            Request::Subscribe(_) => MsgCommand::Subscribe,
+            Request::Unsubscribe => MsgCommand::Unsubscribe,
+            Request::GetCapabilities => MsgCommand::GetCapabilities,
+        }
+    }
+
+    /// The request in the units of protocol `version`, for a daemon older than this build
+    pub fn for_version(self, version: u8) -> Self {
+        match self {
+            Request::SetPowerLimit(target) if version < MILLIWATT_POWER_VERSION => {
+                let watts = target.get_max().as_watts() as u64;
+                Request::SetPowerLimit(TargetPower::new(Milliwatts::new(watts)))
+            }
+            request => request,
+        }
+    }
+
+    /// A request from a peer speaking protocol `version`, in the units of this build
+    pub fn from_version(self, version: u8) -> Self {
+        match self {
+            Request::SetPowerLimit(target) if version < MILLIWATT_POWER_VERSION => {
+                let watts = target.get_max().get_value();
+                Request::SetPowerLimit(TargetPower::new(Milliwatts::from_watts(watts)))
+            }
+            request => request,
+        }
+    }
+
+    /// Whether the request changes the hardware, see `MsgCommand::is_control`
+    pub fn is_control(&self) -> bool {
+        self.get_command().is_control()
+    }
+
+    pub fn encode_payload(&self) -> Result<Vec<Vec<u8>>> {
+        Ok(match self {
+            Request::GetComponentList
+            | Request::GetStatus
+            | Request::GetGpuLimits
+            | Request::ResetGpuLimits
+            | Request::Unsubscribe
+            | Request::GetCapabilities => vec![],
+            Request::GetFanSpeed(fan) | Request::SetFanAuto(fan) => vec![encode(fan)?],
+            Request::SetFreq(target) | Request::SetMemFreq(target) => vec![encode(target)?],
+            Request::SetFanSpeed(duty) => encode_fan_values(duty)?,
+            Request::SetCoreMask(mask) => vec![encode(mask)?],
+            Request::SetPowerLimit(target) => vec![encode(target)?],
+            Request::Subscribe(subscription) => vec![encode(subscription)?],
+        })
+    }
+
+    pub fn decode(
+        command: &MsgCommand,
+        payload: &[Vec<u8>],
+    ) -> std::result::Result<Self, MsgError> {
+        Self::decode_payload(command, payload).map_err(MsgError::InvalidCommand)
+    }
+
+    fn decode_payload(
+        command: &MsgCommand,
+        payload: &[Vec<u8>],
+    ) -> std::result::Result<Self, String> {
+        let request = match command {
+            MsgCommand::GetComponentList => Request::GetComponentList,
+            MsgCommand::GetStatus => Request::GetStatus,
+            MsgCommand::GetFanSpeed => Request::GetFanSpeed(decode_at(command, payload, 0)?),
+            MsgCommand::GetGpuLimits => Request::GetGpuLimits,
+            MsgCommand::SetFreq => Request::SetFreq(decode_at(command, payload, 0)?),
+            MsgCommand::SetFanSpeed => Request::SetFanSpeed(decode_fan_values(command, payload)?),
+            MsgCommand::SetFanAuto => Request::SetFanAuto(decode_at(command, payload, 0)?),
+            MsgCommand::SetCoreMask => Request::SetCoreMask(decode_at(command, payload, 0)?),
+            MsgCommand::SetMemFreq => Request::SetMemFreq(decode_at(command, payload, 0)?),
+            MsgCommand::SetPowerLimit => Request::SetPowerLimit(decode_at(command, payload, 0)?),
+            MsgCommand::ResetGpuLimits => Request::ResetGpuLimits,
+            MsgCommand::Subscribe => Request::Subscribe(decode_at(command, payload, 0)?),
+            MsgCommand::Unsubscribe => Request::Unsubscribe,
+            MsgCommand::GetCapabilities => Request::GetCapabilities,
+            MsgCommand::Hello | MsgCommand::Event => {
+                return Err(format!("{} is not a request", command));
+            }
+        };
+        Ok(request)
+    }
+}
+
+impl Response {
+    pub fn encode_payload(&self) -> Result<Vec<Vec<u8>>> {
+        Ok(match self {
+            Response::ComponentList(list) => vec![encode(list)?],
+            Response::Status(status) => vec![match status {
+                Status::Cpu(status) => encode(status)?,
+                Status::Gpu(status) => encode(status)?,
+                Status::Network(status) => encode(status)?,
+                Status::Memory(status) => encode(status)?,
+                Status::Storage(status) => encode(status)?,
+                Status::PowerSupply(status) => encode(status)?,
+            }],
+            Response::FanSpeed(speed) => encode_fan_values(speed)?,
+            Response::GpuLimits(limits) => vec![encode(limits)?],
+            Response::Freq(applied) => vec![encode(applied)?],
+            Response::CoreState(state) => vec![encode(state)?],
+            Response::Done => vec![],
+            Response::Event(event) => vec![encode(event)?],
+            Response::Capabilities(capabilities) => vec![encode(capabilities)?],
+        })
+    }
+
+    /// Decode the reply to `command`, the type of a status depends on the category of the
+    /// component that sent it
+    pub fn decode(command: &MsgCommand, category: &Category, payload: &[Vec<u8>]) -> Result<Self> {
+        Self::decode_payload(command, category, payload).map_err(ProtoError::Parse)
+    }
+
+    fn decode_payload(
+        command: &MsgCommand,
+        category: &Category,
+        payload: &[Vec<u8>],
+    ) -> std::result::Result<Self, String> {
+        let response = match command {
+            MsgCommand::GetComponentList => {
+                Response::ComponentList(decode_at(command, payload, 0)?)
+            }
+            MsgCommand::GetStatus => Response::Status(match category {
+                Category::Cpu => Status::Cpu(decode_at(command, payload, 0)?),
+                Category::Gpu => Status::Gpu(decode_at(command, payload, 0)?),
+                Category::Network => Status::Network(decode_at(command, payload, 0)?),
+                Category::Memory => Status::Memory(decode_at(command, payload, 0)?),
+                Category::Storage => Status::Storage(decode_at(command, payload, 0)?),
+                Category::PowerSupply => Status::PowerSupply(decode_at(command, payload, 0)?),
+                Category::Fan => {
+                    return Err("a fan has no status".to_string());
+                }
+            }),
+            MsgCommand::GetFanSpeed => Response::FanSpeed(decode_fan_values(command, payload)?),
+            MsgCommand::GetGpuLimits
+            | MsgCommand::SetMemFreq
+            | MsgCommand::SetPowerLimit
+            | MsgCommand::ResetGpuLimits => Response::GpuLimits(decode_at(command, payload, 0)?),
+            MsgCommand::SetFreq => Response::Freq(decode_at(command, payload, 0)?),
+            MsgCommand::SetCoreMask => Response::CoreState(decode_at(command, payload, 0)?),
+            MsgCommand::Event => Response::Event(decode_at(command, payload, 0)?),
+            MsgCommand::GetCapabilities => Response::Capabilities(decode_at(command, payload, 0)?),
+            // older daemons echo the request back for the fan commands, nothing to read
+            MsgCommand::SetFanSpeed
+            | MsgCommand::SetFanAuto
+            | MsgCommand::Subscribe
+            | MsgCommand::Unsubscribe => Response::Done,
+            MsgCommand::Hello => {
+                return Err("Hello outside the handshake".to_string());
+            }
+        };
+        Ok(response)
+    }
+}
+
+impl MsgBody {
+    /// A request to the component `id_num`, the sequence is stamped when it is sent
+    pub fn request(id_num: u8, request: &Request) -> Result<Self> {
+        let packet = MsgPacket::new(MsgMode::Request, None, 0, id_num, request.get_command());
+        Ok(MsgBody::new(packet, request.encode_payload()?))
+    }
+
+    /// A request built by `request`, re-encoded for a daemon speaking protocol `version`.
+    /// Only requests carrying a value whose unit changed since are decoded again
+    pub fn request_for_version(self, version: u8) -> Result<Self> {
+        if version >= MILLIWATT_POWER_VERSION
+            || *self.get_packet().get_command() != MsgCommand::SetPowerLimit
+        {
+            return Ok(self);
+        }
+        let request = Request::decode(self.get_packet().get_command(), self.get_payload())
+            .map_err(|e| ProtoError::Parse(e.to_string()))?
+            .for_version(version);
+        Ok(MsgBody::new(
+            self.get_packet().clone(),
+            request.encode_payload()?,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::{
+        desc::Desc,
+        freq::Freq,
+        mem::MemUsage,
+        power::{Power, PowerState},
+        power_supply::PowerSource,
+        storage::DriveTemp,
+        subscription::Topic,
+        temp::Temp,
+        throttle::{Throttle, ThrottleReason},
+        unit::{Megahertz, MilliCelsius},
+        usage::Usage,
+    };
+    use std::collections::HashMap;
+
+    // Decoding what was encoded gives the same request, it encodes to the same bytes again
+    fn request_round_trip(request: Request) -> Request {
+        let command = request.get_command();
+        let payload = request.encode_payload().unwrap();
+        let decoded = Request::decode(&command, &payload).unwrap();
+        assert_eq!(decoded.get_command(), command);
+        assert_eq!(decoded.encode_payload().unwrap(), payload);
+        decoded
+    }
+
+    fn response_round_trip(
+        command: MsgCommand,
+        category: Category,
+        response: Response,
+    ) -> Response {
+        let payload = response.encode_payload().unwrap();
+        let decoded = Response::decode(&command, &category, &payload).unwrap();
+        assert_eq!(decoded.encode_payload().unwrap(), payload);
+        decoded
+    }
+
+    fn target_freq() -> TargetFreq {
+        TargetFreq::new(Megahertz::new(800), Megahertz::new(4200))
+    }
+
+    #[test]
+    fn get_component_list() {
+        let request = request_round_trip(Request::GetComponentList);
+        assert!(matches!(request, Request::GetComponentList));
+    }
+
+    #[test]
+    fn get_status() {
+        assert!(matches!(
+            request_round_trip(Request::GetStatus),
+            Request::GetStatus
+        ));
+    }
+
+    #[test]
+    fn get_gpu_limits() {
+        assert!(matches!(
+            request_round_trip(Request::GetGpuLimits),
+            Request::GetGpuLimits
+        ));
+    }
+
+    #[test]
+    fn get_fan_speed() {
+        assert!(matches!(
+            request_round_trip(Request::GetFanSpeed(FanIndex::Gpu)),
+            Request::GetFanSpeed(FanIndex::Gpu)
+        ));
+    }
+
+    #[test]
+    fn set_freq() {
+        let Request::SetFreq(target) = request_round_trip(Request::SetFreq(target_freq())) else {
+            panic!("not SetFreq");
+        };
+        assert_eq!(target.get_min(), Megahertz::new(800));
+        assert_eq!(target.get_max(), Megahertz::new(4200));
+    }
+
+    #[test]
+    fn set_fan_speed() {
+        let Request::SetFanSpeed(FanValues::Cpu(duty)) = request_round_trip(Request::SetFanSpeed(
+            FanValues::Cpu(TargetFanSpeed::new(40)),
+        )) else {
+            panic!("not SetFanSpeed for the cpu fan");
+        };
+        assert_eq!(duty.get_duty(), 40);
+        let Request::SetFanSpeed(FanValues::All { cpu, gpu }) =
+            request_round_trip(Request::SetFanSpeed(FanValues::All {
+                cpu: TargetFanSpeed::new(30),
+                gpu: TargetFanSpeed::new(70),
+            }))
+        else {
+            panic!("not SetFanSpeed for both fans");
+        };
+        assert_eq!(cpu.get_duty(), 30);
+        assert_eq!(gpu.get_duty(), 70);
+    }
+
+    #[test]
+    fn set_fan_auto() {
+        assert!(matches!(
+            request_round_trip(Request::SetFanAuto(FanIndex::All)),
+            Request::SetFanAuto(FanIndex::All)
+        ));
+    }
+
+    #[test]
+    fn set_core_mask() {
+        let mask = TargetCoreMask::new(vec![true, false, true]);
+        assert!(matches!(
+            request_round_trip(Request::SetCoreMask(mask)),
+            Request::SetCoreMask(_)
+        ));
+    }
+
+    #[test]
+    fn set_mem_freq() {
+        assert!(matches!(
+            request_round_trip(Request::SetMemFreq(target_freq())),
+            Request::SetMemFreq(_)
+        ));
+    }
+
+    #[test]
+    fn set_power_limit() {
+        let target = TargetPower::new(Milliwatts::new(65500));
+        let Request::SetPowerLimit(target) = request_round_trip(Request::SetPowerLimit(target))
+        else {
+            panic!("not SetPowerLimit");
+        };
+        assert_eq!(target.get_max(), Milliwatts::new(65500));
+    }
+
+    #[test]
+    fn power_limit_from_version_2() {
+        let watts = Request::SetPowerLimit(TargetPower::new(Milliwatts::new(65)));
+        let Request::SetPowerLimit(target) = watts.from_version(2) else {
+            panic!("not SetPowerLimit");
+        };
+        assert_eq!(target.get_max(), Milliwatts::new(65000));
+        // a peer can send any value, it must not overflow
+        let huge = Request::SetPowerLimit(TargetPower::new(Milliwatts::new(u64::MAX)));
+        let Request::SetPowerLimit(target) = huge.from_version(2) else {
+            panic!("not SetPowerLimit");
+        };
+        assert_eq!(target.get_max(), Milliwatts::new(u64::MAX));
+    }
+
+    #[test]
+    fn subscribe() {
+        let topic = Topic::new(3, &Request::GetFanSpeed(FanIndex::All)).unwrap();
+        let subscription = Subscription::new(vec![topic], 500, true);
+        let Request::Subscribe(subscription) = request_round_trip(Request::Subscribe(subscription))
+        else {
+            panic!("not Subscribe");
+        };
+        assert_eq!(subscription.get_topics().len(), 1);
+        assert_eq!(subscription.get_min_interval(), 500);
+        assert!(subscription.wants_events());
+    }
+
+    #[test]
+    fn reset_gpu_limits() {
+        assert!(matches!(
+            request_round_trip(Request::ResetGpuLimits),
+            Request::ResetGpuLimits
+        ));
+    }
+
+    #[test]
+    fn unsubscribe() {
+        assert!(matches!(
+            request_round_trip(Request::Unsubscribe),
+            Request::Unsubscribe
+        ));
+    }
+
+    #[test]
+    fn get_capabilities() {
+        assert!(matches!(
+            request_round_trip(Request::GetCapabilities),
+            Request::GetCapabilities
+        ));
+    }
+
+    #[test]
+    fn bad_request_payload_is_invalid_command() {
+        // missing the fan index
+        assert!(matches!(
+            Request::decode(&MsgCommand::GetFanSpeed, &[]),
+            Err(MsgError::InvalidCommand(_))
+        ));
+        // an index beyond FanIndex
+        assert!(matches!(
+            Request::decode(&MsgCommand::SetFanAuto, &[vec![9]]),
+            Err(MsgError::InvalidCommand(_))
+        ));
+        // both fans but only the cpu duty
+        let payload = vec![
+            encode(&FanIndex::All).unwrap(),
+            encode(&TargetFanSpeed::new(50)).unwrap(),
+        ];
+        assert!(matches!(
+            Request::decode(&MsgCommand::SetFanSpeed, &payload),
+            Err(MsgError::InvalidCommand(_))
+        ));
+        for command in [MsgCommand::Hello, MsgCommand::Event] {
+            assert!(matches!(
+                Request::decode(&command, &[]),
+                Err(MsgError::InvalidCommand(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn component_list_response() {
+        let mut list = HashMap::new();
+        // a single entry, the order of a HashMap isn't stable across encodes
+        list.insert(1, Desc::new(Category::Fan, 1, "fan"));
+        let Response::ComponentList(list) = response_round_trip(
+            MsgCommand::GetComponentList,
+            Category::default(),
+            Response::ComponentList(ComponentList(list)),
+        ) else {
+            panic!("not ComponentList");
+        };
+        assert_eq!(list.0.len(), 1);
+        assert_eq!(list.0[&1].get_desc(), "fan");
+    }
+
+    #[test]
+    fn status_responses() {
+        let cpu = CpuStatus {
+            freq: Freq::new(vec![Some(Megahertz::new(3000)), None]),
+            usage: Usage::new(vec![Some(12.5), None]),
+            power: Power::new(Milliwatts::new(15000)),
+            temp: Temp::new(MilliCelsius::new(65000)),
+            throttle: Throttle::new(vec![1, 0], 2, vec![ThrottleReason::Thermal]),
+            cores: CoreState::new(vec![true, false], vec![false, true]),
+        };
+        let gpu = GpuStatus {
+            power_state: PowerState::Suspended,
+            rc6_residency: Some(80.0),
+            ..Default::default()
+        };
+        let mem = MemStatus {
+            usage: MemUsage::new(16, 8, 4, 2, 1),
+            ..Default::default()
+        };
+        let storage = StorageStatus {
+            drives: vec![DriveTemp::new(
+                "nvme0",
+                "model",
+                Temp::new(MilliCelsius::new(45000)),
+                vec![Temp::new(MilliCelsius::new(50000))],
+                Some(Temp::new(MilliCelsius::new(80000))),
+                None,
+            )],
+        };
+        let power_supply = PowerSupplyStatus {
+            source: PowerSource::UsbPd,
+            adapter_power: Some(Power::new(Milliwatts::from_watts(100))),
+            supplies: vec!["ucsi-source-psy-USBC000:001".to_string()],
+        };
+        let statuses = [
+            (Category::Cpu, Status::Cpu(cpu)),
+            (Category::Gpu, Status::Gpu(gpu)),
+            (Category::Network, Status::Network(NetSpeed::default())),
+            (Category::Memory, Status::Memory(mem)),
+            (Category::Storage, Status::Storage(storage)),
+            (Category::PowerSupply, Status::PowerSupply(power_supply)),
+        ];
+        for (category, status) in statuses {
+            let decoded = response_round_trip(
+                MsgCommand::GetStatus,
+                category.clone(),
+                Response::Status(status),
+            );
+            let matched = matches!(
+                (&category, decoded),
+                (Category::Cpu, Response::Status(Status::Cpu(_)))
+                    | (Category::Gpu, Response::Status(Status::Gpu(_)))
+                    | (Category::Network, Response::Status(Status::Network(_)))
+                    | (Category::Memory, Response::Status(Status::Memory(_)))
+                    | (Category::Storage, Response::Status(Status::Storage(_)))
+                    | (
+                        Category::PowerSupply,
+                        Response::Status(Status::PowerSupply(_))
+                    )
+            );
+            assert!(matched, "{:?} status decoded as another type", category);
+        }
+    }
+
+    #[test]
+    fn fan_speed_response() {
+        let speed = FanValues::All {
+            cpu: FanSpeed::new(2400),
+            gpu: FanSpeed::new(3100),
+        };
+        let Response::FanSpeed(FanValues::All { cpu, gpu }) = response_round_trip(
+            MsgCommand::GetFanSpeed,
+            Category::Fan,
+            Response::FanSpeed(speed),
+        ) else {
+            panic!("not FanSpeed for both fans");
+        };
+        assert_eq!(cpu.get_rpm(), 2400);
+        assert_eq!(gpu.get_rpm(), 3100);
+    }
+
+    #[test]
+    fn gpu_limits_response() {
+        let limits = GpuLimits::new(
+            Power::new(Milliwatts::from_watts(80)),
+            Power::new(Milliwatts::from_watts(35)),
+            Power::new(Milliwatts::from_watts(115)),
+            Power::new(Milliwatts::from_watts(80)),
+            Some(target_freq()),
+            None,
+        );
+        // every command changing the limits answers with them
+        for command in [
+            MsgCommand::GetGpuLimits,
+            MsgCommand::SetMemFreq,
+            MsgCommand::SetPowerLimit,
+            MsgCommand::ResetGpuLimits,
+        ] {
+            let Response::GpuLimits(decoded) =
+                response_round_trip(command, Category::Gpu, Response::GpuLimits(limits.clone()))
+            else {
+                panic!("not GpuLimits");
+            };
+            assert_eq!(
+                decoded.get_max_power_limit().get_value(),
+                Milliwatts::from_watts(115)
+            );
+            assert!(decoded.get_mem_clock().is_none());
+        }
+    }
+
+    #[test]
+    fn freq_response() {
+        assert!(matches!(
+            response_round_trip(
+                MsgCommand::SetFreq,
+                Category::Cpu,
+                Response::Freq(target_freq())
+            ),
+            Response::Freq(_)
+        ));
+    }
+
+    #[test]
+    fn core_state_response() {
+        let state = CoreState::new(vec![true, true], vec![false, true]);
+        assert!(matches!(
+            response_round_trip(
+                MsgCommand::SetCoreMask,
+                Category::Cpu,
+                Response::CoreState(state)
+            ),
+            Response::CoreState(_)
+        ));
+    }
+
+    #[test]
+    fn done_response() {
+        for command in [
+            MsgCommand::SetFanSpeed,
+            MsgCommand::SetFanAuto,
+            MsgCommand::Subscribe,
+            MsgCommand::Unsubscribe,
+        ] {
+            assert!(matches!(
+                response_round_trip(command, Category::Fan, Response::Done),
+                Response::Done
+            ));
+        }
+    }
+
+    #[test]
+    fn event_response() {
+        let event = Event::PowerSourceChanged {
+            from: PowerSource::Mains,
+            to: PowerSource::Battery,
+        };
+        let Response::Event(Event::PowerSourceChanged { from, to }) = response_round_trip(
+            MsgCommand::Event,
+            Category::PowerSupply,
+            Response::Event(event),
+        ) else {
+            panic!("not a power source event");
+        };
+        assert_eq!(from, PowerSource::Mains);
+        assert_eq!(to, PowerSource::Battery);
+    }
+
+    #[test]
+    fn capabilities_response() {
+        let capabilities = Capabilities::new(vec![MsgCommand::GetStatus, MsgCommand::SetFreq]);
+        let Response::Capabilities(capabilities) = response_round_trip(
+            MsgCommand::GetCapabilities,
+            Category::Cpu,
+            Response::Capabilities(capabilities),
+        ) else {
+            panic!("not Capabilities");
+        };
+        assert!(capabilities.supports(&MsgCommand::SetFreq));
+        assert!(!capabilities.supports(&MsgCommand::SetFanSpeed));
+    }
+
+    #[test]
+    fn bad_response_payload_is_parse_error() {
+        assert!(matches!(
+            Response::decode(&MsgCommand::GetStatus, &Category::Cpu, &[]),
+            Err(ProtoError::Parse(_))
+        ));
+        assert!(matches!(
+            Response::decode(&MsgCommand::GetStatus, &Category::Fan, &[vec![]]),
+            Err(ProtoError::Parse(_))
+        ));
+        assert!(matches!(
+            Response::decode(&MsgCommand::Hello, &Category::Cpu, &[]),
+            Err(ProtoError::Parse(_))
+        ));
+    }
+}
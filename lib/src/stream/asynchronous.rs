@@ -0,0 +1,128 @@
+//! Tokio versions of the socket types, see `proto::codec` for reading and writing messages
+//! on them.
+
+#[cfg(unix)]
+use crate::stream::apply_permissions;
+use crate::stream::{Result, SocketPermissions, StreamError, listener_options, to_socket_name};
+use interprocess::local_socket::tokio::{Listener, RecvHalf, SendHalf, Stream, prelude::*};
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+pub struct AsyncSocketStream(Stream);
+
+impl AsyncSocketStream {
+    pub async fn new(name: &str) -> Result<Self> {
+        let stream = Stream::connect(to_socket_name(name)?).await?;
+        Ok(AsyncSocketStream(stream))
+    }
+
+    /// Split into halves that can be used from different tasks
+    pub fn split(self) -> (AsyncSocketReader, AsyncSocketWriter) {
+        let (reader, writer) = self.0.split();
+        (AsyncSocketReader(reader), AsyncSocketWriter(writer))
+    }
+}
+
+// e.g. to read the peer's credentials
+#[cfg(unix)]
+impl std::os::fd::AsFd for AsyncSocketStream {
+    fn as_fd(&self) -> std::os::fd::BorrowedFd<'_> {
+        match &self.0 {
+            Stream::UdSocket(stream) => stream.as_fd(),
+        }
+    }
+}
+
+impl AsyncRead for AsyncSocketStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.0).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for AsyncSocketStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        Pin::new(&mut self.0).poll_write(cx, buf)
+    }
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.0).poll_flush(cx)
+    }
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.0).poll_shutdown(cx)
+    }
+}
+
+pub struct AsyncSocketReader(RecvHalf);
+
+impl AsyncRead for AsyncSocketReader {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.0).poll_read(cx, buf)
+    }
+}
+
+pub struct AsyncSocketWriter(SendHalf);
+
+impl AsyncWrite for AsyncSocketWriter {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        Pin::new(&mut self.0).poll_write(cx, buf)
+    }
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.0).poll_flush(cx)
+    }
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.0).poll_shutdown(cx)
+    }
+}
+
+pub struct AsyncStreamListener(Listener);
+
+impl AsyncStreamListener {
+    /// Must be called from within a tokio runtime
+    pub fn new(socket_name: &str) -> Result<Self> {
+        Self::with_permissions(socket_name, &SocketPermissions::default())
+    }
+
+    /// Listen on `socket_name`, a socket file gets the permissions. Must be called from
+    /// within a tokio runtime
+    pub fn with_permissions(socket_name: &str, permissions: &SocketPermissions) -> Result<Self> {
+        let listener = listener_options(socket_name)?.create_tokio()?;
+        #[cfg(unix)]
+        apply_permissions(socket_name, permissions)?;
+        Ok(AsyncStreamListener(listener))
+    }
+
+    /// A socket that is already bound and listening, e.g. passed by systemd. Must be called
+    /// from within a tokio runtime
+    #[cfg(unix)]
+    pub fn from_fd(fd: std::os::fd::OwnedFd) -> Result<Self> {
+        use interprocess::os::unix::uds_local_socket::tokio::Listener as UdsListener;
+        let listener = UdsListener::try_from(fd)?;
+        Ok(AsyncStreamListener(Listener::from(listener)))
+    }
+
+    /// Cancel safe, a connection is never lost when the future is dropped
+    pub async fn accept(&self) -> Result<AsyncSocketStream> {
+        match self.0.accept().await {
+            Ok(stream) => Ok(AsyncSocketStream(stream)),
+            Err(err) => Err(StreamError::Io(err)),
+        }
+    }
+}
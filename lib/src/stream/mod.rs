@@ -0,0 +1,204 @@
+pub mod asynchronous;
+
+use interprocess::local_socket::{
+    GenericFilePath, GenericNamespaced, ListenerOptions, Name, RecvHalf, SendHalf, Stream,
+    prelude::*,
+};
+use std::io::prelude::*;
+#[derive(Debug, thiserror::Error)]
+pub enum StreamError {
+    #[error("IO Error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Unknown Error: {0}")]
+    Other(String),
+}
+
+type Result<T> = std::result::Result<T, StreamError>;
+
+/// Something a message can be read from, a whole stream or its receiving half
+pub trait StreamRead {
+    fn read(&mut self, length: usize) -> Result<Vec<u8>>;
+}
+
+/// Something a message can be written to, a whole stream or its sending half
+pub trait StreamWrite {
+    fn write(&mut self, buffer: &[u8]) -> Result<()>;
+}
+
+fn read_from(reader: &mut impl Read, length: usize) -> Result<Vec<u8>> {
+    let mut msg = vec![0; length]; // Pre-allocate a buffer of the specified length
+    reader.read_exact(&mut msg)?;
+    Ok(msg)
+}
+
+fn write_to(writer: &mut impl Write, buffer: &[u8]) -> Result<()> {
+    let writren_bytes = writer.write(buffer)?;
+    if writren_bytes == buffer.len() {
+        Ok(())
+    } else {
+        Err(StreamError::Other(format!(
+            "Failed to write all bytes. Expected: {}, Written: {}",
+            buffer.len(),
+            writren_bytes
+        )))
+    }
+}
+
+/// Whether the socket name is a file path, e.g. /run/clevo-controller.sock, rather than a
+/// name in the socket namespace
+pub fn is_socket_path(name: &str) -> bool {
+    name.starts_with('/')
+}
+
+// A path is used as is, other names are namespaced where the platform has it and a file path
+// otherwise
+fn to_socket_name(name: &str) -> Result<Name<'_>> {
+    if !is_socket_path(name) && GenericNamespaced::is_supported() {
+        Ok(name.to_ns_name::<GenericNamespaced>()?)
+    } else {
+        Ok(name.to_fs_name::<GenericFilePath>()?)
+    }
+}
+
+/// Mode and group of a socket file, a namespaced socket has none and anyone can connect
+#[derive(Debug, Clone, Copy)]
+pub struct SocketPermissions {
+    mode: u32,        // e.g. 0o660 for the owner and group only
+    gid: Option<u32>, // The group of the daemon if None
+}
+
+impl Default for SocketPermissions {
+    fn default() -> Self {
+        Self {
+            mode: 0o666,
+            gid: None,
+        }
+    }
+}
+
+impl SocketPermissions {
+    pub fn new(mode: u32, gid: Option<u32>) -> Self {
+        Self { mode, gid }
+    }
+    pub fn get_mode(&self) -> u32 {
+        self.mode
+    }
+    pub fn get_gid(&self) -> Option<u32> {
+        self.gid
+    }
+}
+
+// Options to listen on `name`. A socket file left by a daemon that didn't exit cleanly is
+// removed first, one that someone still answers on is an error. A socket file is created for
+// the owner only, nobody may connect before `apply_permissions`
+fn listener_options(name: &str) -> Result<ListenerOptions<'_>> {
+    #[allow(unused_mut)]
+    let mut options = ListenerOptions::new().name(to_socket_name(name)?);
+    #[cfg(unix)]
+    if is_socket_path(name) {
+        use interprocess::os::unix::local_socket::ListenerOptionsExt;
+        use std::os::unix::{fs::FileTypeExt, net::UnixStream};
+        options = options.mode(0o600);
+        match std::fs::symlink_metadata(name) {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+            Ok(metadata) if !metadata.file_type().is_socket() => {
+                return Err(StreamError::Other(format!("{} is not a socket", name)));
+            }
+            Ok(_) => match UnixStream::connect(name) {
+                Ok(_) => {
+                    return Err(StreamError::Other(format!(
+                        "{} is in use by another process",
+                        name
+                    )));
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::ConnectionRefused => {
+                    println!("Removing stale socket {}", name);
+                    std::fs::remove_file(name)?;
+                }
+                Err(e) => return Err(e.into()),
+            },
+        }
+    }
+    Ok(options)
+}
+
+// The file only exists once the listener is created, its permissions are set afterwards
+#[cfg(unix)]
+fn apply_permissions(name: &str, permissions: &SocketPermissions) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    if is_socket_path(name) {
+        std::fs::set_permissions(name, std::fs::Permissions::from_mode(permissions.mode))?;
+        std::os::unix::fs::chown(name, None, permissions.gid)?;
+    }
+    Ok(())
+}
+
+pub struct SocketStream(Stream);
+
+impl SocketStream {
+    pub fn new(name: &str) -> Result<Self> {
+        let stream = Stream::connect(to_socket_name(name)?)?;
+        Ok(SocketStream(stream))
+    }
+
+    /// Split into halves that can be used from different threads
+    pub fn split(self) -> (SocketReader, SocketWriter) {
+        let (reader, writer) = self.0.split();
+        (SocketReader(reader), SocketWriter(writer))
+    }
+}
+
+impl StreamRead for SocketStream {
+    fn read(&mut self, length: usize) -> Result<Vec<u8>> {
+        read_from(&mut self.0, length)
+    }
+}
+
+impl StreamWrite for SocketStream {
+    fn write(&mut self, buffer: &[u8]) -> Result<()> {
+        write_to(&mut self.0, buffer)
+    }
+}
+
+pub struct SocketReader(RecvHalf);
+
+impl StreamRead for SocketReader {
+    fn read(&mut self, length: usize) -> Result<Vec<u8>> {
+        read_from(&mut self.0, length)
+    }
+}
+
+pub struct SocketWriter(SendHalf);
+
+impl StreamWrite for SocketWriter {
+    fn write(&mut self, buffer: &[u8]) -> Result<()> {
+        write_to(&mut self.0, buffer)
+    }
+}
+
+pub struct StreamListener(interprocess::local_socket::Listener);
+
+impl StreamListener {
+    pub fn new(socket_name: &str) -> Result<Self> {
+        Self::with_permissions(socket_name, &SocketPermissions::default())
+    }
+
+    /// Listen on `socket_name`, a socket file gets the permissions
+    pub fn with_permissions(socket_name: &str, permissions: &SocketPermissions) -> Result<Self> {
+        let listener = listener_options(socket_name)?.create_sync()?;
+        #[cfg(unix)]
+        apply_permissions(socket_name, permissions)?;
+        Ok(StreamListener(listener))
+    }
+
+    pub fn accept(&mut self) -> Result<SocketStream> {
+        match self.0.accept() {
+            Ok(stream) => {
+                let stream_client = SocketStream(stream);
+                Ok(stream_client)
+            }
+            Err(err) => Err(StreamError::Io(err)),
+        }
+    }
+}